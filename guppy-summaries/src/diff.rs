@@ -8,7 +8,10 @@
 //! The main entry point is `SummaryDiff`, which can be created through the `diff` method on
 //! summaries or through `SummaryDiff::new`.
 
-pub use crate::report::SummaryReport;
+pub use crate::{
+    render::{HtmlReport, MarkdownReport},
+    report::SummaryReport,
+};
 use crate::{
     PackageInfo, PackageMap, PackageStatus, SummaryId, SummarySource, SummaryWithMetadata,
 };
@@ -473,7 +476,8 @@ mod removed_impl {
 /// A tag representing `SummaryDiffStatus` except with no data attached.
 ///
 /// The order is significant: it is what's used as the default order in reports.
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SummaryDiffTag {
     /// This package was added.
     Added,
@@ -113,6 +113,8 @@
 #![warn(missing_docs)]
 
 pub mod diff;
+// render::{HtmlReport, MarkdownReport} are exported through the diff module.
+mod render;
 // report::SummaryReport is exported through the diff module.
 mod report;
 mod summary;
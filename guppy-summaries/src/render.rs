@@ -0,0 +1,508 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Markdown and HTML renderings of summary reports.
+
+use crate::{
+    diff::{PackageDiff, SummaryDiffStatus, SummaryDiffTag},
+    report::SummaryReport,
+    PackageStatus, SummaryId, SummarySource,
+};
+use semver::Version;
+use std::fmt;
+
+/// A Markdown rendering of a `SummaryReport`, returned by
+/// [`SummaryReport::markdown`](SummaryReport::markdown).
+///
+/// Target and host packages are shown in collapsible `<details>` sections, with one table for each
+/// kind of change. The output uses GitHub-flavored Markdown, and is meant to be posted as a code
+/// review comment.
+///
+/// This can be generated or written to a file through `fmt::Display`.
+#[derive(Clone, Debug)]
+pub struct MarkdownReport<'r, 'a, 'b> {
+    report: &'r SummaryReport<'a, 'b>,
+}
+
+impl<'r, 'a, 'b> MarkdownReport<'r, 'a, 'b> {
+    pub(crate) fn new(report: &'r SummaryReport<'a, 'b>) -> Self {
+        Self { report }
+    }
+}
+
+impl<'r, 'a, 'b> fmt::Display for MarkdownReport<'r, 'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = Section::all(self.report);
+        if sections.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+
+        for section in &sections {
+            writeln!(f, "<details open>")?;
+            writeln!(
+                f,
+                "<summary><b>{}</b> ({})</summary>",
+                section.title,
+                section.counts()
+            )?;
+
+            for (tag, rows) in &section.groups {
+                writeln!(f)?;
+                writeln!(f, "#### {}", tag_name(*tag))?;
+                writeln!(f)?;
+                writeln!(f, "| Package | Version | Status | Source | Features |")?;
+                writeln!(f, "| --- | --- | --- | --- | --- |")?;
+                for row in rows {
+                    write!(f, "| ")?;
+                    row.write_package(f, &Markdown)?;
+                    write!(f, " | ")?;
+                    row.write_version(f, &Markdown)?;
+                    write!(f, " | ")?;
+                    row.write_status(f, &Markdown)?;
+                    write!(f, " | ")?;
+                    row.write_source(f, &Markdown)?;
+                    write!(f, " | ")?;
+                    row.write_features(f, &Markdown)?;
+                    writeln!(f, " |")?;
+                }
+            }
+
+            writeln!(f)?;
+            writeln!(f, "</details>")?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A self-contained HTML rendering of a `SummaryReport`, returned by
+/// [`SummaryReport::html`](SummaryReport::html).
+///
+/// The output is a complete HTML document with inline styles. Target and host packages are shown in
+/// collapsible `<details>` sections, with one table for each kind of change.
+///
+/// This can be generated or written to a file through `fmt::Display`.
+#[derive(Clone, Debug)]
+pub struct HtmlReport<'r, 'a, 'b> {
+    report: &'r SummaryReport<'a, 'b>,
+}
+
+impl<'r, 'a, 'b> HtmlReport<'r, 'a, 'b> {
+    pub(crate) fn new(report: &'r SummaryReport<'a, 'b>) -> Self {
+        Self { report }
+    }
+}
+
+static HTML_STYLE: &str = r#"body { font-family: sans-serif; margin: 2em; }
+summary { cursor: pointer; font-size: 1.2em; font-weight: bold; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #d0d7de; padding: 4px 8px; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+ins.feature-added { background: #dafbe1; text-decoration: none; }
+del.feature-removed { background: #ffebe9; }"#;
+
+impl<'r, 'a, 'b> fmt::Display for HtmlReport<'r, 'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "<!DOCTYPE html>")?;
+        writeln!(f, "<html lang=\"en\">")?;
+        writeln!(f, "<head>")?;
+        writeln!(f, "<meta charset=\"utf-8\">")?;
+        writeln!(f, "<title>Summary diff</title>")?;
+        writeln!(f, "<style>\n{}\n</style>", HTML_STYLE)?;
+        writeln!(f, "</head>")?;
+        writeln!(f, "<body>")?;
+
+        let sections = Section::all(self.report);
+        if sections.is_empty() {
+            writeln!(f, "<p>No changes.</p>")?;
+        }
+
+        for section in &sections {
+            writeln!(f, "<details open>")?;
+            writeln!(
+                f,
+                "<summary>{} ({})</summary>",
+                section.title,
+                section.counts()
+            )?;
+
+            for (tag, rows) in &section.groups {
+                writeln!(f, "<h4>{}</h4>", tag_name(*tag))?;
+                writeln!(f, "<table>")?;
+                writeln!(
+                    f,
+                    "<thead><tr><th>Package</th><th>Version</th><th>Status</th><th>Source</th>\
+                     <th>Features</th></tr></thead>"
+                )?;
+                writeln!(f, "<tbody>")?;
+                for row in rows {
+                    write!(f, "<tr><td>")?;
+                    row.write_package(f, &Html)?;
+                    write!(f, "</td><td>")?;
+                    row.write_version(f, &Html)?;
+                    write!(f, "</td><td>")?;
+                    row.write_status(f, &Html)?;
+                    write!(f, "</td><td>")?;
+                    row.write_source(f, &Html)?;
+                    write!(f, "</td><td>")?;
+                    row.write_features(f, &Html)?;
+                    writeln!(f, "</td></tr>")?;
+                }
+                writeln!(f, "</tbody>")?;
+                writeln!(f, "</table>")?;
+            }
+
+            writeln!(f, "</details>")?;
+        }
+
+        writeln!(f, "</body>")?;
+        writeln!(f, "</html>")
+    }
+}
+
+fn tag_name(tag: SummaryDiffTag) -> &'static str {
+    match tag {
+        SummaryDiffTag::Added => "Added",
+        SummaryDiffTag::Modified => "Modified",
+        SummaryDiffTag::Removed => "Removed",
+    }
+}
+
+// ---
+// Format-independent representation of a report
+// ---
+
+/// The target or host section of a report, with changes grouped by tag.
+struct Section<'x> {
+    title: &'static str,
+    groups: Vec<(SummaryDiffTag, Vec<Row<'x>>)>,
+}
+
+impl<'x> Section<'x> {
+    /// Returns the sections with changes in them.
+    fn all(report: &'x SummaryReport<'_, '_>) -> Vec<Self> {
+        let sections = vec![
+            Self::new(
+                "Target packages",
+                &report.diff.target_packages,
+                &report.sorted_target,
+            ),
+            Self::new(
+                "Host packages",
+                &report.diff.host_packages,
+                &report.sorted_host,
+            ),
+        ];
+        sections
+            .into_iter()
+            .filter(|section| !section.groups.is_empty())
+            .collect()
+    }
+
+    fn new(
+        title: &'static str,
+        package_diff: &'x PackageDiff<'x>,
+        sorted: &'x [(&'x SummaryId, &'x SummaryDiffStatus<'x>)],
+    ) -> Self {
+        let mut groups: Vec<(SummaryDiffTag, Vec<Row<'x>>)> = vec![];
+        // sorted is ordered by tag first, so each group is a contiguous run.
+        for (summary_id, status) in sorted {
+            let row = Row::new(package_diff, summary_id, status);
+            match groups.last_mut() {
+                Some((tag, rows)) if *tag == status.tag() => rows.push(row),
+                _ => groups.push((status.tag(), vec![row])),
+            }
+        }
+        Self { title, groups }
+    }
+
+    fn counts(&self) -> String {
+        let counts: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(tag, rows)| format!("{} {}", rows.len(), tag_name(*tag).to_ascii_lowercase()))
+            .collect();
+        counts.join(", ")
+    }
+}
+
+/// A value that may have changed, along with the value it changed from.
+struct Change<T> {
+    old: Option<T>,
+    new: T,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum FeatureChange {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+/// A single row in a table.
+struct Row<'x> {
+    name: &'x str,
+    other_versions: Vec<&'x Version>,
+    version: Change<&'x Version>,
+    status: Change<PackageStatus>,
+    source: Change<&'x SummarySource>,
+    features: Vec<(FeatureChange, &'x str)>,
+}
+
+impl<'x> Row<'x> {
+    fn new(
+        package_diff: &'x PackageDiff<'x>,
+        summary_id: &'x SummaryId,
+        status: &'x SummaryDiffStatus<'x>,
+    ) -> Self {
+        let other_versions = package_diff
+            .unchanged
+            .get(summary_id.name.as_str())
+            .map(|unchanged_list| {
+                unchanged_list
+                    .iter()
+                    .map(|(version, _, _)| *version)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let (version, source, status, features) = match status {
+            SummaryDiffStatus::Added { info: package_info }
+            | SummaryDiffStatus::Removed {
+                old_info: package_info,
+            } => {
+                let features = package_info
+                    .features
+                    .iter()
+                    .map(|feature| (FeatureChange::Unchanged, feature.as_str()))
+                    .collect();
+                (
+                    Change {
+                        old: None,
+                        new: &summary_id.version,
+                    },
+                    Change {
+                        old: None,
+                        new: &summary_id.source,
+                    },
+                    Change {
+                        old: None,
+                        new: package_info.status,
+                    },
+                    features,
+                )
+            }
+            SummaryDiffStatus::Modified {
+                old_version,
+                old_source,
+                old_status,
+                new_status,
+                added_features,
+                removed_features,
+                unchanged_features,
+            } => {
+                let features = added_features
+                    .iter()
+                    .map(|feature| (FeatureChange::Added, *feature))
+                    .chain(
+                        removed_features
+                            .iter()
+                            .map(|feature| (FeatureChange::Removed, *feature)),
+                    )
+                    .chain(
+                        unchanged_features
+                            .iter()
+                            .map(|feature| (FeatureChange::Unchanged, *feature)),
+                    )
+                    .collect();
+                (
+                    Change {
+                        old: *old_version,
+                        new: &summary_id.version,
+                    },
+                    Change {
+                        old: *old_source,
+                        new: &summary_id.source,
+                    },
+                    Change {
+                        old: *old_status,
+                        new: *new_status,
+                    },
+                    features,
+                )
+            }
+        };
+
+        Self {
+            name: &summary_id.name,
+            other_versions,
+            version,
+            status,
+            source,
+            features,
+        }
+    }
+
+    fn write_package(&self, f: &mut fmt::Formatter<'_>, markup: &impl Markup) -> fmt::Result {
+        markup.code(f, self.name)?;
+        if !self.other_versions.is_empty() {
+            write!(f, " (other versions: ")?;
+            for (idx, version) in self.other_versions.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                markup.text(f, &version.to_string())?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+
+    fn write_version(&self, f: &mut fmt::Formatter<'_>, markup: &impl Markup) -> fmt::Result {
+        if let Some(old) = self.version.old {
+            markup.text(f, &old.to_string())?;
+            write!(f, " → ")?;
+        }
+        markup.text(f, &self.version.new.to_string())?;
+        match self.version.old {
+            Some(old) if old > self.version.new => {
+                write!(f, " ")?;
+                markup.strong(f, "(downgrade)")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn write_status(&self, f: &mut fmt::Formatter<'_>, markup: &impl Markup) -> fmt::Result {
+        if let Some(old) = self.status.old {
+            markup.text(f, &old.to_string())?;
+            write!(f, " → ")?;
+        }
+        markup.text(f, &self.status.new.to_string())
+    }
+
+    fn write_source(&self, f: &mut fmt::Formatter<'_>, markup: &impl Markup) -> fmt::Result {
+        if let Some(old) = self.source.old {
+            markup.code(f, &old.to_string())?;
+            write!(f, " → ")?;
+        }
+        markup.code(f, &self.source.new.to_string())
+    }
+
+    fn write_features(&self, f: &mut fmt::Formatter<'_>, markup: &impl Markup) -> fmt::Result {
+        if self.features.is_empty() {
+            return markup.none(f);
+        }
+        for (idx, (change, feature)) in self.features.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            match change {
+                FeatureChange::Added => markup.added(f, feature)?,
+                FeatureChange::Removed => markup.removed(f, feature)?,
+                FeatureChange::Unchanged => markup.code(f, feature)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Output-specific markup for table cells. All text passed in is escaped as necessary.
+trait Markup {
+    fn text(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result;
+    fn code(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result;
+    fn strong(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result;
+    fn added(&self, f: &mut fmt::Formatter<'_>, feature: &str) -> fmt::Result;
+    fn removed(&self, f: &mut fmt::Formatter<'_>, feature: &str) -> fmt::Result;
+    fn none(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+struct Markdown;
+
+impl Markdown {
+    fn escape(text: &str) -> String {
+        // Pipes must be escaped everywhere in a table cell, including within code spans. Other
+        // special characters can't appear in package names, versions or statuses.
+        text.replace('|', "\\|")
+    }
+}
+
+impl Markup for Markdown {
+    fn text(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        write!(f, "{}", Self::escape(text))
+    }
+
+    fn code(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        write!(f, "`{}`", Self::escape(text))
+    }
+
+    fn strong(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        write!(f, "**{}**", Self::escape(text))
+    }
+
+    fn added(&self, f: &mut fmt::Formatter<'_>, feature: &str) -> fmt::Result {
+        write!(f, "**+`{}`**", Self::escape(feature))
+    }
+
+    fn removed(&self, f: &mut fmt::Formatter<'_>, feature: &str) -> fmt::Result {
+        write!(f, "~~-`{}`~~", Self::escape(feature))
+    }
+
+    fn none(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "_none_")
+    }
+}
+
+struct Html;
+
+impl Html {
+    fn escape(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+impl Markup for Html {
+    fn text(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        write!(f, "{}", Self::escape(text))
+    }
+
+    fn code(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        write!(f, "<code>{}</code>", Self::escape(text))
+    }
+
+    fn strong(&self, f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+        write!(f, "<strong>{}</strong>", Self::escape(text))
+    }
+
+    fn added(&self, f: &mut fmt::Formatter<'_>, feature: &str) -> fmt::Result {
+        write!(
+            f,
+            "<ins class=\"feature-added\">+<code>{}</code></ins>",
+            Self::escape(feature)
+        )
+    }
+
+    fn removed(&self, f: &mut fmt::Formatter<'_>, feature: &str) -> fmt::Result {
+        write!(
+            f,
+            "<del class=\"feature-removed\">-<code>{}</code></del>",
+            Self::escape(feature)
+        )
+    }
+
+    fn none(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<em>none</em>")
+    }
+}
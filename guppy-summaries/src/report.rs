@@ -3,18 +3,20 @@
 
 use crate::{
     diff::{changed_sort_key, PackageDiff, SummaryDiff, SummaryDiffStatus},
+    render::{HtmlReport, MarkdownReport},
     SummaryId,
 };
 use std::fmt;
 
 /// A report of a diff between two summaries.
 ///
-/// This report can be generated or written to a file through `fmt::Display`.
+/// This report can be generated or written to a file through `fmt::Display`. For Markdown or HTML
+/// output, see the [`markdown`](Self::markdown) and [`html`](Self::html) methods.
 #[derive(Clone, Debug)]
 pub struct SummaryReport<'a, 'b> {
-    diff: &'b SummaryDiff<'a>,
-    sorted_target: Vec<(&'a SummaryId, &'b SummaryDiffStatus<'a>)>,
-    sorted_host: Vec<(&'a SummaryId, &'b SummaryDiffStatus<'a>)>,
+    pub(crate) diff: &'b SummaryDiff<'a>,
+    pub(crate) sorted_target: Vec<(&'a SummaryId, &'b SummaryDiffStatus<'a>)>,
+    pub(crate) sorted_host: Vec<(&'a SummaryId, &'b SummaryDiffStatus<'a>)>,
 }
 
 impl<'a, 'b> SummaryReport<'a, 'b> {
@@ -30,6 +32,20 @@ impl<'a, 'b> SummaryReport<'a, 'b> {
         }
     }
 
+    /// Returns a Markdown version of this report, suitable for code review comments.
+    ///
+    /// The returned value can be used with `fmt::Display`.
+    pub fn markdown<'r>(&'r self) -> MarkdownReport<'r, 'a, 'b> {
+        MarkdownReport::new(self)
+    }
+
+    /// Returns a self-contained HTML document for this report.
+    ///
+    /// The returned value can be used with `fmt::Display`.
+    pub fn html<'r>(&'r self) -> HtmlReport<'r, 'a, 'b> {
+        HtmlReport::new(self)
+    }
+
     fn make_sorted(
         packages: &'b PackageDiff<'a>,
     ) -> Vec<(&'a SummaryId, &'b SummaryDiffStatus<'a>)> {
@@ -421,3 +421,113 @@ fn make_summary(list: Vec<(SummaryId, PackageStatus, Vec<&str>)>) -> PackageMap
         })
         .collect()
 }
+
+#[test]
+fn test_markdown_report() {
+    let summary = Summary::parse(SERIALIZED_SUMMARY).expect("from_str succeeded");
+    let summary2 = Summary::parse(SUMMARY2).expect("from_str succeeded");
+    let diff = summary.diff(&summary2);
+
+    static EXPECTED_MARKDOWN: &str = r#"<details open>
+<summary><b>Target packages</b> (2 added, 1 modified, 1 removed)</summary>
+
+#### Added
+
+| Package | Version | Status | Source | Features |
+| --- | --- | --- | --- | --- |
+| `dep` | 0.4.3 | direct third-party | `crates.io` | `std` |
+| `dep` | 0.5.0 | transitive third-party | `crates.io` | `std` |
+
+#### Modified
+
+| Package | Version | Status | Source | Features |
+| --- | --- | --- | --- | --- |
+| `foo` | 1.2.3 | initial | `path 'foo'` | **+`feature2`**, `default`, `feature1` |
+
+#### Removed
+
+| Package | Version | Status | Source | Features |
+| --- | --- | --- | --- | --- |
+| `dep` | 0.4.2 | direct third-party | `crates.io` | `std` |
+
+</details>
+
+<details open>
+<summary><b>Host packages</b> (1 added, 2 modified)</summary>
+
+#### Added
+
+| Package | Version | Status | Source | Features |
+| --- | --- | --- | --- | --- |
+| `local-dep` | 2.0.0 | transitive third-party | `path '../local-dep-2'` | _none_ |
+
+#### Modified
+
+| Package | Version | Status | Source | Features |
+| --- | --- | --- | --- | --- |
+| `bar` | 0.1.0 → 0.2.0 | workspace → initial | `path 'dir/bar'` | `default`, `feature2` |
+| `local-dep` | 1.1.2 | transitive third-party | `path '../local-dep'` | **+`dep-feature`** |
+
+</details>
+
+"#;
+    assert_eq!(diff.report().markdown().to_string(), EXPECTED_MARKDOWN);
+
+    let no_changes = summary.diff(&summary);
+    assert_eq!(no_changes.report().markdown().to_string(), "No changes.\n");
+}
+
+#[test]
+fn test_html_report() {
+    static OLD: &str = r#"
+[[target-package]]
+name = "foo"
+version = "1.3.0"
+source = "git+https://example.com/foo?branch=a&b"
+status = 'transitive'
+features = ["a", "b"]
+"#;
+    static NEW: &str = r#"
+[[target-package]]
+name = "foo"
+version = "1.2.0"
+crates-io = true
+status = 'transitive'
+features = ["a", "c"]
+"#;
+    let old = Summary::parse(OLD).expect("from_str succeeded");
+    let new = Summary::parse(NEW).expect("from_str succeeded");
+    let diff = old.diff(&new);
+    let report = diff.report();
+
+    let markdown = report.markdown().to_string();
+    assert!(
+        markdown.contains(
+            "| `foo` | 1.3.0 → 1.2.0 **(downgrade)** | transitive third-party \
+             | `external 'git+https://example.com/foo?branch=a&b'` → `crates.io` \
+             | **+`c`**, ~~-`b`~~, `a` |"
+        ),
+        "markdown output has the modified row: {}",
+        markdown
+    );
+    assert!(
+        !markdown.contains("Host packages"),
+        "unchanged sections are skipped"
+    );
+
+    let html = report.html().to_string();
+    assert!(html.starts_with("<!DOCTYPE html>\n"), "html is a document");
+    assert!(html.ends_with("</html>\n"), "html is a document");
+    assert!(
+        html.contains(
+            "<tr><td><code>foo</code></td><td>1.3.0 → 1.2.0 <strong>(downgrade)</strong></td>\
+             <td>transitive third-party</td>\
+             <td><code>external &#39;git+https://example.com/foo?branch=a&amp;b&#39;</code> → \
+             <code>crates.io</code></td>\
+             <td><ins class=\"feature-added\">+<code>c</code></ins>, \
+             <del class=\"feature-removed\">-<code>b</code></del>, <code>a</code></td></tr>"
+        ),
+        "html output has the modified row: {}",
+        html
+    );
+}
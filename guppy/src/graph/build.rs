@@ -9,7 +9,7 @@ use crate::{
         PlatformStatusImpl, WorkspaceImpl,
     },
     sorted_set::SortedSet,
-    Error, PackageId,
+    Error, JsonValue, PackageId,
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{Dependency, DependencyKind, Metadata, NodeDep, Package, Resolve, Target};
@@ -51,7 +51,13 @@ impl PackageGraph {
 
         let dep_graph = build_state.finish();
 
-        let workspace = WorkspaceImpl::new(workspace_root, &packages, workspace_members)?;
+        let workspace = WorkspaceImpl::new(
+            workspace_root,
+            metadata.target_directory,
+            metadata.workspace_metadata,
+            &packages,
+            workspace_members,
+        )?;
 
         Ok(Self {
             dep_graph,
@@ -69,6 +75,8 @@ impl WorkspaceImpl {
     /// Indexes and creates a new workspace.
     fn new(
         workspace_root: impl Into<Utf8PathBuf>,
+        target_directory: impl Into<Utf8PathBuf>,
+        metadata_table: JsonValue,
         packages: &HashMap<PackageId, PackageMetadataImpl>,
         members: impl IntoIterator<Item = PackageId>,
     ) -> Result<Self, Error> {
@@ -113,6 +121,8 @@ impl WorkspaceImpl {
 
         Ok(Self {
             root: workspace_root,
+            target_directory: target_directory.into(),
+            metadata_table,
             members_by_path,
             members_by_name,
             #[cfg(feature = "proptest1")]
@@ -721,6 +731,9 @@ impl DepRequiredOrOptional {
             None => None,
         };
 
+        if let Some(target_spec) = &target_spec {
+            self.target_specs.push(target_spec.clone());
+        }
         self.build_if.add_spec(target_spec.as_ref());
         if dep.uses_default_features {
             self.default_features_if.add_spec(target_spec.as_ref());
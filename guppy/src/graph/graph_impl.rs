@@ -10,6 +10,7 @@ use crate::{
     },
     petgraph_support::{scc::Sccs, topo::TopoWithCycles, IxBitSet},
     CargoMetadata, DependencyKind, Error, JsonValue, MetadataCommand, PackageId, Platform,
+    PlatformMatches, PlatformSet, TargetSpecLint,
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::NodeDep;
@@ -267,6 +268,33 @@ impl PackageGraph {
         self.dep_graph.edge_count()
     }
 
+    /// Checks the platform specs for every dependency in this graph for likely mistakes, such as
+    /// misspelled `target_os` values.
+    ///
+    /// Lints are returned for every link and dependency kind whose `Cargo.toml` entries have
+    /// platform specs with mistakes in them. For the kinds of mistakes found, see
+    /// `TargetSpecLint`.
+    pub fn lint_target_specs(&self) -> Vec<PackageLinkLint<'_>> {
+        // The same specs tend to be repeated across many links, so cache lint results.
+        let mut cache: HashMap<String, Vec<TargetSpecLint>> = HashMap::new();
+        let mut res = vec![];
+        for edge in self.dep_graph.edge_references() {
+            let link = self.edge_ref_to_link(edge);
+            for dep_kind in DependencyKind::VALUES {
+                for spec in link.req_for_kind(*dep_kind).target_specs() {
+                    let lints = cache.entry(spec.to_string()).or_insert_with(|| spec.lint());
+                    res.extend(lints.iter().map(|lint| PackageLinkLint {
+                        link,
+                        dep_kind: *dep_kind,
+                        spec,
+                        lint: lint.clone(),
+                    }));
+                }
+            }
+        }
+        res
+    }
+
     /// Creates a new cache for `depends_on` queries.
     ///
     /// The cache is optional but can speed up some queries.
@@ -479,6 +507,19 @@ impl<'g> Workspace<'g> {
         &self.inner.root
     }
 
+    /// Returns the target directory in which output artifacts are stored.
+    pub fn target_directory(&self) -> &'g Utf8Path {
+        &self.inner.target_directory
+    }
+
+    /// Returns the freeform metadata table for this workspace.
+    ///
+    /// This is the same as the `workspace.metadata` section of `Cargo.toml`. This section is
+    /// typically used by tools which would like to store workspace configuration in `Cargo.toml`.
+    pub fn metadata_table(&self) -> &'g JsonValue {
+        &self.inner.metadata_table
+    }
+
     /// Returns the number of packages in this workspace.
     pub fn member_count(&self) -> usize {
         self.inner.members_by_path.len()
@@ -645,6 +686,8 @@ mod workspace_rayon {
 #[derive(Clone, Debug)]
 pub(super) struct WorkspaceImpl {
     pub(super) root: Utf8PathBuf,
+    pub(super) target_directory: Utf8PathBuf,
+    pub(super) metadata_table: JsonValue,
     // This is a BTreeMap to allow presenting data in sorted order.
     pub(super) members_by_path: BTreeMap<Utf8PathBuf, PackageId>,
     pub(super) members_by_name: BTreeMap<Box<str>, PackageId>,
@@ -1692,6 +1735,38 @@ impl<'g> PackageLink<'g> {
     }
 }
 
+/// A likely mistake in the platform spec for a dependency, as returned by
+/// `PackageGraph::lint_target_specs`.
+#[derive(Clone, Debug)]
+pub struct PackageLinkLint<'g> {
+    link: PackageLink<'g>,
+    dep_kind: DependencyKind,
+    spec: &'g TargetSpec<'static>,
+    lint: TargetSpecLint,
+}
+
+impl<'g> PackageLinkLint<'g> {
+    /// Returns the link whose platform spec has a likely mistake.
+    pub fn link(&self) -> &PackageLink<'g> {
+        &self.link
+    }
+
+    /// Returns the kind of dependency the platform spec was specified for.
+    pub fn dep_kind(&self) -> DependencyKind {
+        self.dep_kind
+    }
+
+    /// Returns the platform spec.
+    pub fn spec(&self) -> &'g TargetSpec<'static> {
+        self.spec
+    }
+
+    /// Returns the likely mistake found in the platform spec.
+    pub fn lint(&self) -> &TargetSpecLint {
+        &self.lint
+    }
+}
+
 /// An opaque identifier for a PackageLink's pointers. Used for tests.
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 #[doc(hidden)]
@@ -1762,6 +1837,18 @@ impl<'g> DependencyReq<'g> {
         self.inner.no_default_features()
     }
 
+    /// Returns the platform specs for the `Cargo.toml` entries corresponding to this requirement,
+    /// such as the `cfg(unix)` in `[target.'cfg(unix)'.dependencies]`.
+    ///
+    /// Entries that aren't platform-specific aren't included.
+    pub fn target_specs(&self) -> impl Iterator<Item = &'g TargetSpec<'static>> {
+        self.inner
+            .required
+            .target_specs
+            .iter()
+            .chain(&self.inner.optional.target_specs)
+    }
+
     /// Returns a list of all features possibly enabled by this dependency. This includes features
     /// that are only turned on if the dependency is optional, or features enabled by inactive
     /// platforms.
@@ -1953,6 +2040,17 @@ impl<'g> PlatformStatus<'g> {
             PlatformStatus::PlatformDependent { eval } => eval.eval(platform),
         }
     }
+
+    /// Evaluates whether this dependency is enabled on each platform in the given set.
+    ///
+    /// This can be used to answer questions like "on which of our supported platforms is this
+    /// dependency active?"
+    ///
+    /// Platforms on which this dependency is enabled are returned as matches, and ones on which
+    /// the status is unknown are returned as unknowns.
+    pub fn enabled_on_platforms<'a>(&self, platforms: &PlatformSet<'a>) -> PlatformMatches<'a> {
+        platforms.eval_with(|platform| self.enabled_on(platform).to_option())
+    }
 }
 
 /// Whether a dependency or feature is enabled on a specific platform.
@@ -1984,6 +2082,14 @@ impl EnabledTernary {
         }
     }
 
+    fn to_option(self) -> Option<bool> {
+        match self {
+            EnabledTernary::Disabled => Some(false),
+            EnabledTernary::Unknown => None,
+            EnabledTernary::Enabled => Some(true),
+        }
+    }
+
     /// Returns true if the status is known (either enabled or disabled).
     pub fn is_known(self) -> bool {
         match self {
@@ -2032,6 +2138,20 @@ impl<'g> PlatformEval<'g> {
         }
         res
     }
+
+    /// Runs this evaluator against each platform in the given set.
+    ///
+    /// For more, see the documentation for `PlatformStatus::enabled_on_platforms`.
+    pub fn eval_platforms<'a>(&self, platforms: &PlatformSet<'a>) -> PlatformMatches<'a> {
+        platforms.eval_with(|platform| self.eval(platform).to_option())
+    }
+
+    /// Returns the platform specs this evaluator matches against.
+    ///
+    /// This evaluator is enabled on a platform if any of these specs match it.
+    pub fn target_specs(&self) -> impl Iterator<Item = &'g TargetSpec<'static>> {
+        self.specs.iter()
+    }
 }
 
 /// Information about dependency requirements.
@@ -2084,6 +2204,7 @@ impl DependencyReqImpl {
 /// optional.
 #[derive(Clone, Debug, Default)]
 pub(super) struct DepRequiredOrOptional {
+    pub(super) target_specs: Vec<TargetSpec<'static>>,
     pub(super) build_if: PlatformStatusImpl,
     pub(super) default_features_if: PlatformStatusImpl,
     pub(super) no_default_features_if: PlatformStatusImpl,
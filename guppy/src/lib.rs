@@ -115,4 +115,7 @@ pub use semver::{Version, VersionReq};
 pub use serde_json::Value as JsonValue;
 // These are inlined -- generally, treat target_spec as a private dependency so expose these types
 // as part of guppy's API.
-pub use target_spec::{Error as TargetSpecError, Platform, TargetFeatures};
+pub use target_spec::{
+    Error as TargetSpecError, Platform, PlatformMatches, PlatformSet, TargetFeatures, TargetSpec,
+    TargetSpecLint,
+};
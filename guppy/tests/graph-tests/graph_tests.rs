@@ -16,7 +16,10 @@ mod small {
     use super::*;
     use crate::feature_helpers::assert_features_for_package;
     use fixtures::json::METADATA_CYCLE_FEATURES_BASE;
-    use guppy::graph::feature::StandardFeatures;
+    use guppy::{
+        graph::feature::StandardFeatures, CargoMetadata, DependencyKind, PlatformSet,
+        TargetFeatures, TargetSpecLint,
+    };
     use pretty_assertions::assert_eq;
 
     // Test specific details extracted from metadata1.json.
@@ -299,6 +302,119 @@ mod small {
             &[None],
             "lazy_static",
         );
+
+        // lazy_static 0.2 is only a dependency on non-Windows platforms.
+        let platforms = PlatformSet::from_triples(
+            vec![
+                "x86_64-unknown-linux-gnu",
+                "x86_64-pc-windows-msvc",
+                "x86_64-apple-darwin",
+            ],
+            TargetFeatures::Unknown,
+        )
+        .expect("known triples");
+        let lazy_static_02_id = package_id(json::METADATA_TARGETS1_LAZY_STATIC_02);
+        let link = package_graph
+            .metadata(&package_id(json::METADATA_TARGETS1_TESTCRATE))
+            .expect("valid package ID")
+            .direct_links()
+            .find(|link| *link.to().id() == lazy_static_02_id)
+            .expect("link to lazy_static 0.2 exists");
+        let platform_matches = link
+            .normal()
+            .status()
+            .required_status()
+            .enabled_on_platforms(&platforms);
+        assert_eq!(
+            platform_matches
+                .matches()
+                .iter()
+                .map(|platform| platform.triple())
+                .collect::<Vec<_>>(),
+            vec!["x86_64-unknown-linux-gnu", "x86_64-apple-darwin"],
+            "lazy_static 0.2 is enabled on non-Windows platforms"
+        );
+        assert_eq!(
+            platform_matches
+                .non_matches()
+                .iter()
+                .map(|platform| platform.triple())
+                .collect::<Vec<_>>(),
+            vec!["x86_64-pc-windows-msvc"],
+            "lazy_static 0.2 is disabled on Windows"
+        );
+        assert!(platform_matches.unknowns().is_empty(), "no unknowns");
+
+        assert_eq!(
+            link.normal()
+                .target_specs()
+                .map(|spec| spec.to_string())
+                .collect::<Vec<_>>(),
+            vec!["cfg(not(windows))"],
+            "lazy_static 0.2 has a single platform spec"
+        );
+        let lints = package_graph.lint_target_specs();
+        assert!(lints.is_empty(), "no lints in fixture: {:?}", lints);
+    }
+
+    #[test]
+    fn metadata_targets1_lints() {
+        // Misspell a family, and add a large spec with many target features. The latter must be
+        // checked quickly and not produce any lints.
+        let features: Vec<_> = (0..200)
+            .map(|idx| format!("target_feature = \\\"f{}\\\"", idx))
+            .collect();
+        let json = JsonFixture::metadata_targets1()
+            .json()
+            .replace("cfg(not(windows))", "cfg(not(windos))")
+            .replace(
+                "\"target\":\"cfg(windows)\"",
+                &format!("\"target\":\"cfg(any(windows, {}))\"", features.join(", ")),
+            );
+        let package_graph = CargoMetadata::parse_json(&json)
+            .expect("valid JSON")
+            .build_graph()
+            .expect("valid graph");
+
+        let lints: Vec<_> = package_graph
+            .lint_target_specs()
+            .into_iter()
+            .map(|lint| {
+                (
+                    lint.link().from().name(),
+                    lint.link().to().id().repr(),
+                    lint.dep_kind(),
+                    lint.spec().to_string(),
+                    lint.lint().clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lints,
+            vec![
+                (
+                    "testcrate-targets",
+                    json::METADATA_TARGETS1_DEP_A,
+                    DependencyKind::Normal,
+                    "cfg(not(windos))".to_string(),
+                    TargetSpecLint::MisspelledFamily {
+                        flag: "windos".to_string(),
+                        suggestion: "windows",
+                    },
+                ),
+                (
+                    "testcrate-targets",
+                    json::METADATA_TARGETS1_LAZY_STATIC_02,
+                    DependencyKind::Normal,
+                    "cfg(not(windos))".to_string(),
+                    TargetSpecLint::MisspelledFamily {
+                        flag: "windos".to_string(),
+                        suggestion: "windows",
+                    },
+                ),
+            ],
+            "misspelled family is linted"
+        );
     }
 
     proptest_suite!(metadata_targets1);
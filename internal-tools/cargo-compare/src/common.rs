@@ -196,7 +196,7 @@ impl GuppyCargoCommon {
                 pf,
                 include_dev,
                 v2,
-                target_platform: target_platform.map(|s| s.to_string()),
+                target_platform,
                 metadata_opts: metadata_opts.clone(),
             })
     }
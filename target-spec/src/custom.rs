@@ -0,0 +1,299 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for platforms defined by rustc target JSON files.
+
+use crate::{
+    custom_platforms::{Arch, Endian, Env, Family, Os, TargetInfo, Vendor},
+    CustomTargetError, Platform, TargetFeatures,
+};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+impl Platform<'static> {
+    /// Creates a new, custom platform from the contents of a
+    /// [rustc target JSON file](https://doc.rust-lang.org/rustc/targets/custom.html).
+    ///
+    /// `triple` is the name of the target: rustc uses the file name without the `.json`
+    /// extension. The arch, OS, environment, vendor, family, endianness and pointer width are
+    /// read from the JSON, along with the target features enabled through `features`. Fields not
+    /// relevant to evaluating target specs are ignored.
+    ///
+    /// Requires the `custom` feature to be enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetSpec};
+    ///
+    /// let json = r#"{
+    ///     "llvm-target": "thumbv7em-none-eabi",
+    ///     "arch": "arm",
+    ///     "target-endian": "little",
+    ///     "target-pointer-width": "32",
+    ///     "features": "+v7,+thumb-mode,-neon"
+    /// }"#;
+    /// let platform = Platform::from_target_json("thumbv7em-custom", json).unwrap();
+    /// assert!(platform.is_custom());
+    ///
+    /// let spec: TargetSpec = "cfg(all(target_arch = \"arm\", target_feature = \"v7\"))".parse().unwrap();
+    /// assert_eq!(spec.eval(&platform), Some(true));
+    /// ```
+    pub fn from_target_json(
+        triple: impl AsRef<str>,
+        json: &str,
+    ) -> Result<Self, CustomTargetError> {
+        let target: TargetJson = serde_json::from_str(json).map_err(CustomTargetError::Json)?;
+        target.to_platform(triple.as_ref())
+    }
+
+    /// Reads a custom platform from a
+    /// [rustc target JSON file](https://doc.rust-lang.org/rustc/targets/custom.html).
+    ///
+    /// The name of the target is the file name without the extension, as with rustc. For more,
+    /// see the documentation for `from_target_json`.
+    ///
+    /// Requires the `custom` feature to be enabled.
+    pub fn from_target_json_file(path: impl AsRef<Path>) -> Result<Self, CustomTargetError> {
+        let path = path.as_ref();
+        let triple = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| CustomTargetError::InvalidFileName(path.to_path_buf()))?;
+        let json = fs::read_to_string(path).map_err(|err| CustomTargetError::Read {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        Self::from_target_json(triple, &json)
+    }
+}
+
+/// The subset of a rustc target JSON file used by target-spec.
+///
+/// Defaults match the ones used by rustc.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TargetJson {
+    arch: String,
+    #[serde(default = "default_os")]
+    os: String,
+    #[serde(default)]
+    env: String,
+    #[serde(default = "default_vendor")]
+    vendor: String,
+    #[serde(default)]
+    target_family: Option<TargetFamilyJson>,
+    #[serde(default = "default_endian")]
+    target_endian: String,
+    target_pointer_width: PointerWidthJson,
+    #[serde(default)]
+    features: String,
+}
+
+impl TargetJson {
+    fn to_platform(&self, triple: &str) -> Result<Platform<'static>, CustomTargetError> {
+        let target_info = TargetInfo {
+            triple,
+            // Bare-metal targets use "none", which is also what `cfg(target_os)` evaluates to.
+            os: Some(Os(&self.os)),
+            arch: Arch(&self.arch),
+            env: Some(self.env.as_str())
+                .filter(|env| !env.is_empty())
+                .map(Env),
+            vendor: Some(Vendor(&self.vendor)),
+            family: self.family(),
+            pointer_width: self.pointer_width()?,
+            endian: self
+                .target_endian
+                .parse::<Endian>()
+                .map_err(|_| invalid_field("target-endian", &self.target_endian))?,
+        };
+
+        Ok(Platform::custom_owned(
+            &target_info,
+            TargetFeatures::features(enabled_features(&self.features)),
+        ))
+    }
+
+    /// Returns the first family known to cfg-expr.
+    fn family(&self) -> Option<Family> {
+        match self.target_family.as_ref()? {
+            TargetFamilyJson::Single(family) => family.parse().ok(),
+            TargetFamilyJson::List(families) => {
+                families.iter().find_map(|family| family.parse().ok())
+            }
+        }
+    }
+
+    fn pointer_width(&self) -> Result<u8, CustomTargetError> {
+        match &self.target_pointer_width {
+            PointerWidthJson::String(width) => width
+                .parse()
+                .map_err(|_| invalid_field("target-pointer-width", width)),
+            PointerWidthJson::Int(width) => {
+                let width = *width;
+                if width <= u8::MAX as u64 {
+                    Ok(width as u8)
+                } else {
+                    Err(invalid_field("target-pointer-width", &width.to_string()))
+                }
+            }
+        }
+    }
+}
+
+/// Older versions of rustc use a single string for the family, while newer ones use a list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TargetFamilyJson {
+    Single(String),
+    List(Vec<String>),
+}
+
+/// Older versions of rustc use a string for the pointer width, while newer ones use an integer.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PointerWidthJson {
+    String(String),
+    Int(u64),
+}
+
+fn default_os() -> String {
+    "none".to_string()
+}
+
+fn default_vendor() -> String {
+    "unknown".to_string()
+}
+
+fn default_endian() -> String {
+    "little".to_string()
+}
+
+fn invalid_field(field: &'static str, value: &str) -> CustomTargetError {
+    CustomTargetError::InvalidField {
+        field,
+        value: value.to_string(),
+    }
+}
+
+/// Returns the features enabled in an LLVM-style feature string like `+foo,-bar`.
+fn enabled_features(features: &str) -> Vec<String> {
+    let mut enabled: Vec<String> = vec![];
+    for feature in features.split(',').map(str::trim) {
+        if let Some(feature) = feature.strip_prefix('-') {
+            // Later entries override earlier ones.
+            enabled.retain(|f| f != feature);
+        } else {
+            let feature = feature.strip_prefix('+').unwrap_or(feature);
+            if !feature.is_empty() {
+                enabled.push(feature.to_string());
+            }
+        }
+    }
+    enabled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetSpec;
+
+    static THUMBV7EM_JSON: &str = r#"{
+        "arch": "arm",
+        "data-layout": "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64",
+        "llvm-target": "thumbv7em-none-eabi",
+        "max-atomic-width": 32,
+        "target-endian": "little",
+        "target-pointer-width": "32",
+        "features": "+v7,+thumb-mode,+neon,-neon,+dsp"
+    }"#;
+
+    static LINUX_JSON: &str = r#"{
+        "arch": "x86_64",
+        "os": "linux",
+        "env": "gnu",
+        "vendor": "custom",
+        "target-family": ["unix"],
+        "target-endian": "little",
+        "target-pointer-width": 64,
+        "llvm-target": "x86_64-unknown-linux-gnu"
+    }"#;
+
+    #[test]
+    fn parse_bare_metal() {
+        let platform =
+            Platform::from_target_json("thumbv7em-custom", THUMBV7EM_JSON).expect("valid JSON");
+        assert!(platform.is_custom(), "platform is custom");
+        assert_eq!(platform.triple(), "thumbv7em-custom");
+
+        let target_info = platform.target_info();
+        assert_eq!(target_info.arch, Arch::arm);
+        assert_eq!(target_info.os, Some(Os("none")));
+        assert_eq!(target_info.env, None, "empty env is no env");
+        assert_eq!(target_info.vendor, Some(Vendor::unknown));
+        assert_eq!(target_info.family, None);
+        assert_eq!(target_info.pointer_width, 32);
+        assert_eq!(target_info.endian, Endian::little);
+        assert_eq!(
+            platform.target_features(),
+            &TargetFeatures::features(vec!["v7", "thumb-mode", "dsp"]),
+        );
+
+        for (spec, expected) in &[
+            ("cfg(target_arch = \"arm\")", true),
+            ("cfg(target_os = \"none\")", true),
+            ("cfg(target_feature = \"dsp\")", true),
+            ("cfg(target_feature = \"neon\")", false),
+            ("cfg(unix)", false),
+            // Custom platforms don't match builtin triples.
+            ("thumbv7em-none-eabi", false),
+        ] {
+            let spec: TargetSpec = spec.parse().expect("valid spec");
+            assert_eq!(spec.eval(&platform), Some(*expected), "for spec {:?}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_hosted() {
+        let platform =
+            Platform::from_target_json("x86_64-custom-linux-gnu", LINUX_JSON).expect("valid JSON");
+        let target_info = platform.target_info();
+        assert_eq!(target_info.os, Some(Os::linux));
+        assert_eq!(target_info.env, Some(Env::gnu));
+        assert_eq!(target_info.vendor, Some(Vendor("custom")));
+        assert_eq!(target_info.family, Some(Family::unix));
+        assert_eq!(target_info.pointer_width, 64);
+        assert_eq!(platform.target_features(), &TargetFeatures::none());
+
+        let spec: TargetSpec = "cfg(all(unix, target_env = \"gnu\"))".parse().unwrap();
+        assert_eq!(spec.eval(&platform), Some(true));
+    }
+
+    #[test]
+    fn parse_bad() {
+        let missing_arch = r#"{ "target-pointer-width": "32" }"#;
+        let bad_endian =
+            r#"{ "arch": "arm", "target-pointer-width": "32", "target-endian": "middle" }"#;
+        let bad_width = r#"{ "arch": "arm", "target-pointer-width": "thirty-two" }"#;
+
+        assert!(matches!(
+            Platform::from_target_json("foo", missing_arch),
+            Err(CustomTargetError::Json(_))
+        ));
+        assert!(matches!(
+            Platform::from_target_json("foo", bad_endian),
+            Err(CustomTargetError::InvalidField {
+                field: "target-endian",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Platform::from_target_json("foo", bad_width),
+            Err(CustomTargetError::InvalidField {
+                field: "target-pointer-width",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Runtime detection of CPU target features.
+
+/// Returns the target features supported by the CPU this process is running on.
+///
+/// Only features that rustc reports through `cfg(target_feature)` are included.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+// The avx512-prefixed names below are deprecated in newer versions of Rust, but the unprefixed ones
+// aren't available in older versions.
+#[allow(deprecated)]
+pub(crate) fn detect_target_features() -> Vec<&'static str> {
+    // Each feature is either a name that both is_x86_feature_detected! and rustc use, or a
+    // `detected => reported` pair for features that is_x86_feature_detected! knows under another
+    // name.
+    macro_rules! detect_x86 {
+        ($($feature:tt $(=> $reported:tt)?),* $(,)?) => {{
+            let mut features = vec![];
+            $(
+                if is_x86_feature_detected!($feature) {
+                    features.push(detect_x86!(@reported $feature $(=> $reported)?));
+                }
+            )*
+            features
+        }};
+        (@reported $feature:tt) => { $feature };
+        (@reported $feature:tt => $reported:tt) => { $reported };
+    }
+
+    detect_x86!(
+        "adx",
+        "aes",
+        "avx",
+        "avx2",
+        "avx512bitalg",
+        "avx512bw",
+        "avx512cd",
+        "avx512dq",
+        "avx512er",
+        "avx512f",
+        "avx512gfni" => "gfni",
+        "avx512ifma",
+        "avx512pf",
+        "avx512vbmi",
+        "avx512vbmi2",
+        "avx512vl",
+        "avx512vnni",
+        "avx512vaes" => "vaes",
+        "avx512vpclmulqdq" => "vpclmulqdq",
+        "avx512vpopcntdq",
+        "bmi1",
+        "bmi2",
+        "cmpxchg16b",
+        "f16c",
+        "fma",
+        "fxsr",
+        "lzcnt",
+        // "movbe" isn't supported by is_x86_feature_detected! in Rust 1.48, the minimum supported
+        // version.
+        "pclmulqdq",
+        "popcnt",
+        "rdrand",
+        "rdseed",
+        "sha",
+        "sse",
+        "sse2",
+        "sse3",
+        "sse4.1",
+        "sse4.2",
+        "sse4a",
+        "ssse3",
+        "tbm",
+        "xsave",
+        "xsavec",
+        "xsaveopt",
+        "xsaves",
+    )
+}
+
+/// Runtime detection isn't supported on this architecture, so only the features enabled at build
+/// time are used.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn detect_target_features() -> Vec<&'static str> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Platform, TargetFeatures};
+
+    #[test]
+    fn runtime_includes_build_time() {
+        let build_time = Platform::current().expect("current platform is known");
+        let runtime = Platform::current_with_runtime_features().expect("current platform is known");
+        assert_eq!(build_time.triple(), runtime.triple());
+
+        match (build_time.target_features(), runtime.target_features()) {
+            (TargetFeatures::Features(build_time), TargetFeatures::Features(runtime)) => {
+                assert!(
+                    build_time.is_subset(runtime),
+                    "features enabled at build time {:?} are available at runtime {:?}",
+                    build_time,
+                    runtime
+                );
+            }
+            other => panic!("expected known features, found {:?}", other),
+        }
+    }
+}
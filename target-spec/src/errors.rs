@@ -1,22 +1,36 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use std::{error, fmt};
+use std::{error, fmt, ops::Range};
+#[cfg(feature = "custom")]
+use std::{io, path::PathBuf};
 
 /// An error that happened during `target-spec` parsing or evaluation.
 #[derive(Debug, PartialEq)]
 #[non_exhaustive]
 pub enum Error {
     /// A `cfg()` expression was invalid and could not be parsed.
-    InvalidCfg(cfg_expr::ParseError),
+    InvalidCfg(ExpressionParseError),
     /// The provided target triple (in the position that a `cfg()` expression would be) was unknown.
     UnknownTargetTriple(String),
     /// The provided platform triple was unknown.
     UnknownPlatformTriple(String),
     /// The provided `cfg()` expression parsed correctly, but it had an unknown predicate.
+    ///
+    /// Key-value predicates for arbitrary keys are now supported, so this error is no longer
+    /// returned.
     UnknownPredicate(String),
     /// Attempt to generate summary from custom platform.
+    ///
+    /// Summaries for custom platforms are now supported, so this error is no longer returned.
     CustomPlatformSummary,
+    /// A custom platform summary had an invalid value.
+    InvalidCustomTargetSummary(String),
+    /// A line in `rustc --print cfg` output, or a cfg passed in through an environment variable,
+    /// was invalid.
+    InvalidPlatformCfg(String),
+    /// A cfg or environment variable required to construct a platform was missing.
+    MissingPlatformCfg(&'static str),
 }
 
 impl fmt::Display for Error {
@@ -34,6 +48,11 @@ impl fmt::Display for Error {
                 f,
                 "generating summaries for custom platforms is currently unsupported"
             ),
+            Error::InvalidCustomTargetSummary(value) => {
+                write!(f, "invalid {} in custom platform summary", value)
+            }
+            Error::InvalidPlatformCfg(cfg) => write!(f, "invalid platform cfg: {}", cfg),
+            Error::MissingPlatformCfg(name) => write!(f, "missing platform cfg: {}", name),
         }
     }
 }
@@ -46,6 +65,216 @@ impl error::Error for Error {
             Error::UnknownPlatformTriple(_) => None,
             Error::UnknownPredicate(_) => None,
             Error::CustomPlatformSummary => None,
+            Error::InvalidCustomTargetSummary(_) => None,
+            Error::InvalidPlatformCfg(_) => None,
+            Error::MissingPlatformCfg(_) => None,
+        }
+    }
+}
+
+/// An error that occurred while parsing a `cfg()` expression.
+///
+/// ## Examples
+///
+/// ```
+/// use target_spec::{Error, ExpressionParseErrorKind, TargetSpec};
+///
+/// let input = "cfg(any(unix, = \"windows\"))";
+/// let err = match input.parse::<TargetSpec>() {
+///     Err(Error::InvalidCfg(err)) => err,
+///     other => panic!("expected a parse error, found {:?}", other),
+/// };
+/// assert_eq!(&input[err.span.clone()], "=");
+/// assert!(matches!(err.kind, ExpressionParseErrorKind::Unexpected { .. }));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ExpressionParseError {
+    /// The input that was being parsed.
+    pub input: String,
+
+    /// The range of bytes in `input` where the error occurred.
+    pub span: Range<usize>,
+
+    /// The kind of error that occurred.
+    pub kind: ExpressionParseErrorKind,
+}
+
+impl ExpressionParseError {
+    pub(crate) fn new(input: &str, err: cfg_expr::ParseError) -> Self {
+        // cfg-expr strips the surrounding cfg( and ) before parsing, and reports spans relative to
+        // the stripped string.
+        let offset = if input.starts_with("cfg(") && input.ends_with(')') {
+            4
+        } else {
+            0
+        };
+        Self {
+            input: input.to_string(),
+            span: (err.span.start + offset)..(err.span.end + offset),
+            kind: ExpressionParseErrorKind::new(err.reason),
+        }
+    }
+}
+
+impl fmt::Display for ExpressionParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error parsing `{}` at bytes {}..{}: {}",
+            self.input, self.span.start, self.span.end, self.kind
+        )
+    }
+}
+
+impl error::Error for ExpressionParseError {}
+
+/// The kind of error that occurred while parsing a `cfg()` expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ExpressionParseErrorKind {
+    /// `not()` was passed a number of predicates other than 1.
+    InvalidNot(usize),
+    /// The expression contained characters that are invalid in `cfg()` expressions.
+    InvalidCharacters,
+    /// An opening parenthesis wasn't closed.
+    UnclosedParens,
+    /// A closing parenthesis didn't have a matching opening one.
+    UnopenedParens,
+    /// An opening quote wasn't closed.
+    UnclosedQuotes,
+    /// A closing quote didn't have a matching opening one.
+    UnopenedQuotes,
+    /// The expression was empty.
+    Empty,
+    /// A term was found where it wasn't expected.
+    Unexpected {
+        /// The terms that were expected instead, if any.
+        expected: Vec<&'static str>,
+    },
+    /// An integer value, such as a pointer width, was invalid.
+    InvalidInteger,
+    /// The expression had more than one predicate at the top level.
+    MultipleRootPredicates,
+    /// A value for a builtin key, such as `target_family`, was unknown.
+    UnknownBuiltin,
+}
+
+impl ExpressionParseErrorKind {
+    fn new(reason: cfg_expr::error::Reason) -> Self {
+        use cfg_expr::error::Reason;
+
+        match reason {
+            Reason::InvalidNot(count) => ExpressionParseErrorKind::InvalidNot(count),
+            Reason::InvalidCharacters => ExpressionParseErrorKind::InvalidCharacters,
+            Reason::UnclosedParens => ExpressionParseErrorKind::UnclosedParens,
+            Reason::UnopenedParens => ExpressionParseErrorKind::UnopenedParens,
+            Reason::UnclosedQuotes => ExpressionParseErrorKind::UnclosedQuotes,
+            Reason::UnopenedQuotes => ExpressionParseErrorKind::UnopenedQuotes,
+            Reason::Empty => ExpressionParseErrorKind::Empty,
+            Reason::Unexpected(expected) => ExpressionParseErrorKind::Unexpected {
+                expected: expected.to_vec(),
+            },
+            Reason::InvalidInteger => ExpressionParseErrorKind::InvalidInteger,
+            Reason::MultipleRootPredicates => ExpressionParseErrorKind::MultipleRootPredicates,
+            Reason::UnknownBuiltin => ExpressionParseErrorKind::UnknownBuiltin,
+        }
+    }
+}
+
+impl fmt::Display for ExpressionParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionParseErrorKind::InvalidNot(count) => {
+                write!(f, "not() takes 1 predicate, found {}", count)
+            }
+            ExpressionParseErrorKind::InvalidCharacters => write!(f, "invalid characters"),
+            ExpressionParseErrorKind::UnclosedParens => write!(f, "unclosed parenthesis"),
+            ExpressionParseErrorKind::UnopenedParens => write!(f, "unopened parenthesis"),
+            ExpressionParseErrorKind::UnclosedQuotes => write!(f, "unclosed quotes"),
+            ExpressionParseErrorKind::UnopenedQuotes => write!(f, "unopened quotes"),
+            ExpressionParseErrorKind::Empty => write!(f, "empty expression"),
+            ExpressionParseErrorKind::Unexpected { expected } => match expected.as_slice() {
+                [] => write!(f, "unexpected term"),
+                [expected] => write!(f, "expected `{}`", expected),
+                expected => {
+                    write!(f, "expected one of ")?;
+                    for (idx, term) in expected.iter().enumerate() {
+                        if idx > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "`{}`", term)?;
+                    }
+                    Ok(())
+                }
+            },
+            ExpressionParseErrorKind::InvalidInteger => write!(f, "invalid integer"),
+            ExpressionParseErrorKind::MultipleRootPredicates => {
+                write!(f, "multiple root predicates")
+            }
+            ExpressionParseErrorKind::UnknownBuiltin => write!(f, "unknown builtin value"),
+        }
+    }
+}
+
+/// An error that happened while creating a custom platform from a rustc target JSON file.
+///
+/// Requires the `custom` feature to be enabled.
+#[cfg(feature = "custom")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CustomTargetError {
+    /// An error occurred while reading a target JSON file.
+    Read {
+        /// The path that was read.
+        path: PathBuf,
+
+        /// The error that occurred.
+        err: io::Error,
+    },
+
+    /// The name of the target couldn't be determined from the file name.
+    InvalidFileName(PathBuf),
+
+    /// The target JSON could not be deserialized.
+    Json(serde_json::Error),
+
+    /// A field in the target JSON had an invalid value.
+    InvalidField {
+        /// The name of the field.
+        field: &'static str,
+
+        /// The invalid value.
+        value: String,
+    },
+}
+
+#[cfg(feature = "custom")]
+impl fmt::Display for CustomTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CustomTargetError::Read { path, .. } => {
+                write!(f, "error reading target JSON file {}", path.display())
+            }
+            CustomTargetError::InvalidFileName(path) => {
+                write!(f, "invalid target JSON file name: {}", path.display())
+            }
+            CustomTargetError::Json(_) => write!(f, "error deserializing target JSON"),
+            CustomTargetError::InvalidField { field, value } => {
+                write!(f, "invalid value for {} in target JSON: {}", field, value)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "custom")]
+impl error::Error for CustomTargetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CustomTargetError::Read { err, .. } => Some(err),
+            CustomTargetError::InvalidFileName(_) => None,
+            CustomTargetError::Json(err) => Some(err),
+            CustomTargetError::InvalidField { .. } => None,
         }
     }
 }
@@ -5,7 +5,7 @@ use crate::{
     platform::{Platform, TargetFeatures},
     Error, Target, TargetSpec,
 };
-use cfg_expr::{Expression, Predicate};
+use cfg_expr::{Expression, Predicate, TargetPredicate};
 use std::sync::Arc;
 
 /// Evaluates the given spec against the provided target and returns `Some(true)` on a successful
@@ -25,35 +25,44 @@ pub fn eval(spec_or_triple: &str, platform: &str) -> Result<Option<bool>, Error>
 
 pub(crate) fn eval_target(target: &Target<'_>, platform: &Platform<'_>) -> Option<bool> {
     match target {
-        Target::TargetInfo(ref target_info) => Some(platform.triple() == target_info.triple),
+        Target::TargetInfo(ref target_info) => Some(platform.triple() == target_info.triple()),
         Target::Spec(ref expr) => eval_expr(expr, platform),
     }
 }
 
 fn eval_expr(spec: &Arc<Expression>, platform: &Platform<'_>) -> Option<bool> {
-    spec.eval(|pred| {
-        match pred {
-            Predicate::Target(target) => Some(target.matches(platform.target_info())),
-            Predicate::TargetFeature(feature) => platform.target_features().matches(feature),
-            Predicate::Test | Predicate::DebugAssertions | Predicate::ProcMacro => {
-                // Known families that always evaluate to false. See
-                // https://docs.rs/cargo-platform/0.1.1/src/cargo_platform/lib.rs.html#76.
-                Some(false)
-            }
-            Predicate::Feature(_) => {
-                // NOTE: This is not supported by Cargo which always evaluates this to false. See
-                // https://github.com/rust-lang/cargo/issues/7442 for more details.
-                Some(false)
-            }
-            Predicate::Flag(flag) => {
-                // This returns false by default but true in some cases.
-                Some(platform.has_flag(flag))
-            }
-            Predicate::KeyValue { .. } => {
-                unreachable!("these predicates are disallowed at TargetSpec construction time")
-            }
+    spec.eval(|pred| eval_predicate(pred, platform))
+}
+
+pub(crate) fn eval_predicate(pred: &Predicate<'_>, platform: &Platform<'_>) -> Option<bool> {
+    match pred {
+        Predicate::Target(TargetPredicate::Os(os)) => {
+            // Builtin targets without an OS, such as bare-metal ones, have no os in cfg-expr, but
+            // rustc reports them as target_os = "none".
+            let target_os = platform.target_info().os.map_or("none", |os| os.0);
+            Some(target_os == os.0)
+        }
+        Predicate::Target(target) => Some(target.matches(&platform.target_info())),
+        Predicate::TargetFeature(feature) => platform.target_feature_matches(feature),
+        Predicate::Test | Predicate::DebugAssertions | Predicate::ProcMacro => {
+            // Known families that always evaluate to false. See
+            // https://docs.rs/cargo-platform/0.1.1/src/cargo_platform/lib.rs.html#76.
+            Some(false)
+        }
+        Predicate::Feature(_) => {
+            // NOTE: This is not supported by Cargo which always evaluates this to false. See
+            // https://github.com/rust-lang/cargo/issues/7442 for more details.
+            Some(false)
+        }
+        Predicate::Flag(flag) => {
+            // This returns false by default but true in some cases.
+            Some(platform.has_flag(flag))
         }
-    })
+        Predicate::KeyValue { key, val } => {
+            // Unknown keys evaluate to None, similar to unknown target features.
+            platform.key_value_matches(key, val)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -68,6 +77,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_target_os_none() {
+        assert_eq!(
+            eval("cfg(target_os = \"none\")", "thumbv7em-none-eabi"),
+            Ok(Some(true)),
+        );
+        assert_eq!(
+            eval("cfg(target_os = \"none\")", "x86_64-unknown-linux-gnu"),
+            Ok(Some(false)),
+        );
+    }
+
     #[test]
     fn test_not_target_os() {
         assert_eq!(
@@ -250,4 +271,87 @@ mod tests {
             Some(false),
         );
     }
+
+    #[test]
+    fn test_implied_target_feature() {
+        let mut platform = Platform::new(
+            "x86_64-unknown-linux-gnu",
+            TargetFeatures::features(vec!["avx2", "popcnt"]),
+        )
+        .unwrap();
+        let mut exact_platform = platform.clone();
+        exact_platform.set_exact_target_features(true);
+        assert_ne!(platform, exact_platform, "matching modes are compared");
+
+        for (spec, implied, exact) in &[
+            ("cfg(target_feature = \"avx2\")", true, true),
+            ("cfg(target_feature = \"avx\")", true, false),
+            ("cfg(target_feature = \"sse4.1\")", true, false),
+            ("cfg(target_feature = \"sse\")", true, false),
+            ("cfg(target_feature = \"avx512f\")", false, false),
+            ("cfg(target_feature = \"fma\")", false, false),
+        ] {
+            let spec: TargetSpec = spec.parse().unwrap();
+            assert_eq!(spec.eval(&platform), Some(*implied), "for spec {:?}", spec);
+            assert_eq!(
+                spec.eval(&exact_platform),
+                Some(*exact),
+                "for spec {:?} with exact matching",
+                spec
+            );
+        }
+
+        // Implications are per-architecture.
+        platform = Platform::new(
+            "aarch64-unknown-linux-gnu",
+            TargetFeatures::features(vec!["avx2", "sve2"]),
+        )
+        .unwrap();
+        for (spec, expected) in &[
+            ("cfg(target_feature = \"avx\")", false),
+            ("cfg(target_feature = \"sve\")", true),
+            ("cfg(target_feature = \"neon\")", true),
+        ] {
+            let spec: TargetSpec = spec.parse().unwrap();
+            assert_eq!(spec.eval(&platform), Some(*expected), "for spec {:?}", spec);
+        }
+    }
+
+    #[test]
+    fn test_key_value() {
+        let platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+        let mut platform_with_keys = platform.clone();
+        platform_with_keys.add_key_values(vec![("my_backend", "vulkan"), ("my_backend", "gl")]);
+        platform_with_keys.add_known_keys(vec!["my_mode"]);
+
+        for (spec, unknown, with_keys) in &[
+            ("cfg(my_backend = \"vulkan\")", None, Some(true)),
+            ("cfg(my_backend = \"gl\")", None, Some(true)),
+            ("cfg(my_backend = \"metal\")", None, Some(false)),
+            ("cfg(not(my_backend = \"metal\"))", None, Some(true)),
+            // Known keys without values always evaluate to false.
+            ("cfg(my_mode = \"fast\")", None, Some(false)),
+            ("cfg(other_key = \"value\")", None, None),
+            // The usual ternary logic applies.
+            (
+                "cfg(any(unix, other_key = \"value\"))",
+                Some(true),
+                Some(true),
+            ),
+            (
+                "cfg(all(windows, other_key = \"value\"))",
+                Some(false),
+                Some(false),
+            ),
+        ] {
+            let spec: TargetSpec = spec.parse().expect("key-value predicates are supported");
+            assert_eq!(&spec.eval(&platform), unknown, "for spec {:?}", spec);
+            assert_eq!(
+                &spec.eval(&platform_with_keys),
+                with_keys,
+                "for spec {:?} with keys",
+                spec
+            );
+        }
+    }
 }
@@ -0,0 +1,734 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A tree representation of `cfg()` expressions, used for display, simplification and
+//! implication checks.
+
+use crate::{evaluator::eval_predicate, Platform, PlatformSet, Target};
+use cfg_expr::{
+    expr::{
+        lexer::{Lexer, Token},
+        Logic,
+    },
+    targets::{Arch, Endian, Env, Family, Os, Vendor},
+    Expression, Predicate, TargetPredicate,
+};
+use std::{collections::BTreeSet, fmt, iter::Peekable};
+
+/// A `cfg()` expression as a tree.
+///
+/// `all()` with no arguments is always true and `any()` with no arguments is always false, so
+/// there's no separate representation for constants.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum CfgExpr {
+    Pred(CfgPredicate),
+    Not(Box<CfgExpr>),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Converts a parsed expression into a tree, preserving its structure.
+    pub(crate) fn new(expr: &Expression) -> Self {
+        // cfg-expr stores expressions in postfix form (with predicates reordered) and doesn't
+        // expose its nodes, so rebuild the structure from the tokens.
+        let mut tokens = Lexer::new(expr.original())
+            .map(|token| token.expect("expression was already parsed").token)
+            .peekable();
+        Self::parse_tokens(&mut tokens)
+    }
+
+    fn parse_tokens<'e>(tokens: &mut Peekable<impl Iterator<Item = Token<'e>>>) -> Self {
+        match tokens.next().expect("expression was already parsed") {
+            Token::Key(key) => {
+                let leaf = if tokens.peek() == Some(&Token::Equals) {
+                    tokens.next();
+                    match tokens.next() {
+                        Some(Token::Value(val)) => format!("{} = \"{}\"", key, val),
+                        other => panic!("expected value after =, found {:?}", other),
+                    }
+                } else {
+                    key.to_string()
+                };
+                // Parse the predicate on its own so that it's classified the same way as in the
+                // original expression.
+                let leaf = Expression::parse(&leaf).expect("predicate was already parsed");
+                let pred = leaf
+                    .predicates()
+                    .next()
+                    .expect("a single predicate was parsed");
+                CfgExpr::Pred(CfgPredicate::new(&pred))
+            }
+            Token::Not => {
+                let mut children = Self::parse_list(tokens);
+                let child = children.pop().expect("not() has exactly one predicate");
+                CfgExpr::Not(Box::new(child))
+            }
+            Token::All => CfgExpr::All(Self::parse_list(tokens)),
+            Token::Any => CfgExpr::Any(Self::parse_list(tokens)),
+            other => panic!("unexpected token {} in parsed expression", other),
+        }
+    }
+
+    fn parse_list<'e>(tokens: &mut Peekable<impl Iterator<Item = Token<'e>>>) -> Vec<Self> {
+        assert_eq!(
+            tokens.next(),
+            Some(Token::OpenParen),
+            "function names are followed by ("
+        );
+        let mut children = vec![];
+        loop {
+            match tokens.peek() {
+                Some(Token::CloseParen) => {
+                    tokens.next();
+                    return children;
+                }
+                Some(Token::Comma) => {
+                    tokens.next();
+                }
+                _ => children.push(Self::parse_tokens(tokens)),
+            }
+        }
+    }
+
+    /// Returns an expression that always evaluates to `value`.
+    pub(crate) fn constant(value: bool) -> Self {
+        if value {
+            CfgExpr::All(vec![])
+        } else {
+            CfgExpr::Any(vec![])
+        }
+    }
+
+    /// Returns `Some(value)` if this expression is `all()` or `any()`.
+    pub(crate) fn as_constant(&self) -> Option<bool> {
+        match self {
+            CfgExpr::All(children) if children.is_empty() => Some(true),
+            CfgExpr::Any(children) if children.is_empty() => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Evaluates this expression against the given platform.
+    pub(crate) fn eval(&self, platform: &Platform<'_>) -> Option<bool> {
+        self.eval_with(&mut |pred| eval_predicate(&pred.as_predicate(), platform))
+    }
+
+    fn eval_with<T: Logic>(&self, eval_pred: &mut impl FnMut(&CfgPredicate) -> T) -> T {
+        match self {
+            CfgExpr::Pred(pred) => eval_pred(pred),
+            CfgExpr::Not(child) => child.eval_with(eval_pred).not(),
+            CfgExpr::All(children) => children
+                .iter()
+                .fold(T::top(), |acc, child| acc.and(child.eval_with(eval_pred))),
+            CfgExpr::Any(children) => children
+                .iter()
+                .fold(T::bottom(), |acc, child| acc.or(child.eval_with(eval_pred))),
+        }
+    }
+
+    /// Simplifies this expression.
+    ///
+    /// Nested `all()` and `any()` are flattened, duplicate and absorbed subexpressions are removed,
+    /// double negations are dropped, and subexpressions for which `fold` returns a value are replaced with constants
+    /// which are then propagated upwards.
+    pub(crate) fn simplify(&self, fold: &mut impl FnMut(&CfgExpr) -> Option<bool>) -> Self {
+        let simplified = match self {
+            CfgExpr::Pred(_) => self.clone(),
+            CfgExpr::Not(child) => match child.simplify(fold) {
+                CfgExpr::Not(grandchild) => *grandchild,
+                child => match child.as_constant() {
+                    Some(value) => CfgExpr::constant(!value),
+                    None => CfgExpr::Not(Box::new(child)),
+                },
+            },
+            CfgExpr::All(children) => Self::simplify_list(children, true, fold),
+            CfgExpr::Any(children) => Self::simplify_list(children, false, fold),
+        };
+
+        if simplified.as_constant().is_some() {
+            return simplified;
+        }
+        match fold(&simplified) {
+            Some(value) => CfgExpr::constant(value),
+            None => simplified,
+        }
+    }
+
+    /// Simplifies the children of an `all()` if `is_all` is true, or an `any()` otherwise.
+    fn simplify_list(
+        children: &[CfgExpr],
+        is_all: bool,
+        fold: &mut impl FnMut(&CfgExpr) -> Option<bool>,
+    ) -> Self {
+        // For all(), true is the identity and false short-circuits. any() is the dual.
+        let mut simplified = BTreeSet::new();
+        for child in children {
+            let child = child.simplify(fold);
+            match child.as_constant() {
+                Some(value) if value == is_all => continue,
+                Some(_) => return CfgExpr::constant(!is_all),
+                None => {}
+            }
+            match child {
+                CfgExpr::All(grandchildren) if is_all => simplified.extend(grandchildren),
+                CfgExpr::Any(grandchildren) if !is_all => simplified.extend(grandchildren),
+                child => {
+                    simplified.insert(child);
+                }
+            }
+        }
+
+        // all(x, not(x)) is always false, and any(x, not(x)) is always true.
+        let has_complement = simplified.iter().any(|child| match child {
+            CfgExpr::Not(grandchild) => simplified.contains(grandchild.as_ref()),
+            _ => false,
+        });
+        if has_complement {
+            return CfgExpr::constant(!is_all);
+        }
+
+        // any(x, all(x, y)) is any(x), and all(x, any(x, y)) is all(x).
+        let absorbed: Vec<_> = simplified
+            .iter()
+            .filter(|child| match child {
+                CfgExpr::All(grandchildren) if !is_all => grandchildren
+                    .iter()
+                    .any(|grandchild| simplified.contains(grandchild)),
+                CfgExpr::Any(grandchildren) if is_all => grandchildren
+                    .iter()
+                    .any(|grandchild| simplified.contains(grandchild)),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        for child in &absorbed {
+            simplified.remove(child);
+        }
+
+        let mut simplified: Vec<_> = simplified.into_iter().collect();
+        if simplified.len() == 1 {
+            simplified.pop().expect("checked length")
+        } else if is_all {
+            CfgExpr::All(simplified)
+        } else {
+            CfgExpr::Any(simplified)
+        }
+    }
+
+    /// Simplifies this expression, folding subexpressions that are constant over every platform
+    /// in the set.
+    pub(crate) fn simplify_for(&self, platforms: &PlatformSet<'_>) -> Self {
+        self.simplify(&mut |expr| {
+            let mut results = platforms
+                .platforms()
+                .iter()
+                .map(|platform| expr.eval(platform));
+            let first = results.next()??;
+            if results.all(|res| res == Some(first)) {
+                Some(first)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns `Some(value)` if this expression evaluates to `value` on every platform in the set,
+    /// whatever the values of target features, flags and key-value pairs are.
+    ///
+    /// Returns `None` if the set is empty, or if this couldn't be decided within
+    /// `SATISFIABILITY_BUDGET` assignments across all platforms.
+    pub(crate) fn constant_over(&self, platforms: &PlatformSet<'_>) -> Option<bool> {
+        // Many platforms leave behind the same residual, so only check each one once.
+        let residuals: BTreeSet<_> = platforms
+            .platforms()
+            .iter()
+            .map(|platform| {
+                self.simplify(&mut |expr| match expr {
+                    CfgExpr::Pred(pred) => match pred {
+                        // These can be set through RUSTFLAGS and similar, so treat them as unknown.
+                        CfgPredicate::TargetFeature(_)
+                        | CfgPredicate::Flag(_)
+                        | CfgPredicate::KeyValue { .. } => None,
+                        _ => eval_predicate(&pred.as_predicate(), platform),
+                    },
+                    _ => None,
+                })
+            })
+            .collect();
+
+        // The budget is shared across platforms to bound the total work done.
+        let mut budget = SATISFIABILITY_BUDGET;
+        let mut always_true = true;
+        let mut always_false = true;
+        for residual in &residuals {
+            // If satisfiability can't be decided, the expression isn't known to be constant.
+            always_true = always_true
+                && CfgExpr::Not(Box::new(residual.clone())).is_satisfiable(&mut budget)
+                    == Some(false);
+            always_false = always_false && residual.is_satisfiable(&mut budget) == Some(false);
+            if !always_true && !always_false {
+                return None;
+            }
+        }
+
+        if residuals.is_empty() {
+            None
+        } else if always_true {
+            Some(true)
+        } else {
+            Some(false)
+        }
+    }
+
+    /// Returns the predicates in this expression, in the order they appear.
+    pub(crate) fn predicates(&self) -> Vec<&CfgPredicate> {
+        let mut preds = vec![];
+        self.visit_predicates(&mut |pred| preds.push(pred));
+        preds
+    }
+
+    fn visit_predicates<'e>(&'e self, f: &mut impl FnMut(&'e CfgPredicate)) {
+        match self {
+            CfgExpr::Pred(pred) => f(pred),
+            CfgExpr::Not(child) => child.visit_predicates(f),
+            CfgExpr::All(children) | CfgExpr::Any(children) => {
+                for child in children {
+                    child.visit_predicates(f);
+                }
+            }
+        }
+    }
+
+    /// Partially evaluates this expression against a platform, leaving behind the predicates whose
+    /// values are unknown.
+    fn residual(&self, platform: &Platform<'_>) -> Self {
+        self.simplify(&mut |expr| match expr {
+            CfgExpr::Pred(pred) => eval_predicate(&pred.as_predicate(), platform),
+            _ => None,
+        })
+    }
+
+    /// Returns `Some(true)` if some assignment of values to the predicates in this expression makes
+    /// it true, `Some(false)` if none does, and `None` if the budget ran out before this could be
+    /// decided.
+    ///
+    /// This picks a predicate, assigns it each value in turn and simplifies the result, until the
+    /// expression is constant. Each assignment uses up one unit of `budget`. Simplification
+    /// short-circuits most expressions seen in practice well before the budget is exhausted, but
+    /// deciding satisfiability takes exponential time in the worst case, so the budget is needed to
+    /// bound the work done for adversarial inputs.
+    pub(crate) fn is_satisfiable(&self, budget: &mut usize) -> Option<bool> {
+        self.simplify(&mut |_| None)
+            .is_satisfiable_simplified(budget)
+    }
+
+    fn is_satisfiable_simplified(&self, budget: &mut usize) -> Option<bool> {
+        match self {
+            // Simplified expressions with no constants in them are all satisfiable, except for
+            // all(), any() and not() of more complex expressions.
+            CfgExpr::Pred(_) => return Some(true),
+            CfgExpr::Not(child) if matches!(child.as_ref(), CfgExpr::Pred(_)) => return Some(true),
+            CfgExpr::Any(children) if !children.is_empty() => {
+                let mut res = Some(false);
+                for child in children {
+                    match child.is_satisfiable_simplified(budget) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => res = None,
+                    }
+                }
+                return res;
+            }
+            _ => {}
+        }
+        if let Some(value) = self.as_constant() {
+            return Some(value);
+        }
+
+        let pred = self.predicates()[0].clone();
+        let mut res = Some(false);
+        for &value in &[true, false] {
+            if *budget == 0 {
+                return None;
+            }
+            *budget -= 1;
+            let assigned = self.simplify(&mut |expr| match expr {
+                CfgExpr::Pred(other) if other == &pred => Some(value),
+                _ => None,
+            });
+            match assigned.is_satisfiable_simplified(budget) {
+                Some(true) => return Some(true),
+                Some(false) => {}
+                None => res = None,
+            }
+        }
+        res
+    }
+}
+
+impl fmt::Display for CfgExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, children) = match self {
+            CfgExpr::Pred(pred) => return write!(f, "{}", pred),
+            CfgExpr::Not(child) => return write!(f, "not({})", child),
+            CfgExpr::All(children) => ("all", children),
+            CfgExpr::Any(children) => ("any", children),
+        };
+        write!(f, "{}(", name)?;
+        for (idx, child) in children.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", child)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// An owned version of `Predicate`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum CfgPredicate {
+    Family(Family),
+    Os(String),
+    Arch(String),
+    Env(String),
+    Vendor(String),
+    Endian(Endian),
+    PointerWidth(u8),
+    TargetFeature(String),
+    Test,
+    DebugAssertions,
+    ProcMacro,
+    Feature(String),
+    Flag(String),
+    KeyValue { key: String, val: String },
+}
+
+impl CfgPredicate {
+    fn new(pred: &Predicate<'_>) -> Self {
+        match pred {
+            Predicate::Target(target) => match target {
+                TargetPredicate::Family(family) => CfgPredicate::Family(*family),
+                TargetPredicate::Os(os) => CfgPredicate::Os(os.0.to_string()),
+                TargetPredicate::Arch(arch) => CfgPredicate::Arch(arch.0.to_string()),
+                TargetPredicate::Env(env) => CfgPredicate::Env(env.0.to_string()),
+                TargetPredicate::Vendor(vendor) => CfgPredicate::Vendor(vendor.0.to_string()),
+                TargetPredicate::Endian(endian) => CfgPredicate::Endian(*endian),
+                TargetPredicate::PointerWidth(width) => CfgPredicate::PointerWidth(*width),
+            },
+            Predicate::TargetFeature(feature) => CfgPredicate::TargetFeature(feature.to_string()),
+            Predicate::Test => CfgPredicate::Test,
+            Predicate::DebugAssertions => CfgPredicate::DebugAssertions,
+            Predicate::ProcMacro => CfgPredicate::ProcMacro,
+            Predicate::Feature(feature) => CfgPredicate::Feature(feature.to_string()),
+            Predicate::Flag(flag) => CfgPredicate::Flag(flag.to_string()),
+            Predicate::KeyValue { key, val } => CfgPredicate::KeyValue {
+                key: key.to_string(),
+                val: val.to_string(),
+            },
+        }
+    }
+
+    fn as_predicate(&self) -> Predicate<'_> {
+        match self {
+            CfgPredicate::Family(family) => Predicate::Target(TargetPredicate::Family(*family)),
+            CfgPredicate::Os(os) => Predicate::Target(TargetPredicate::Os(Os(os))),
+            CfgPredicate::Arch(arch) => Predicate::Target(TargetPredicate::Arch(Arch(arch))),
+            CfgPredicate::Env(env) => Predicate::Target(TargetPredicate::Env(Env(env))),
+            CfgPredicate::Vendor(vendor) => {
+                Predicate::Target(TargetPredicate::Vendor(Vendor(vendor)))
+            }
+            CfgPredicate::Endian(endian) => Predicate::Target(TargetPredicate::Endian(*endian)),
+            CfgPredicate::PointerWidth(width) => {
+                Predicate::Target(TargetPredicate::PointerWidth(*width))
+            }
+            CfgPredicate::TargetFeature(feature) => Predicate::TargetFeature(feature),
+            CfgPredicate::Test => Predicate::Test,
+            CfgPredicate::DebugAssertions => Predicate::DebugAssertions,
+            CfgPredicate::ProcMacro => Predicate::ProcMacro,
+            CfgPredicate::Feature(feature) => Predicate::Feature(feature),
+            CfgPredicate::Flag(flag) => Predicate::Flag(flag),
+            CfgPredicate::KeyValue { key, val } => Predicate::KeyValue { key, val },
+        }
+    }
+
+    /// Returns `Some(value)` if this predicate has the same value on every platform.
+    pub(crate) fn platform_independent_value(&self) -> Option<bool> {
+        match self {
+            // Cargo always evaluates these to false.
+            CfgPredicate::Test
+            | CfgPredicate::DebugAssertions
+            | CfgPredicate::ProcMacro
+            | CfgPredicate::Feature(_) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CfgPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CfgPredicate::Family(Family::unix) => write!(f, "unix"),
+            CfgPredicate::Family(Family::windows) => write!(f, "windows"),
+            CfgPredicate::Os(os) => write!(f, "target_os = \"{}\"", os),
+            CfgPredicate::Arch(arch) => write!(f, "target_arch = \"{}\"", arch),
+            CfgPredicate::Env(env) => write!(f, "target_env = \"{}\"", env),
+            CfgPredicate::Vendor(vendor) => write!(f, "target_vendor = \"{}\"", vendor),
+            CfgPredicate::Endian(Endian::big) => write!(f, "target_endian = \"big\""),
+            CfgPredicate::Endian(Endian::little) => write!(f, "target_endian = \"little\""),
+            CfgPredicate::PointerWidth(width) => {
+                write!(f, "target_pointer_width = \"{}\"", width)
+            }
+            CfgPredicate::TargetFeature(feature) => write!(f, "target_feature = \"{}\"", feature),
+            CfgPredicate::Test => write!(f, "test"),
+            CfgPredicate::DebugAssertions => write!(f, "debug_assertions"),
+            CfgPredicate::ProcMacro => write!(f, "proc_macro"),
+            CfgPredicate::Feature(feature) => write!(f, "feature = \"{}\"", feature),
+            CfgPredicate::Flag(flag) => write!(f, "{}", flag),
+            CfgPredicate::KeyValue { key, val } => write!(f, "{} = \"{}\"", key, val),
+        }
+    }
+}
+
+/// The number of predicate assignments tried while checking satisfiability, before giving up.
+pub(crate) const SATISFIABILITY_BUDGET: usize = 1 << 14;
+
+/// Returns `Some(true)` if, on every platform in the set, `a` matching means that `b` matches as
+/// well, `Some(false)` if not, and `None` if this couldn't be decided within
+/// `SATISFIABILITY_BUDGET` assignments.
+///
+/// Predicates whose values are unknown on a platform, such as target features, are treated as
+/// independent variables that can take any value.
+pub(crate) fn target_implies(
+    a: &Target<'_>,
+    b: &Target<'_>,
+    platforms: &PlatformSet<'_>,
+) -> Option<bool> {
+    let a = TargetTree::new(a);
+    let b = TargetTree::new(b);
+    let mut budget = SATISFIABILITY_BUDGET;
+    let mut res = Some(true);
+    for platform in platforms.platforms() {
+        // a implies b iff all(a, not(b)) can never be true.
+        let counterexample = CfgExpr::All(vec![
+            a.residual(platform),
+            CfgExpr::Not(Box::new(b.residual(platform))),
+        ]);
+        match counterexample.is_satisfiable(&mut budget) {
+            Some(true) => return Some(false),
+            Some(false) => {}
+            None => res = None,
+        }
+    }
+    res
+}
+
+enum TargetTree<'a> {
+    Triple(&'a str),
+    Expr(CfgExpr),
+}
+
+impl<'a> TargetTree<'a> {
+    fn new(target: &'a Target<'_>) -> Self {
+        match target {
+            Target::TargetInfo(target_info) => TargetTree::Triple(target_info.triple()),
+            Target::Spec(expr) => TargetTree::Expr(CfgExpr::new(expr)),
+        }
+    }
+
+    fn residual(&self, platform: &Platform<'_>) -> CfgExpr {
+        match self {
+            TargetTree::Triple(triple) => CfgExpr::constant(platform.triple() == *triple),
+            TargetTree::Expr(expr) => expr.residual(platform),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PlatformSet, TargetFeatures, TargetSpec};
+
+    fn spec(input: &str) -> TargetSpec<'static> {
+        input.parse().expect("valid spec")
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for (input, expected) in &[
+            ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"),
+            ("cfg(windows)", "cfg(windows)"),
+            ("cfg(target_family = \"unix\")", "cfg(unix)"),
+            (
+                "cfg(  all( unix,target_arch=\"x86_64\" , ))",
+                "cfg(all(unix, target_arch = \"x86_64\"))",
+            ),
+            (
+                "cfg(any(not(target_os = \"linux\"), all(), any(foo, bar = \"baz\")))",
+                "cfg(any(not(target_os = \"linux\"), all(), any(foo, bar = \"baz\")))",
+            ),
+            (
+                "cfg(all(target_endian = \"big\", target_pointer_width = \"32\", target_env = \"\"))",
+                "cfg(all(target_endian = \"big\", target_pointer_width = \"32\", target_env = \"\"))",
+            ),
+            (
+                "cfg(any(test, debug_assertions, proc_macro, feature = \"x\", target_feature = \"y\"))",
+                "cfg(any(test, debug_assertions, proc_macro, feature = \"x\", target_feature = \"y\"))",
+            ),
+        ] {
+            let displayed = spec(input).to_string();
+            assert_eq!(&displayed, expected, "for input {}", input);
+            assert_eq!(
+                spec(&displayed).to_string(),
+                displayed,
+                "display round-trips for input {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn simplify() {
+        for (input, expected) in &[
+            ("cfg(all(all(unix, foo), all(foo)))", "cfg(all(unix, foo))"),
+            ("cfg(not(not(windows)))", "cfg(windows)"),
+            ("cfg(any(windows, not(windows)))", "cfg(all())"),
+            ("cfg(all(windows, not(windows)))", "cfg(any())"),
+            ("cfg(all(unix, any(unix, windows)))", "cfg(unix)"),
+            ("cfg(any(unix, all(test, windows)))", "cfg(unix)"),
+            ("cfg(not(feature = \"foo\"))", "cfg(all())"),
+            ("cfg(any(foo, bar, foo))", "cfg(any(bar, foo))"),
+            ("cfg(all(any(), unix))", "cfg(any())"),
+            ("x86_64-pc-windows-msvc", "x86_64-pc-windows-msvc"),
+        ] {
+            let simplified = spec(input).simplify();
+            assert_eq!(&simplified.to_string(), expected, "for input {}", input);
+            assert_eq!(
+                simplified.is_equivalent(
+                    &spec(input),
+                    &PlatformSet::builtins(TargetFeatures::Unknown)
+                ),
+                Some(true),
+                "simplified spec is equivalent for input {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn simplify_for() {
+        let platforms = PlatformSet::from_triples(
+            vec!["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"],
+            TargetFeatures::features(vec!["sse2"]),
+        )
+        .unwrap();
+
+        for (input, expected) in &[
+            ("cfg(any(unix, windows))", "cfg(all())"),
+            ("cfg(target_arch = \"aarch64\")", "cfg(any())"),
+            (
+                "cfg(all(target_arch = \"x86_64\", target_os = \"linux\"))",
+                "cfg(target_os = \"linux\")",
+            ),
+            ("cfg(target_feature = \"sse2\")", "cfg(all())"),
+            (
+                "cfg(all(windows, bar = \"baz\"))",
+                "cfg(all(windows, bar = \"baz\"))",
+            ),
+        ] {
+            assert_eq!(
+                &spec(input).simplify_for(&platforms).to_string(),
+                expected,
+                "for input {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn implies() {
+        let platforms = PlatformSet::builtins(TargetFeatures::Unknown);
+        for (a, b, expected) in &[
+            ("cfg(target_os = \"macos\")", "cfg(unix)", true),
+            ("cfg(unix)", "cfg(target_os = \"macos\")", false),
+            ("x86_64-apple-darwin", "cfg(target_os = \"macos\")", true),
+            ("cfg(target_os = \"macos\")", "x86_64-apple-darwin", false),
+            (
+                "cfg(all(windows, target_feature = \"sse2\"))",
+                "cfg(windows)",
+                true,
+            ),
+            (
+                "cfg(windows)",
+                "cfg(all(windows, target_feature = \"sse2\"))",
+                false,
+            ),
+            (
+                "cfg(target_feature = \"sse2\")",
+                "cfg(any(target_feature = \"sse2\", target_feature = \"avx\"))",
+                true,
+            ),
+            ("cfg(test)", "cfg(windows)", true),
+            ("cfg(windows)", "cfg(not(proc_macro))", true),
+        ] {
+            assert_eq!(
+                spec(a).implies(&spec(b), &platforms),
+                Some(*expected),
+                "{} implies {}",
+                a,
+                b
+            );
+        }
+
+        assert_eq!(
+            spec("cfg(any(unix, windows, target_os = \"none\"))").is_equivalent(
+                &spec("cfg(any(windows, unix, target_os = \"none\"))"),
+                &platforms
+            ),
+            Some(true)
+        );
+        assert_eq!(
+            spec("cfg(unix)").is_equivalent(&spec("cfg(not(windows))"), &platforms),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn implies_many_unknowns() {
+        let platforms = PlatformSet::builtins(TargetFeatures::Unknown);
+        let features: Vec<_> = (0..100)
+            .map(|idx| format!("target_feature = \"f{}\"", idx))
+            .collect();
+        let any = spec(&format!("cfg(any({}))", features.join(", ")));
+        let all = spec(&format!("cfg(all({}))", features.join(", ")));
+        let mut reversed = features.clone();
+        reversed.reverse();
+        let any_reversed = spec(&format!("cfg(any({}))", reversed.join(", ")));
+
+        assert_eq!(any.is_equivalent(&any_reversed, &platforms), Some(true));
+        assert_eq!(all.implies(&any, &platforms), Some(true));
+        assert_eq!(any.implies(&all, &platforms), Some(false));
+    }
+
+    #[test]
+    fn satisfiable_budget() {
+        let expr = CfgExpr::new(
+            &Expression::parse("all(any(a, b), any(not(a), c), any(not(b), not(c)))")
+                .expect("valid expression"),
+        );
+        assert_eq!(
+            expr.is_satisfiable(&mut SATISFIABILITY_BUDGET.clone()),
+            Some(true)
+        );
+        assert_eq!(expr.is_satisfiable(&mut 0), None, "budget exhausted");
+
+        let unsatisfiable = CfgExpr::new(
+            &Expression::parse("all(any(a, b), not(a), not(b))").expect("valid expression"),
+        );
+        assert_eq!(
+            unsatisfiable.is_satisfiable(&mut SATISFIABILITY_BUDGET.clone()),
+            Some(false)
+        );
+    }
+}
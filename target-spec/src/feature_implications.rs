@@ -0,0 +1,170 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tables of target features implied by other target features.
+//!
+//! These are based on the tables in rustc's `rustc_target::target_features`. Enabling a feature in
+//! rustc also enables the features it implies, so for example `cfg(target_feature = "sse4.1")`
+//! is true whenever `avx2` is enabled.
+
+use std::collections::BTreeSet;
+
+/// Features implied by x86 and x86_64 target features. Only direct implications are listed.
+static X86_IMPLICATIONS: &[(&str, &[&str])] = &[
+    ("aes", &["sse2"]),
+    ("avx", &["sse4.2"]),
+    ("avx2", &["avx"]),
+    ("avx512bf16", &["avx512bw"]),
+    ("avx512bitalg", &["avx512bw"]),
+    ("avx512bw", &["avx512f"]),
+    ("avx512cd", &["avx512f"]),
+    ("avx512dq", &["avx512f"]),
+    ("avx512er", &["avx512f"]),
+    ("avx512f", &["avx2", "f16c", "fma"]),
+    ("avx512fp16", &["avx512bw"]),
+    ("avx512ifma", &["avx512f"]),
+    ("avx512pf", &["avx512f"]),
+    ("avx512vbmi", &["avx512bw"]),
+    ("avx512vbmi2", &["avx512bw"]),
+    ("avx512vl", &["avx512f"]),
+    ("avx512vnni", &["avx512f"]),
+    ("avx512vp2intersect", &["avx512f"]),
+    ("avx512vpopcntdq", &["avx512f"]),
+    ("f16c", &["avx"]),
+    ("fma", &["avx"]),
+    ("gfni", &["sse2"]),
+    ("pclmulqdq", &["sse2"]),
+    ("sha", &["sse2"]),
+    ("sse2", &["sse"]),
+    ("sse3", &["sse2"]),
+    ("sse4.1", &["ssse3"]),
+    ("sse4.2", &["sse4.1"]),
+    ("sse4a", &["sse3"]),
+    ("ssse3", &["sse3"]),
+    ("vaes", &["aes", "avx2"]),
+    ("vpclmulqdq", &["avx", "pclmulqdq"]),
+    ("xsavec", &["xsave"]),
+    ("xsaveopt", &["xsave"]),
+    ("xsaves", &["xsave"]),
+];
+
+/// Features implied by 32-bit ARM target features.
+static ARM_IMPLICATIONS: &[(&str, &[&str])] = &[
+    ("aes", &["neon"]),
+    ("dotprod", &["neon"]),
+    ("fp-armv8", &["vfp4"]),
+    ("i8mm", &["neon"]),
+    ("neon", &["vfp3"]),
+    ("sha2", &["neon"]),
+    ("v6", &["v5te"]),
+    ("v6k", &["v6"]),
+    ("v6t2", &["thumb2", "v6k"]),
+    ("v7", &["v6t2"]),
+    ("v8", &["v7"]),
+    ("vfp3", &["vfp2"]),
+    ("vfp4", &["vfp3"]),
+];
+
+/// Features implied by AArch64 target features.
+static AARCH64_IMPLICATIONS: &[(&str, &[&str])] = &[
+    ("aes", &["neon"]),
+    ("dotprod", &["neon"]),
+    ("f32mm", &["sve"]),
+    ("f64mm", &["sve"]),
+    ("fcma", &["neon"]),
+    ("fhm", &["fp16"]),
+    ("fp16", &["neon"]),
+    ("jsconv", &["neon"]),
+    ("rcpc2", &["rcpc"]),
+    ("rdm", &["neon"]),
+    ("sha2", &["neon"]),
+    ("sha3", &["sha2"]),
+    ("sm4", &["neon"]),
+    ("sve", &["fp16"]),
+    ("sve2", &["sve"]),
+    ("sve2-aes", &["aes", "sve2"]),
+    ("sve2-bitperm", &["sve2"]),
+    ("sve2-sha3", &["sha3", "sve2"]),
+    ("sve2-sm4", &["sm4", "sve2"]),
+];
+
+/// Features implied by RISC-V target features.
+static RISCV_IMPLICATIONS: &[(&str, &[&str])] = &[("d", &["f"]), ("q", &["d"])];
+
+/// Features implied by WebAssembly target features.
+static WASM_IMPLICATIONS: &[(&str, &[&str])] = &[("relaxed-simd", &["simd128"])];
+
+/// Returns the implication table for the given architecture.
+fn implications(arch: &str) -> &'static [(&'static str, &'static [&'static str])] {
+    match arch {
+        "x86" | "x86_64" => X86_IMPLICATIONS,
+        "arm" => ARM_IMPLICATIONS,
+        "aarch64" => AARCH64_IMPLICATIONS,
+        "riscv32" | "riscv64" => RISCV_IMPLICATIONS,
+        "wasm32" | "wasm64" => WASM_IMPLICATIONS,
+        _ => &[],
+    }
+}
+
+/// Returns the given features along with every feature they imply on the given architecture.
+pub(crate) fn implied_closure<'f>(
+    arch: &str,
+    features: impl IntoIterator<Item = &'f str>,
+) -> BTreeSet<&'f str> {
+    let table = implications(arch);
+    let mut closure = BTreeSet::new();
+    let mut stack: Vec<&'f str> = features.into_iter().collect();
+    while let Some(feature) = stack.pop() {
+        if !closure.insert(feature) {
+            continue;
+        }
+        if let Ok(idx) = table.binary_search_by_key(&feature, |&(name, _)| name) {
+            stack.extend(table[idx].1.iter().copied());
+        }
+    }
+    closure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_sorted() {
+        for table in &[
+            X86_IMPLICATIONS,
+            ARM_IMPLICATIONS,
+            AARCH64_IMPLICATIONS,
+            RISCV_IMPLICATIONS,
+            WASM_IMPLICATIONS,
+        ] {
+            for window in table.windows(2) {
+                assert!(
+                    window[0].0 < window[1].0,
+                    "table is sorted and has no duplicates: {} >= {}",
+                    window[0].0,
+                    window[1].0,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closure() {
+        let closure = implied_closure("x86_64", vec!["avx2", "popcnt"]);
+        assert_eq!(
+            closure.into_iter().collect::<Vec<_>>(),
+            vec!["avx", "avx2", "popcnt", "sse", "sse2", "sse3", "sse4.1", "sse4.2", "ssse3"],
+        );
+
+        // Features on one architecture don't imply anything on another.
+        let closure = implied_closure("aarch64", vec!["avx2"]);
+        assert_eq!(closure.into_iter().collect::<Vec<_>>(), vec!["avx2"]);
+
+        let closure = implied_closure("aarch64", vec!["sve2-aes"]);
+        assert_eq!(
+            closure.into_iter().collect::<Vec<_>>(),
+            vec!["aes", "fp16", "neon", "sve", "sve2", "sve2-aes"],
+        );
+    }
+}
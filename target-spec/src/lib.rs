@@ -37,16 +37,26 @@
 #![warn(missing_docs)]
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "custom")]
+mod custom;
+mod detect;
 mod errors;
 mod evaluator;
+mod expr;
+mod feature_implications;
+mod lint;
 mod parser;
 mod platform;
+mod platform_set;
 #[cfg(feature = "proptest1")]
 mod proptest;
+mod rustc_cfg;
 #[cfg(feature = "summaries")]
 pub mod summaries;
 
 pub use errors::*;
 pub use evaluator::*;
+pub use lint::*;
 pub use parser::*;
 pub use platform::*;
+pub use platform_set::*;
@@ -0,0 +1,353 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    expr::{CfgExpr, CfgPredicate},
+    PlatformSet, Target, TargetFeatures,
+};
+use cfg_expr::targets::ALL_BUILTINS;
+use std::{collections::BTreeSet, fmt};
+
+/// A likely mistake in a target spec, as found by `TargetSpec::lint`.
+///
+/// Mistakes in `cfg()` expressions, such as misspelled values, don't cause errors: they silently
+/// evaluate to false. Lints help catch dependencies that would otherwise quietly disappear on some
+/// platforms.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum TargetSpecLint {
+    /// A value for `target_os`, `target_arch`, `target_env` or `target_vendor` isn't used by any
+    /// builtin target.
+    ///
+    /// Such values may still be valid for custom platforms.
+    UnknownValue {
+        /// The key, for example `target_os`.
+        key: &'static str,
+
+        /// The unknown value.
+        value: String,
+
+        /// A known value that is similar to the unknown one, if any.
+        suggestion: Option<&'static str>,
+    },
+
+    /// A bare flag looks like a misspelling of `unix` or `windows`.
+    MisspelledFamily {
+        /// The flag.
+        flag: String,
+
+        /// The family the flag is similar to.
+        suggestion: &'static str,
+    },
+
+    /// A `feature = "..."` predicate was used. Cargo always evaluates these to false in target
+    /// specs.
+    FeaturePredicate {
+        /// The name of the feature.
+        feature: String,
+    },
+
+    /// The expression evaluates to true on every builtin platform, whatever the values of target
+    /// features, flags and key-value pairs.
+    AlwaysTrue,
+
+    /// The expression evaluates to false on every builtin platform, whatever the values of target
+    /// features, flags and key-value pairs.
+    AlwaysFalse,
+}
+
+impl fmt::Display for TargetSpecLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetSpecLint::UnknownValue {
+                key,
+                value,
+                suggestion,
+            } => {
+                write!(f, "unknown value for {}: \"{}\"", key, value)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, " (did you mean \"{}\"?)", suggestion)?;
+                }
+                Ok(())
+            }
+            TargetSpecLint::MisspelledFamily { flag, suggestion } => write!(
+                f,
+                "flag `{}` looks like a misspelling of `{}`",
+                flag, suggestion
+            ),
+            TargetSpecLint::FeaturePredicate { feature } => write!(
+                f,
+                "`feature = \"{}\"` always evaluates to false in target specs",
+                feature
+            ),
+            TargetSpecLint::AlwaysTrue => write!(f, "expression is always true"),
+            TargetSpecLint::AlwaysFalse => write!(f, "expression is always false"),
+        }
+    }
+}
+
+pub(crate) fn lint_target(target: &Target<'_>) -> Vec<TargetSpecLint> {
+    let expr = match target {
+        // Triples are checked while parsing.
+        Target::TargetInfo(_) => return vec![],
+        Target::Spec(expr) => CfgExpr::new(expr),
+    };
+
+    let mut lints = vec![];
+    let mut push = |lint| {
+        if !lints.contains(&lint) {
+            lints.push(lint);
+        }
+    };
+
+    for pred in expr.predicates() {
+        match pred {
+            CfgPredicate::Os(os) => {
+                // Targets without an OS match "none".
+                check_value(
+                    "target_os",
+                    os,
+                    ALL_BUILTINS
+                        .iter()
+                        .map(|info| info.os.map_or("none", |os| os.0)),
+                    &mut push,
+                );
+            }
+            CfgPredicate::Arch(arch) => {
+                check_value(
+                    "target_arch",
+                    arch,
+                    ALL_BUILTINS.iter().map(|info| info.arch.0),
+                    &mut push,
+                );
+            }
+            CfgPredicate::Env(env) => {
+                // An empty env matches targets without one.
+                check_value(
+                    "target_env",
+                    env,
+                    ALL_BUILTINS
+                        .iter()
+                        .map(|info| info.env.map_or("", |env| env.0)),
+                    &mut push,
+                );
+            }
+            CfgPredicate::Vendor(vendor) => {
+                // Targets without a vendor match "unknown".
+                check_value(
+                    "target_vendor",
+                    vendor,
+                    ALL_BUILTINS
+                        .iter()
+                        .map(|info| info.vendor.map_or("unknown", |vendor| vendor.0)),
+                    &mut push,
+                );
+            }
+            CfgPredicate::Flag(flag) => {
+                let suggestion = ["unix", "windows"]
+                    .iter()
+                    .copied()
+                    .find(|family| is_similar(flag, family));
+                if let Some(suggestion) = suggestion {
+                    push(TargetSpecLint::MisspelledFamily {
+                        flag: flag.clone(),
+                        suggestion,
+                    });
+                }
+            }
+            CfgPredicate::Feature(feature) => push(TargetSpecLint::FeaturePredicate {
+                feature: feature.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    match expr.constant_over(&PlatformSet::builtins(TargetFeatures::Unknown)) {
+        Some(true) => push(TargetSpecLint::AlwaysTrue),
+        Some(false) => push(TargetSpecLint::AlwaysFalse),
+        None => {}
+    }
+
+    lints
+}
+
+fn check_value(
+    key: &'static str,
+    value: &str,
+    known: impl IntoIterator<Item = &'static str>,
+    push: &mut impl FnMut(TargetSpecLint),
+) {
+    let known: BTreeSet<_> = known.into_iter().collect();
+    if known.contains(value) {
+        return;
+    }
+    let suggestion = known
+        .iter()
+        .copied()
+        .filter(|known| is_similar(value, known))
+        .min_by_key(|known| edit_distance(value, known));
+    push(TargetSpecLint::UnknownValue {
+        key,
+        value: value.to_string(),
+        suggestion,
+    });
+}
+
+/// Returns true if `value` is different from, but likely a misspelling of, `known`.
+fn is_similar(value: &str, known: &str) -> bool {
+    if value == known {
+        return false;
+    }
+    if value.eq_ignore_ascii_case(known) {
+        return true;
+    }
+    let max_distance = if known.len() <= 3 { 1 } else { 2 };
+    edit_distance(value, known) <= max_distance
+}
+
+/// Returns the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev[j] + if a_char == *b_char { 0 } else { 1 };
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetSpec;
+
+    fn lint(spec: &str) -> Vec<TargetSpecLint> {
+        spec.parse::<TargetSpec>().expect("valid spec").lint()
+    }
+
+    #[test]
+    fn lint_values() {
+        assert_eq!(lint("cfg(target_os = \"macos\")"), vec![]);
+        assert_eq!(lint("x86_64-unknown-linux-gnu"), vec![]);
+        assert_eq!(
+            lint("cfg(any(target_os = \"macosx\", target_arch = \"x86-64\"))"),
+            vec![
+                TargetSpecLint::UnknownValue {
+                    key: "target_os",
+                    value: "macosx".to_string(),
+                    suggestion: Some("macos"),
+                },
+                TargetSpecLint::UnknownValue {
+                    key: "target_arch",
+                    value: "x86-64".to_string(),
+                    suggestion: Some("x86_64"),
+                },
+                TargetSpecLint::AlwaysFalse,
+            ],
+        );
+        assert_eq!(
+            lint("cfg(any(unix, target_env = \"\", target_vendor = \"unknown\"))"),
+            vec![],
+            "empty env and unknown vendor are valid"
+        );
+        assert_eq!(
+            lint("cfg(target_os = \"none\")"),
+            vec![],
+            "none is valid for bare-metal targets"
+        );
+        assert_eq!(
+            lint("cfg(all(target_arch = \"arm\", target_os = \"none\"))"),
+            vec![],
+            "none is valid for bare-metal ARM targets"
+        );
+        assert_eq!(
+            lint("cfg(any(unix, target_vendor = \"acme\"))"),
+            vec![TargetSpecLint::UnknownValue {
+                key: "target_vendor",
+                value: "acme".to_string(),
+                suggestion: None,
+            }],
+        );
+    }
+
+    #[test]
+    fn lint_flags() {
+        assert_eq!(
+            lint("cfg(any(unix, windos))"),
+            vec![TargetSpecLint::MisspelledFamily {
+                flag: "windos".to_string(),
+                suggestion: "windows",
+            }],
+        );
+        assert_eq!(
+            lint("cfg(not(Unix))"),
+            vec![TargetSpecLint::MisspelledFamily {
+                flag: "Unix".to_string(),
+                suggestion: "unix",
+            }],
+        );
+        assert_eq!(lint("cfg(cargo_web)"), vec![], "other flags are fine");
+    }
+
+    #[test]
+    fn lint_constant() {
+        assert_eq!(
+            lint("cfg(feature = \"foo\")"),
+            vec![
+                TargetSpecLint::FeaturePredicate {
+                    feature: "foo".to_string()
+                },
+                TargetSpecLint::AlwaysFalse,
+            ],
+        );
+        assert_eq!(
+            lint("cfg(any(unix, not(unix)))"),
+            vec![TargetSpecLint::AlwaysTrue]
+        );
+        assert_eq!(
+            lint("cfg(all(target_os = \"linux\", target_os = \"macos\"))"),
+            vec![TargetSpecLint::AlwaysFalse],
+        );
+        assert_eq!(
+            lint("cfg(all(target_os = \"windows\", not(windows)))"),
+            vec![TargetSpecLint::AlwaysFalse],
+        );
+        assert_eq!(
+            lint("cfg(any(target_pointer_width = \"16\", target_pointer_width = \"32\", target_pointer_width = \"64\"))"),
+            vec![TargetSpecLint::AlwaysTrue],
+        );
+        // Flags, key-value pairs and target features can be set externally.
+        assert_eq!(lint("cfg(all(foo, target_feature = \"sse2\"))"), vec![]);
+        assert_eq!(lint("cfg(my_key = \"value\")"), vec![]);
+    }
+
+    #[test]
+    fn lint_many_unknowns() {
+        let features: Vec<_> = (0..200)
+            .map(|idx| format!("target_feature = \"f{}\"", idx))
+            .collect();
+        let features = features.join(", ");
+        assert_eq!(lint(&format!("cfg(any({}))", features)), vec![]);
+        assert_eq!(lint(&format!("cfg(all(unix, {}))", features)), vec![]);
+        assert_eq!(
+            lint(&format!("cfg(all(unix, windows, any({})))", features)),
+            vec![TargetSpecLint::AlwaysFalse],
+        );
+        assert_eq!(
+            lint(&format!("cfg(any(unix, not(unix), all({})))", features)),
+            vec![TargetSpecLint::AlwaysTrue],
+        );
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("windos", "windows"), 1);
+        assert_eq!(edit_distance("macosx", "macos"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -1,9 +1,17 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{custom_platforms::TargetInfo, eval_target, Error, Platform};
-use cfg_expr::{targets::get_builtin_target_by_triple, Expression, Predicate};
-use std::{str::FromStr, sync::Arc};
+use crate::{
+    custom_platforms::TargetInfo,
+    eval_target,
+    expr::{target_implies, CfgExpr},
+    lint::lint_target,
+    platform::PlatformTargetInfo,
+    Error, ExpressionParseError, Platform, PlatformMatches, PlatformSet, TargetFeatures,
+    TargetSpecLint,
+};
+use cfg_expr::{targets::get_builtin_target_by_triple, Expression};
+use std::{fmt, str::FromStr, sync::Arc};
 
 /// A parsed target specification or triple, as found in a `Cargo.toml` file.
 ///
@@ -31,6 +39,25 @@ use std::{str::FromStr, sync::Arc};
 /// assert_eq!(spec.eval(&x86_64_mac), Some(false), "x86_64 MacOS matches no features");
 /// assert_eq!(spec.eval(&i686_linux), Some(true), "i686 Linux matches some features");
 /// ```
+///
+/// ## Serialization
+///
+/// If the `summaries` feature is enabled, `TargetSpec` can be serialized and deserialized as a
+/// string, using the normalized form produced by its `Display` implementation. Specs that refer
+/// to custom platforms can't be serialized, since `FromStr` only knows about builtin platforms.
+///
+/// ```
+/// # #[cfg(feature = "summaries")] {
+/// use target_spec::TargetSpec;
+///
+/// let spec: TargetSpec = "cfg(any( unix,target_os = \"redox\"))".parse().unwrap();
+/// let serialized = serde_json::to_string(&spec).unwrap();
+/// assert_eq!(serialized, r#""cfg(any(unix, target_os = \"redox\"))""#);
+///
+/// let deserialized: TargetSpec = serde_json::from_str(&serialized).unwrap();
+/// assert_eq!(deserialized.to_string(), spec.to_string());
+/// # }
+/// ```
 #[derive(Clone, Debug)]
 pub struct TargetSpec<'a> {
     target: Target<'a>,
@@ -44,9 +71,37 @@ impl<'a> TargetSpec<'a> {
     ///
     /// Custom platforms are often found in embedded and similar environments. For built-in
     /// platforms, the `FromStr` implementation is recommended instead.
+    ///
+    /// To create a custom target spec that doesn't borrow from `target_info`, use
+    /// `custom_owned`.
     pub fn custom(target_info: &'a TargetInfo<'a>) -> Self {
         Self {
-            target: Target::TargetInfo(target_info),
+            target: Target::TargetInfo(PlatformTargetInfo::Borrowed(target_info)),
+        }
+    }
+
+    /// Converts this specification into one that doesn't borrow any data.
+    ///
+    /// Custom target information is copied if necessary.
+    pub fn into_owned(self) -> TargetSpec<'static> {
+        let target = match self.target {
+            Target::TargetInfo(PlatformTargetInfo::Borrowed(target_info)) => {
+                Target::TargetInfo(PlatformTargetInfo::new_owned(target_info))
+            }
+            Target::TargetInfo(PlatformTargetInfo::Owned(target_info)) => {
+                Target::TargetInfo(PlatformTargetInfo::Owned(target_info))
+            }
+            Target::Spec(expr) => Target::Spec(expr),
+        };
+        TargetSpec { target }
+    }
+
+    /// Returns true if this is an exact spec for a custom platform, created by `custom` or
+    /// `custom_owned`.
+    pub fn is_custom(&self) -> bool {
+        match &self.target {
+            Target::TargetInfo(target_info) => target_info.is_custom(),
+            Target::Spec(_) => false,
         }
     }
 
@@ -58,6 +113,248 @@ impl<'a> TargetSpec<'a> {
     pub fn eval(&self, platform: &Platform<'_>) -> Option<bool> {
         eval_target(&self.target, platform)
     }
+
+    /// Evaluates this specification against every builtin platform, with unknown target features.
+    ///
+    /// For more control over the set of platforms, see `eval_platforms`.
+    pub fn matching_platforms(&self) -> PlatformMatches<'static> {
+        PlatformSet::builtins(TargetFeatures::Unknown).eval(self)
+    }
+
+    /// Evaluates this specification against every platform in the given set.
+    pub fn eval_platforms<'b>(&self, platforms: &PlatformSet<'b>) -> PlatformMatches<'b> {
+        platforms.eval(self)
+    }
+
+    /// Returns a simplified version of this specification.
+    ///
+    /// Nested `all()` and `any()` expressions are flattened, duplicate and redundant predicates are
+    /// removed, and predicates that Cargo always evaluates to false (such as `test` and
+    /// `feature = "..."`) are folded away. Predicates are sorted into a canonical order, so
+    /// equivalent specs that only differ in ordering simplify to the same result.
+    ///
+    /// A spec that always matches simplifies to `cfg(all())`, and one that never matches to
+    /// `cfg(any())`. Triples are returned unchanged.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpec;
+    ///
+    /// let spec: TargetSpec = "cfg(any(all(unix, target_arch = \"x86_64\"), any(windows, unix), test))"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     spec.simplify().to_string(),
+    ///     "cfg(any(unix, windows))",
+    /// );
+    /// ```
+    pub fn simplify(&self) -> Self {
+        self.map_expr(|expr| {
+            expr.simplify(&mut |expr| match expr {
+                CfgExpr::Pred(pred) => pred.platform_independent_value(),
+                _ => None,
+            })
+        })
+    }
+
+    /// Returns a version of this specification simplified for the given set of platforms.
+    ///
+    /// In addition to the simplifications performed by `simplify`, subexpressions that evaluate to
+    /// the same value on every platform in the set are replaced with constants. For example,
+    /// `cfg(any(unix, windows))` simplifies to `cfg(all())` if every platform in the set is
+    /// either Unix or Windows.
+    ///
+    /// Subexpressions that depend on unknown target features or key-value pairs are left in
+    /// place.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::{PlatformSet, TargetFeatures, TargetSpec};
+    ///
+    /// let platforms = PlatformSet::from_triples(
+    ///     vec!["x86_64-unknown-linux-gnu", "x86_64-apple-darwin"],
+    ///     TargetFeatures::Unknown,
+    /// ).unwrap();
+    ///
+    /// let spec: TargetSpec = "cfg(all(unix, target_pointer_width = \"64\", target_feature = \"sse2\"))"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(spec.simplify_for(&platforms).to_string(), "cfg(target_feature = \"sse2\")");
+    /// ```
+    pub fn simplify_for(&self, platforms: &PlatformSet<'_>) -> Self {
+        if platforms.is_empty() {
+            return self.simplify();
+        }
+        self.map_expr(|expr| expr.simplify_for(platforms))
+    }
+
+    /// Returns `Some(true)` if, on every platform in the given set, this specification matching
+    /// means that `other` matches as well, and `Some(false)` if it doesn't.
+    ///
+    /// Predicates whose values aren't known for a platform, such as unknown target features, are
+    /// assumed to be able to take any value. For example, `cfg(all(unix, target_feature = "sse2"))`
+    /// implies `cfg(unix)` even if target features are unknown, but the reverse isn't true.
+    ///
+    /// Checking implication can take time exponential in the number of predicates with unknown
+    /// values, so the work done is bounded. Returns `None` if the answer couldn't be found within
+    /// that bound. This doesn't happen for specs seen in practice.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::{PlatformSet, TargetFeatures, TargetSpec};
+    ///
+    /// let platforms = PlatformSet::builtins(TargetFeatures::Unknown);
+    /// let linux: TargetSpec = "cfg(target_os = \"linux\")".parse().unwrap();
+    /// let unix: TargetSpec = "cfg(unix)".parse().unwrap();
+    /// let triple: TargetSpec = "x86_64-unknown-linux-gnu".parse().unwrap();
+    ///
+    /// assert_eq!(linux.implies(&unix, &platforms), Some(true));
+    /// assert_eq!(unix.implies(&linux, &platforms), Some(false));
+    /// assert_eq!(triple.implies(&linux, &platforms), Some(true));
+    /// ```
+    pub fn implies(&self, other: &TargetSpec<'_>, platforms: &PlatformSet<'_>) -> Option<bool> {
+        target_implies(&self.target, &other.target, platforms)
+    }
+
+    /// Returns `Some(true)` if this specification and `other` match exactly the same platforms in
+    /// the given set, for any values of predicates that aren't known, and `Some(false)` if they
+    /// don't.
+    ///
+    /// Returns `None` if the answer couldn't be found within a bounded amount of work: see
+    /// `implies` for more.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::{PlatformSet, TargetFeatures, TargetSpec};
+    ///
+    /// let platforms = PlatformSet::builtins(TargetFeatures::Unknown);
+    /// let a: TargetSpec = "cfg(not(any(windows, target_feature = \"sse2\")))".parse().unwrap();
+    /// let b: TargetSpec = "cfg(all(not(target_feature = \"sse2\"), not(windows)))".parse().unwrap();
+    /// assert_eq!(a.is_equivalent(&b, &platforms), Some(true));
+    /// ```
+    pub fn is_equivalent(
+        &self,
+        other: &TargetSpec<'_>,
+        platforms: &PlatformSet<'_>,
+    ) -> Option<bool> {
+        match self.implies(other, platforms) {
+            Some(false) => Some(false),
+            forward => match other.implies(self, platforms) {
+                Some(false) => Some(false),
+                backward => forward.and(backward),
+            },
+        }
+    }
+
+    /// Checks this specification for likely mistakes, such as misspelled values.
+    ///
+    /// Mistakes in `cfg()` expressions don't cause parse errors: for example, `cfg(windos)` is a
+    /// valid expression that checks for a flag called `windos`, and always evaluates to false in a
+    /// default build. See `TargetSpecLint` for the kinds of mistakes found.
+    ///
+    /// Returns an empty list if no mistakes were found, or if this is a triple.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::{TargetSpec, TargetSpecLint};
+    ///
+    /// let spec: TargetSpec = "cfg(any(windos, target_os = \"macosx\"))".parse().unwrap();
+    /// assert_eq!(
+    ///     spec.lint(),
+    ///     vec![
+    ///         TargetSpecLint::MisspelledFamily {
+    ///             flag: "windos".to_string(),
+    ///             suggestion: "windows",
+    ///         },
+    ///         TargetSpecLint::UnknownValue {
+    ///             key: "target_os",
+    ///             value: "macosx".to_string(),
+    ///             suggestion: Some("macos"),
+    ///         },
+    ///     ],
+    /// );
+    /// ```
+    pub fn lint(&self) -> Vec<TargetSpecLint> {
+        lint_target(&self.target)
+    }
+
+    fn map_expr(&self, f: impl FnOnce(CfgExpr) -> CfgExpr) -> Self {
+        match &self.target {
+            Target::TargetInfo(_) => self.clone(),
+            Target::Spec(expr) => {
+                let expr = f(CfgExpr::new(expr));
+                let expr = Expression::parse(&format!("cfg({})", expr))
+                    .expect("displayed expressions are always valid");
+                Self {
+                    target: Target::Spec(Arc::new(expr)),
+                }
+            }
+        }
+    }
+}
+
+/// Displays a normalized form of this specification.
+///
+/// Triples are displayed as is. `cfg()` expressions have their whitespace normalized, and `unix`
+/// and `windows` are displayed in their short forms. The output can be parsed back into an
+/// equivalent `TargetSpec` through its `FromStr` implementation (other than for custom platforms,
+/// which aren't known to `FromStr`).
+impl<'a> fmt::Display for TargetSpec<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target {
+            Target::TargetInfo(target_info) => write!(f, "{}", target_info.triple()),
+            Target::Spec(expr) => write!(f, "cfg({})", CfgExpr::new(expr)),
+        }
+    }
+}
+
+#[cfg(feature = "summaries")]
+mod serde_impl {
+    use super::*;
+    use serde::{
+        de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    impl<'a> Serialize for TargetSpec<'a> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if self.is_custom() {
+                return Err(S::Error::custom(format!(
+                    "target spec for custom platform {} cannot be serialized",
+                    self
+                )));
+            }
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TargetSpec<'static> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let input = String::deserialize(deserializer)?;
+            input.parse().map_err(D::Error::custom)
+        }
+    }
+}
+
+impl TargetSpec<'static> {
+    /// Creates a new exact, custom target spec that owns a copy of `target_info`.
+    ///
+    /// Unlike `custom`, the returned spec doesn't borrow from `target_info`.
+    pub fn custom_owned(target_info: &TargetInfo<'_>) -> Self {
+        Self {
+            target: Target::TargetInfo(PlatformTargetInfo::new_owned(target_info)),
+        }
+    }
 }
 
 impl FromStr for TargetSpec<'static> {
@@ -72,7 +369,7 @@ impl FromStr for TargetSpec<'static> {
 
 #[derive(Clone, Debug)]
 pub(crate) enum Target<'a> {
-    TargetInfo(&'a TargetInfo<'a>),
+    TargetInfo(PlatformTargetInfo<'a>),
     Spec(Arc<Expression>),
 }
 
@@ -80,13 +377,14 @@ impl Target<'static> {
     /// Parses this expression into a `Target` instance.
     fn parse(input: &str) -> Result<Self, Error> {
         if input.starts_with("cfg(") {
-            let expr = Expression::parse(input).map_err(Error::InvalidCfg)?;
+            let expr = Expression::parse(input)
+                .map_err(|err| Error::InvalidCfg(ExpressionParseError::new(input, err)))?;
             Self::verify_expr(expr)
         } else {
-            Ok(Target::TargetInfo(
+            Ok(Target::TargetInfo(PlatformTargetInfo::Borrowed(
                 get_builtin_target_by_triple(input)
                     .ok_or_else(|| Error::UnknownTargetTriple(input.to_string()))?,
-            ))
+            )))
         }
     }
 }
@@ -94,13 +392,9 @@ impl Target<'static> {
 impl<'a> Target<'a> {
     /// Verify this `cfg()` expression.
     fn verify_expr(expr: Expression) -> Result<Self, Error> {
-        // Error out on unknown key-value pairs. Everything else is recognized (though
-        // DebugAssertions/ProcMacro etc always returns false, and flags return false by default).
-        for pred in expr.predicates() {
-            if let Predicate::KeyValue { key, .. } = pred {
-                return Err(Error::UnknownPredicate(key.to_string()));
-            }
-        }
+        // All predicates are currently recognized (though DebugAssertions/ProcMacro etc always
+        // return false, flags return false by default, and key-value pairs for unknown keys
+        // return None).
         Ok(Target::Spec(Arc::new(expr)))
     }
 }
@@ -108,17 +402,18 @@ impl<'a> Target<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cfg_expr::{
-        targets::{Family, Os},
-        Predicate, TargetPredicate,
+    use crate::{
+        custom_platforms::{Arch, Endian, Env, Family, Os, Vendor},
+        ExpressionParseErrorKind,
     };
+    use cfg_expr::{Predicate, TargetPredicate};
 
     #[test]
     fn test_triple() {
         let res = Target::parse("x86_64-apple-darwin");
         assert!(matches!(
             res,
-            Ok(Target::TargetInfo(target_info)) if target_info.triple == "x86_64-apple-darwin"
+            Ok(Target::TargetInfo(target_info)) if target_info.triple() == "x86_64-apple-darwin"
         ));
     }
 
@@ -184,9 +479,20 @@ mod tests {
     }
 
     #[test]
-    fn test_unknown_predicate() {
-        let err = Target::parse("cfg(bogus_key = \"bogus_value\")").expect_err("unknown predicate");
-        assert_eq!(err, Error::UnknownPredicate("bogus_key".to_string()));
+    fn test_key_value() {
+        let expr = match Target::parse("cfg(bogus_key = \"bogus_value\")").unwrap() {
+            Target::TargetInfo(target_info) => {
+                panic!("expected spec, got target info: {:?}", target_info)
+            }
+            Target::Spec(expr) => expr,
+        };
+        assert_eq!(
+            expr.predicates().collect::<Vec<_>>(),
+            vec![Predicate::KeyValue {
+                key: "bogus_key",
+                val: "bogus_value"
+            }],
+        );
     }
 
     #[test]
@@ -195,6 +501,109 @@ mod tests {
         res.expect_err("extra content at the end");
     }
 
+    #[test]
+    fn test_error_span() {
+        for (input, span_str, expected_kind) in &[
+            (
+                "cfg(any(unix, = \"foo\"))",
+                "=",
+                ExpressionParseErrorKind::Unexpected {
+                    expected: vec![")", "<key>"],
+                },
+            ),
+            (
+                "cfg(target_pointer_width = \"sixty-four\")",
+                "sixty-four",
+                ExpressionParseErrorKind::InvalidInteger,
+            ),
+            (
+                "cfg(target_family = \"windos\")",
+                "target_family",
+                ExpressionParseErrorKind::Unexpected {
+                    expected: vec!["unix", "windows"],
+                },
+            ),
+            (
+                "cfg(not(unix, windows))",
+                "not(unix, windows)",
+                ExpressionParseErrorKind::InvalidNot(2),
+            ),
+        ] {
+            let err = match Target::parse(input) {
+                Err(Error::InvalidCfg(err)) => err,
+                other => panic!("for input {}, expected parse error, got {:?}", input, other),
+            };
+            assert_eq!(&err.input, input);
+            assert_eq!(
+                &&input[err.span.clone()],
+                span_str,
+                "span matches for input {}",
+                input
+            );
+            assert_eq!(&err.kind, expected_kind, "kind matches for input {}", input);
+        }
+    }
+
+    #[test]
+    fn test_owned() {
+        let target_info = TargetInfo {
+            triple: "x86_64-custom-linux-gnu",
+            os: Some(Os::linux),
+            arch: Arch::x86_64,
+            env: Some(Env::gnu),
+            vendor: Some(Vendor("custom")),
+            family: Some(Family::unix),
+            pointer_width: 64,
+            endian: Endian::little,
+        };
+        let owned = TargetSpec::custom(&target_info).into_owned();
+        assert!(owned.is_custom(), "custom spec stays custom");
+        assert!(matches!(
+            owned.target,
+            Target::TargetInfo(PlatformTargetInfo::Owned(_))
+        ));
+
+        let builtin =
+            get_builtin_target_by_triple("x86_64-unknown-linux-gnu").expect("known triple");
+        let owned = TargetSpec::custom(builtin).into_owned();
+        assert!(!owned.is_custom(), "builtin spec is not custom");
+        assert!(matches!(
+            owned.target,
+            Target::TargetInfo(PlatformTargetInfo::Borrowed(_))
+        ));
+    }
+
+    #[cfg(feature = "summaries")]
+    #[test]
+    fn test_serde() {
+        for input in &[
+            "x86_64-unknown-linux-gnu",
+            "cfg(windows)",
+            "cfg(all(not(target_os = \"linux\"), any(foo, bar = \"baz\")))",
+        ] {
+            let spec: TargetSpec = input.parse().unwrap();
+            let serialized = serde_json::to_string(&spec).expect("serialization succeeded");
+            let deserialized: TargetSpec =
+                serde_json::from_str(&serialized).expect("deserialization succeeded");
+            assert_eq!(&deserialized.to_string(), input);
+        }
+
+        let target_info = TargetInfo {
+            triple: "x86_64-custom-linux-gnu",
+            os: Some(Os::linux),
+            arch: Arch::x86_64,
+            env: None,
+            vendor: None,
+            family: Some(Family::unix),
+            pointer_width: 64,
+            endian: Endian::little,
+        };
+        serde_json::to_string(&TargetSpec::custom_owned(&target_info))
+            .expect_err("custom specs can't be serialized");
+        serde_json::from_str::<TargetSpec>("\"cfg(any(unix\"")
+            .expect_err("invalid specs can't be deserialized");
+    }
+
     #[test]
     fn test_incomplete() {
         // This fails because the ) at the end is missing.
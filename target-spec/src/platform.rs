@@ -1,10 +1,17 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::Error;
+use crate::{detect::detect_target_features, feature_implications::implied_closure, Error};
 use cfg_expr::targets::get_builtin_target_by_triple;
-use custom_platforms::TargetInfo;
-use std::{borrow::Cow, collections::BTreeSet, ops::Deref};
+use custom_platforms::{Arch, Endian, Env, Family, Os, TargetInfo, Vendor};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::Arc,
+};
 
 // This is generated by the build script.
 include!(concat!(env!("OUT_DIR"), "/current_platform.rs"));
@@ -19,11 +26,20 @@ pub mod custom_platforms {
 }
 
 /// A platform to evaluate target specs against.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+///
+/// Platforms compare equal if their target information, target features, flags, key-value pairs
+/// and target feature matching modes are the same, regardless of whether the target information
+/// is borrowed or owned.
+#[derive(Clone, Debug)]
 pub struct Platform<'a> {
-    target_info: &'a TargetInfo<'a>,
+    target_info: PlatformTargetInfo<'a>,
     target_features: TargetFeatures,
+    // target_features along with every feature they imply, computed once up front since target
+    // features are looked up for every predicate evaluated.
+    implied_features: TargetFeatures,
     flags: BTreeSet<Cow<'static, str>>,
+    key_values: BTreeMap<Cow<'static, str>, BTreeSet<Cow<'static, str>>>,
+    exact_target_features: bool,
     is_custom: bool,
 }
 
@@ -33,13 +49,26 @@ impl Platform<'static> {
     /// Returns `None` if this platform wasn't known to `target-spec`.
     pub fn new(triple: impl AsRef<str>, target_features: TargetFeatures) -> Result<Self, Error> {
         let triple = triple.as_ref();
-        Ok(Self {
-            target_info: get_builtin_target_by_triple(triple)
-                .ok_or_else(|| Error::UnknownPlatformTriple(triple.to_string()))?,
+        let target_info = get_builtin_target_by_triple(triple)
+            .ok_or_else(|| Error::UnknownPlatformTriple(triple.to_string()))?;
+        Ok(Self::new_impl(
+            PlatformTargetInfo::Borrowed(target_info),
             target_features,
-            flags: BTreeSet::new(),
-            is_custom: false,
-        })
+            false,
+        ))
+    }
+
+    /// Creates a new, custom platform that owns a copy of `target_info`.
+    ///
+    /// Unlike `custom`, the returned platform doesn't borrow from `target_info`. This is useful
+    /// for custom platforms that are constructed at runtime, for example from a rustc target JSON
+    /// file.
+    pub fn custom_owned(target_info: &TargetInfo<'_>, target_features: TargetFeatures) -> Self {
+        Self::new_impl(
+            PlatformTargetInfo::Owned(Arc::new(OwnedTargetInfo::new(target_info))),
+            target_features,
+            true,
+        )
     }
 }
 
@@ -48,13 +77,14 @@ impl<'a> Platform<'a> {
     ///
     /// Custom platforms are often found in embedded and similar environments. For built-in
     /// platforms, `new` is recommended instead.
+    ///
+    /// To create a custom platform that doesn't borrow from `target_info`, use `custom_owned`.
     pub fn custom(target_info: &'a TargetInfo<'a>, target_features: TargetFeatures) -> Self {
-        Self {
-            target_info,
+        Self::new_impl(
+            PlatformTargetInfo::Borrowed(target_info),
             target_features,
-            flags: BTreeSet::new(),
-            is_custom: true,
-        }
+            true,
+        )
     }
 
     /// Adds a set of flags to accept.
@@ -69,8 +99,8 @@ impl<'a> Platform<'a> {
     }
 
     /// Returns the target triple for this platform.
-    pub fn triple(&self) -> &'a str {
-        self.target_info.triple
+    pub fn triple(&self) -> &str {
+        self.target_info.triple()
     }
 
     /// Returns the set of flags enabled for this platform.
@@ -83,9 +113,63 @@ impl<'a> Platform<'a> {
         self.flags.contains(flag.as_ref())
     }
 
+    /// Adds a set of key-value pairs to accept.
+    ///
+    /// A key-value pair is a predicate like the `foo = "bar"` in `cfg(foo = "bar")`, for keys that
+    /// aren't part of the target information. Keys may have more than one value, for example
+    /// `target_has_atomic`.
+    ///
+    /// Keys with at least one value are known: see `key_value_matches` for more.
+    pub fn add_key_values(
+        &mut self,
+        key_values: impl IntoIterator<
+            Item = (impl Into<Cow<'static, str>>, impl Into<Cow<'static, str>>),
+        >,
+    ) {
+        for (key, value) in key_values {
+            self.key_values
+                .entry(key.into())
+                .or_default()
+                .insert(value.into());
+        }
+    }
+
+    /// Marks a set of keys as known, without adding any values for them.
+    ///
+    /// Predicates on known keys evaluate to false unless a matching value is added through
+    /// `add_key_values`.
+    pub fn add_known_keys(&mut self, keys: impl IntoIterator<Item = impl Into<Cow<'static, str>>>) {
+        for key in keys {
+            self.key_values.entry(key.into()).or_default();
+        }
+    }
+
+    /// Returns the set of key-value pairs enabled for this platform, sorted by key.
+    pub fn key_values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.key_values
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key.deref(), value.deref())))
+    }
+
+    /// Returns the set of keys known to this platform, including ones without any values.
+    pub fn known_keys(&self) -> impl Iterator<Item = &str> {
+        self.key_values.keys().map(|key| key.deref())
+    }
+
+    /// Returns `Some(true)` if this key-value pair is a match, `Some(false)` if it isn't, and
+    /// `None` if the key is unknown.
+    ///
+    /// This follows the same logic as `TargetFeatures::matches`. A key is known if it was passed
+    /// in to `add_key_values` or `add_known_keys`.
+    pub fn key_value_matches(&self, key: &str, value: &str) -> Option<bool> {
+        self.key_values
+            .get(key)
+            .map(|values| values.contains(value))
+    }
+
     /// Returns the underlying `TargetInfo`.
-    pub fn target_info(&self) -> &'a TargetInfo<'a> {
-        self.target_info
+    pub fn target_info(&self) -> TargetInfo<'_> {
+        self.target_info.target_info()
     }
 
     /// Returns the set of target features for this platform.
@@ -93,10 +177,108 @@ impl<'a> Platform<'a> {
         &self.target_features
     }
 
-    /// Returns true if this is a custom platform, created by `Platform::custom`.
+    /// Sets whether target features are matched exactly.
+    ///
+    /// By default, a target feature matches if it is either in the set of target features for
+    /// this platform or implied by one of them on this platform's architecture, the way rustc
+    /// enables them. For example, `cfg(target_feature = "sse4.1")` matches an x86_64 platform
+    /// declared with `avx2`. If `exact` is true, only the target features in the set match.
+    pub fn set_exact_target_features(&mut self, exact: bool) {
+        self.exact_target_features = exact;
+    }
+
+    /// Returns true if target features are matched exactly, as set by
+    /// `set_exact_target_features`.
+    pub fn exact_target_features(&self) -> bool {
+        self.exact_target_features
+    }
+
+    /// Returns `Some(true)` if this target feature matches this platform, `Some(false)` if it
+    /// doesn't, and `None` if the set of target features is unknown.
+    ///
+    /// Unless `set_exact_target_features` was used to turn this off, features implied by the
+    /// platform's target features match as well: see `TargetFeatures::matches_implied` for more.
+    pub fn target_feature_matches(&self, feature: &str) -> Option<bool> {
+        if self.exact_target_features {
+            self.target_features.matches(feature)
+        } else {
+            self.implied_features.matches(feature)
+        }
+    }
+
+    /// Returns true if this is a custom platform, created by `Platform::custom` or
+    /// `Platform::custom_owned`.
     pub fn is_custom(&self) -> bool {
         self.is_custom
     }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn new_impl(
+        target_info: PlatformTargetInfo<'a>,
+        target_features: TargetFeatures,
+        is_custom: bool,
+    ) -> Self {
+        let implied_features = target_features.with_implied(target_info.target_info().arch);
+        Self {
+            target_info,
+            target_features,
+            implied_features,
+            flags: BTreeSet::new(),
+            key_values: BTreeMap::new(),
+            exact_target_features: false,
+            is_custom,
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn cmp_key(
+        &self,
+    ) -> (
+        TargetInfo<'_>,
+        &TargetFeatures,
+        &BTreeSet<Cow<'static, str>>,
+        &BTreeMap<Cow<'static, str>, BTreeSet<Cow<'static, str>>>,
+        bool,
+        bool,
+    ) {
+        (
+            self.target_info(),
+            &self.target_features,
+            &self.flags,
+            &self.key_values,
+            self.exact_target_features,
+            self.is_custom,
+        )
+    }
+}
+
+impl<'a> PartialEq for Platform<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp_key() == other.cmp_key()
+    }
+}
+
+impl<'a> Eq for Platform<'a> {}
+
+impl<'a> PartialOrd for Platform<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Platform<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_key().cmp(&other.cmp_key())
+    }
+}
+
+impl<'a> Hash for Platform<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cmp_key().hash(state)
+    }
 }
 
 impl Platform<'static> {
@@ -107,12 +289,137 @@ impl Platform<'static> {
     pub fn current() -> Option<Self> {
         let target_info = get_builtin_target_by_triple(CURRENT_TARGET)?;
         let target_features = TargetFeatures::features(CURRENT_TARGET_FEATURES.iter().copied());
-        Some(Self {
-            target_info,
+        Some(Self::new_impl(
+            PlatformTargetInfo::Borrowed(target_info),
             target_features,
-            flags: BTreeSet::new(),
-            is_custom: false,
-        })
+            false,
+        ))
+    }
+
+    /// Returns the current platform, with target features detected at runtime.
+    ///
+    /// `current` returns the target features enabled at build time, so for example a binary built
+    /// for baseline x86_64 only reports `sse` and `sse2` even if it runs on a CPU that supports
+    /// AVX-512. This method queries the running CPU instead, and combines the result with the
+    /// features enabled at build time.
+    ///
+    /// Runtime detection is currently only supported on x86 and x86_64. On other architectures,
+    /// this returns the same target features as `current`. A few x86 features, such as `movbe`,
+    /// can't be detected with the minimum supported Rust version, and are only present if enabled
+    /// at build time.
+    ///
+    /// This will return `None` if the current platform was unknown to this version of
+    /// `target-spec`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetSpec};
+    ///
+    /// let platform = Platform::current_with_runtime_features().expect("current platform is known");
+    ///
+    /// // Features enabled at build time are always present at runtime.
+    /// let build_time = Platform::current().expect("current platform is known");
+    /// let spec: TargetSpec = "cfg(target_feature = \"sse2\")".parse().unwrap();
+    /// if spec.eval(&build_time) == Some(true) {
+    ///     assert_eq!(spec.eval(&platform), Some(true));
+    /// }
+    /// ```
+    pub fn current_with_runtime_features() -> Option<Self> {
+        let target_info = get_builtin_target_by_triple(CURRENT_TARGET)?;
+        let target_features = TargetFeatures::features(
+            CURRENT_TARGET_FEATURES
+                .iter()
+                .copied()
+                .chain(detect_target_features()),
+        );
+        Some(Self::new_impl(
+            PlatformTargetInfo::Borrowed(target_info),
+            target_features,
+            false,
+        ))
+    }
+}
+
+/// Target information that is either borrowed or owned by a `Platform` or `TargetSpec`.
+#[derive(Clone, Debug)]
+pub(crate) enum PlatformTargetInfo<'a> {
+    Borrowed(&'a TargetInfo<'a>),
+    Owned(Arc<OwnedTargetInfo>),
+}
+
+impl<'a> PlatformTargetInfo<'a> {
+    /// Returns a copy of `target_info` that doesn't borrow from it.
+    ///
+    /// Builtin targets are borrowed from the static list of builtins.
+    pub(crate) fn new_owned(target_info: &TargetInfo<'_>) -> PlatformTargetInfo<'static> {
+        match get_builtin_target_by_triple(target_info.triple) {
+            Some(builtin) if builtin == target_info => PlatformTargetInfo::Borrowed(builtin),
+            _ => PlatformTargetInfo::Owned(Arc::new(OwnedTargetInfo::new(target_info))),
+        }
+    }
+
+    pub(crate) fn triple(&self) -> &str {
+        match self {
+            PlatformTargetInfo::Borrowed(target_info) => target_info.triple,
+            PlatformTargetInfo::Owned(target_info) => &target_info.triple,
+        }
+    }
+
+    pub(crate) fn target_info(&self) -> TargetInfo<'_> {
+        match self {
+            PlatformTargetInfo::Borrowed(target_info) => (*target_info).clone(),
+            PlatformTargetInfo::Owned(target_info) => target_info.as_target_info(),
+        }
+    }
+
+    /// Returns true if this isn't one of the builtin targets.
+    pub(crate) fn is_custom(&self) -> bool {
+        match get_builtin_target_by_triple(self.triple()) {
+            Some(builtin) => *builtin != self.target_info(),
+            None => true,
+        }
+    }
+}
+
+/// An owned version of `TargetInfo`.
+#[derive(Clone, Debug)]
+pub(crate) struct OwnedTargetInfo {
+    triple: String,
+    os: Option<String>,
+    arch: String,
+    env: Option<String>,
+    vendor: Option<String>,
+    family: Option<Family>,
+    pointer_width: u8,
+    endian: Endian,
+}
+
+impl OwnedTargetInfo {
+    fn new(target_info: &TargetInfo<'_>) -> Self {
+        Self {
+            triple: target_info.triple.to_string(),
+            os: target_info.os.map(|os| os.0.to_string()),
+            arch: target_info.arch.0.to_string(),
+            env: target_info.env.map(|env| env.0.to_string()),
+            vendor: target_info.vendor.map(|vendor| vendor.0.to_string()),
+            family: target_info.family,
+            pointer_width: target_info.pointer_width,
+            endian: target_info.endian,
+        }
+    }
+
+    fn as_target_info(&self) -> TargetInfo<'_> {
+        TargetInfo {
+            triple: &self.triple,
+            os: self.os.as_deref().map(Os),
+            arch: Arch(&self.arch),
+            env: self.env.as_deref().map(Env),
+            vendor: self.vendor.as_deref().map(Vendor),
+            family: self.family,
+            pointer_width: self.pointer_width,
+            endian: self.endian,
+        }
     }
 }
 
@@ -141,6 +448,9 @@ impl TargetFeatures {
 
     /// Returns `Some(true)` if this feature is a match, `Some(false)` if it isn't, and `None` if
     /// the set of target features is unknown.
+    ///
+    /// This performs an exact lookup. To also match features implied by the ones in this set, use
+    /// `matches_implied`.
     pub fn matches(&self, feature: &str) -> Option<bool> {
         match self {
             TargetFeatures::Unknown => None,
@@ -148,4 +458,50 @@ impl TargetFeatures {
             TargetFeatures::All => Some(true),
         }
     }
+
+    /// Returns `Some(true)` if this feature, or a feature that implies it on the given
+    /// architecture, is a match, `Some(false)` if not, and `None` if the set of target features is
+    /// unknown.
+    ///
+    /// `target-spec` ships with tables of implied features for x86, x86_64, ARM, AArch64, RISC-V
+    /// and WebAssembly, based on the ones in rustc.
+    ///
+    /// This computes the implied features on every call. `Platform` computes them once when it is
+    /// created, so prefer `Platform::target_feature_matches` for repeated lookups.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use target_spec::{custom_platforms::Arch, TargetFeatures};
+    ///
+    /// let features = TargetFeatures::features(vec!["avx2"]);
+    /// assert_eq!(features.matches("sse4.1"), Some(false));
+    /// assert_eq!(features.matches_implied("sse4.1", Arch::x86_64), Some(true));
+    /// assert_eq!(features.matches_implied("sse4.1", Arch::aarch64), Some(false));
+    /// ```
+    pub fn matches_implied(&self, feature: &str, arch: Arch<'_>) -> Option<bool> {
+        match self {
+            TargetFeatures::Features(features) => Some(
+                features.contains(feature)
+                    || implied_closure(arch.0, features.iter().map(|f| f.deref()))
+                        .contains(feature),
+            ),
+            TargetFeatures::Unknown | TargetFeatures::All => self.matches(feature),
+        }
+    }
+
+    /// Returns a new `TargetFeatures` with the features in this set, along with every feature
+    /// they imply on the given architecture.
+    ///
+    /// `Unknown` and `All` are returned unchanged.
+    pub fn with_implied(&self, arch: Arch<'_>) -> Self {
+        match self {
+            TargetFeatures::Features(features) => Self::features(
+                implied_closure(arch.0, features.iter().map(|f| f.deref()))
+                    .into_iter()
+                    .map(|f| f.to_string()),
+            ),
+            TargetFeatures::Unknown | TargetFeatures::All => self.clone(),
+        }
+    }
 }
@@ -0,0 +1,209 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{Error, Platform, TargetFeatures, TargetSpec};
+use cfg_expr::targets::ALL_BUILTINS;
+
+/// A set of platforms to evaluate target specs against.
+///
+/// This can be every builtin platform, a user-supplied list of triples such as a tier list, or
+/// arbitrary platforms including custom ones.
+///
+/// # Examples
+///
+/// ```
+/// use target_spec::{PlatformSet, TargetFeatures, TargetSpec};
+///
+/// let tier_1 = PlatformSet::from_triples(
+///     vec!["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc", "x86_64-apple-darwin"],
+///     TargetFeatures::Unknown,
+/// ).unwrap();
+///
+/// let spec: TargetSpec = "cfg(unix)".parse().unwrap();
+/// let matches = spec.eval_platforms(&tier_1);
+/// assert_eq!(
+///     matches.matches().iter().map(|platform| platform.triple()).collect::<Vec<_>>(),
+///     vec!["x86_64-unknown-linux-gnu", "x86_64-apple-darwin"],
+/// );
+/// assert_eq!(matches.non_matches().len(), 1);
+/// assert!(matches.unknowns().is_empty());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlatformSet<'a> {
+    platforms: Vec<Platform<'a>>,
+}
+
+impl PlatformSet<'static> {
+    /// Returns a set containing every builtin platform known to `target-spec`, with the given
+    /// target features.
+    pub fn builtins(target_features: TargetFeatures) -> Self {
+        Self {
+            platforms: ALL_BUILTINS
+                .iter()
+                .map(|target_info| {
+                    Platform::new(target_info.triple, target_features.clone())
+                        .expect("builtin triple is known")
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns a set of builtin platforms with the given triples and target features.
+    ///
+    /// Returns an error if any of the triples is unknown.
+    pub fn from_triples(
+        triples: impl IntoIterator<Item = impl AsRef<str>>,
+        target_features: TargetFeatures,
+    ) -> Result<Self, Error> {
+        let platforms = triples
+            .into_iter()
+            .map(|triple| Platform::new(triple, target_features.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { platforms })
+    }
+}
+
+impl<'a> PlatformSet<'a> {
+    /// Creates a new `PlatformSet` from the given platforms.
+    pub fn new(platforms: impl IntoIterator<Item = Platform<'a>>) -> Self {
+        Self {
+            platforms: platforms.into_iter().collect(),
+        }
+    }
+
+    /// Returns the platforms in this set, in the order they were added.
+    pub fn platforms(&self) -> &[Platform<'a>] {
+        &self.platforms
+    }
+
+    /// Returns the number of platforms in this set.
+    pub fn len(&self) -> usize {
+        self.platforms.len()
+    }
+
+    /// Returns true if this set has no platforms.
+    pub fn is_empty(&self) -> bool {
+        self.platforms.is_empty()
+    }
+
+    /// Evaluates a target spec against every platform in this set.
+    pub fn eval(&self, spec: &TargetSpec<'_>) -> PlatformMatches<'a> {
+        self.eval_with(|platform| spec.eval(platform))
+    }
+
+    /// Evaluates every platform in this set with a custom function.
+    ///
+    /// `eval_fn` returns `Some(true)` for a match, `Some(false)` for a non-match, and `None` if the
+    /// result is unknown. This is useful for evaluating several target specs at once.
+    pub fn eval_with(
+        &self,
+        mut eval_fn: impl FnMut(&Platform<'a>) -> Option<bool>,
+    ) -> PlatformMatches<'a> {
+        let mut res = PlatformMatches::default();
+        for platform in &self.platforms {
+            let list = match eval_fn(platform) {
+                Some(true) => &mut res.matches,
+                Some(false) => &mut res.non_matches,
+                None => &mut res.unknowns,
+            };
+            list.push(platform.clone());
+        }
+        res
+    }
+}
+
+/// The result of evaluating a target spec against a `PlatformSet`.
+///
+/// Platforms in each list are in the same order as in the `PlatformSet`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PlatformMatches<'a> {
+    matches: Vec<Platform<'a>>,
+    non_matches: Vec<Platform<'a>>,
+    unknowns: Vec<Platform<'a>>,
+}
+
+impl<'a> PlatformMatches<'a> {
+    /// Returns the platforms that matched.
+    pub fn matches(&self) -> &[Platform<'a>] {
+        &self.matches
+    }
+
+    /// Returns the platforms that didn't match.
+    pub fn non_matches(&self) -> &[Platform<'a>] {
+        &self.non_matches
+    }
+
+    /// Returns the platforms for which the result was unknown, typically because target features
+    /// or key-value pairs were unknown.
+    pub fn unknowns(&self) -> &[Platform<'a>] {
+        &self.unknowns
+    }
+
+    /// Returns true if every platform matched.
+    pub fn is_all(&self) -> bool {
+        self.non_matches.is_empty() && self.unknowns.is_empty()
+    }
+
+    /// Returns true if no platform matched or was unknown.
+    pub fn is_none(&self) -> bool {
+        self.matches.is_empty() && self.unknowns.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtins() {
+        let spec: TargetSpec = "cfg(target_os = \"linux\")".parse().unwrap();
+        let matches = spec.matching_platforms();
+        assert_eq!(
+            matches.matches().len() + matches.non_matches().len(),
+            ALL_BUILTINS.len(),
+            "every builtin platform is evaluated",
+        );
+        assert!(matches.unknowns().is_empty(), "no unknowns");
+        assert!(matches
+            .matches()
+            .iter()
+            .any(|platform| platform.triple() == "x86_64-unknown-linux-gnu"));
+        assert!(matches
+            .non_matches()
+            .iter()
+            .any(|platform| platform.triple() == "x86_64-pc-windows-msvc"));
+
+        let spec: TargetSpec = "cfg(target_feature = \"sse2\")".parse().unwrap();
+        let matches = spec.matching_platforms();
+        assert_eq!(
+            matches.unknowns().len(),
+            ALL_BUILTINS.len(),
+            "target features are unknown"
+        );
+        assert!(!matches.is_all() && !matches.is_none());
+    }
+
+    #[test]
+    fn from_triples() {
+        let set = PlatformSet::from_triples(
+            vec!["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-gnu"],
+            TargetFeatures::features(vec!["neon"]),
+        )
+        .expect("known triples");
+
+        let spec: TargetSpec = "cfg(target_feature = \"neon\")".parse().unwrap();
+        let matches = set.eval(&spec);
+        assert!(matches.is_all(), "all platforms match");
+
+        let spec: TargetSpec = "cfg(windows)".parse().unwrap();
+        assert!(set.eval(&spec).is_none(), "no platforms match");
+
+        assert_eq!(
+            PlatformSet::from_triples(
+                vec!["x86_64-unknown-linux-gnu", "bogus"],
+                TargetFeatures::Unknown
+            ),
+            Err(Error::UnknownPlatformTriple("bogus".to_string())),
+        );
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for platforms described by `rustc --print cfg` and `CARGO_CFG_*` environment variables.
+
+use crate::{
+    custom_platforms::{Arch, Endian, Env, Family, Os, TargetInfo, Vendor},
+    Error, Platform, TargetFeatures,
+};
+use std::env;
+
+impl Platform<'static> {
+    /// Creates a new, custom platform from the output of `rustc --print cfg`.
+    ///
+    /// `triple` is the target the output was generated for, for example through
+    /// `rustc --print cfg --target <triple>`. Unlike builtin platforms, the result reflects any
+    /// additional cfgs and target features set through `RUSTFLAGS`:
+    ///
+    /// * The target information is read from the `target_arch`, `target_os`, `target_env`,
+    ///   `target_vendor`, `target_family`, `target_endian` and `target_pointer_width` keys.
+    /// * Target features are read from the `target_feature` key.
+    /// * Other names, such as `cargo_web`, are added as flags. Names with special meaning to
+    ///   Cargo, such as `unix` or `debug_assertions`, are skipped.
+    /// * Other key-value pairs, such as `panic = "unwind"`, are added as key-value pairs.
+    ///
+    /// Returns an error if a line couldn't be parsed, or if any of `target_arch`,
+    /// `target_endian` or `target_pointer_width` is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetFeatures};
+    ///
+    /// let cfg = "target_arch=\"x86_64\"\n\
+    ///            target_endian=\"little\"\n\
+    ///            target_env=\"gnu\"\n\
+    ///            target_family=\"unix\"\n\
+    ///            target_feature=\"sse2\"\n\
+    ///            target_os=\"linux\"\n\
+    ///            target_pointer_width=\"64\"\n\
+    ///            target_vendor=\"unknown\"\n\
+    ///            unix\n\
+    ///            my_cfg\n";
+    /// let platform = Platform::from_rustc_cfg("x86_64-unknown-linux-gnu", cfg).unwrap();
+    /// assert_eq!(platform.target_features(), &TargetFeatures::features(vec!["sse2"]));
+    /// assert!(platform.has_flag("my_cfg"));
+    /// ```
+    pub fn from_rustc_cfg(triple: impl AsRef<str>, cfg: &str) -> Result<Self, Error> {
+        let mut builder = CfgBuilder::default();
+        for line in cfg.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match line.find('=') {
+                Some(idx) => {
+                    let value = line[idx + 1..].trim();
+                    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                        return Err(Error::InvalidPlatformCfg(line.to_string()));
+                    }
+                    builder.add_key_value(line[..idx].trim(), &value[1..value.len() - 1]);
+                }
+                None => builder.add_name(line),
+            }
+        }
+        builder.build(triple.as_ref())
+    }
+
+    /// Creates a new, custom platform from the `TARGET` and `CARGO_CFG_*` environment variables
+    /// passed in to build scripts.
+    ///
+    /// This is meant to be called from a build script. For more about how cfgs are handled, see
+    /// the documentation for `from_rustc_cfg`.
+    pub fn from_cargo_cfg_env() -> Result<Self, Error> {
+        let triple = env::var("TARGET").map_err(|_| Error::MissingPlatformCfg("TARGET"))?;
+        Self::from_cargo_cfg_vars(triple, env::vars())
+    }
+
+    /// Creates a new, custom platform from a target triple and a list of environment variables.
+    ///
+    /// Variables that don't start with `CARGO_CFG_` are ignored. Multiple values for a key are
+    /// separated by commas, as with Cargo. For more about how cfgs are handled, see the
+    /// documentation for `from_rustc_cfg`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::Platform;
+    ///
+    /// let vars = vec![
+    ///     ("CARGO_CFG_TARGET_ARCH", "arm"),
+    ///     ("CARGO_CFG_TARGET_ENDIAN", "little"),
+    ///     ("CARGO_CFG_TARGET_POINTER_WIDTH", "32"),
+    ///     ("CARGO_CFG_TARGET_OS", "none"),
+    ///     ("CARGO_CFG_TARGET_HAS_ATOMIC", "8,16,32"),
+    ///     ("CARGO_PKG_NAME", "my-crate"),
+    /// ];
+    /// let platform = Platform::from_cargo_cfg_vars("thumbv7em-none-eabi", vars).unwrap();
+    /// assert_eq!(platform.key_values().filter(|(key, _)| *key == "target_has_atomic").count(), 3);
+    /// ```
+    pub fn from_cargo_cfg_vars(
+        triple: impl AsRef<str>,
+        vars: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>)>,
+    ) -> Result<Self, Error> {
+        let mut builder = CfgBuilder::default();
+        for (var, value) in vars {
+            let name = match var.as_ref().strip_prefix("CARGO_CFG_") {
+                Some(name) => name.to_ascii_lowercase(),
+                None => continue,
+            };
+            let value = value.as_ref();
+            if value.is_empty() && !is_target_key(&name) {
+                builder.add_name(&name);
+            } else {
+                for value in value.split(',') {
+                    builder.add_key_value(&name, value);
+                }
+            }
+        }
+        builder.build(triple.as_ref())
+    }
+}
+
+/// Collects cfgs from either source.
+#[derive(Debug, Default)]
+struct CfgBuilder {
+    arch: Option<String>,
+    os: Option<String>,
+    env: Option<String>,
+    vendor: Option<String>,
+    families: Vec<String>,
+    endian: Option<String>,
+    pointer_width: Option<String>,
+    features: Vec<String>,
+    flags: Vec<String>,
+    key_values: Vec<(String, String)>,
+}
+
+impl CfgBuilder {
+    fn add_name(&mut self, name: &str) {
+        match name {
+            // These names are either covered by the target information, or have special meaning to
+            // Cargo.
+            "unix" | "windows" | "debug_assertions" | "test" | "proc_macro" => {}
+            _ => self.flags.push(name.to_string()),
+        }
+    }
+
+    fn add_key_value(&mut self, key: &str, value: &str) {
+        let value = value.to_string();
+        match key {
+            "target_arch" => self.arch = Some(value),
+            "target_os" => self.os = Some(value),
+            "target_env" => self.env = Some(value),
+            "target_vendor" => self.vendor = Some(value),
+            // Cargo passes in an empty string if there are no families or features.
+            "target_family" | "target_feature" if value.is_empty() => {}
+            "target_family" => self.families.push(value),
+            "target_endian" => self.endian = Some(value),
+            "target_pointer_width" => self.pointer_width = Some(value),
+            "target_feature" => self.features.push(value),
+            _ => self.key_values.push((key.to_string(), value)),
+        }
+    }
+
+    fn build(self, triple: &str) -> Result<Platform<'static>, Error> {
+        let arch = self.arch.ok_or(Error::MissingPlatformCfg("target_arch"))?;
+        let endian = self
+            .endian
+            .ok_or(Error::MissingPlatformCfg("target_endian"))?;
+        let endian = endian
+            .parse::<Endian>()
+            .map_err(|_| Error::InvalidPlatformCfg(format!("target_endian=\"{}\"", endian)))?;
+        let pointer_width = self
+            .pointer_width
+            .ok_or(Error::MissingPlatformCfg("target_pointer_width"))?;
+        let pointer_width = pointer_width.parse::<u8>().map_err(|_| {
+            Error::InvalidPlatformCfg(format!("target_pointer_width=\"{}\"", pointer_width))
+        })?;
+
+        let target_info = TargetInfo {
+            triple,
+            os: self.os.as_deref().map(Os),
+            arch: Arch(&arch),
+            // rustc prints an empty environment for targets without one.
+            env: self.env.as_deref().filter(|env| !env.is_empty()).map(Env),
+            vendor: self.vendor.as_deref().map(Vendor),
+            family: self
+                .families
+                .iter()
+                .find_map(|family| family.parse::<Family>().ok()),
+            pointer_width,
+            endian,
+        };
+
+        let mut platform =
+            Platform::custom_owned(&target_info, TargetFeatures::features(self.features));
+        platform.add_flags(self.flags);
+        platform.add_key_values(self.key_values);
+        Ok(platform)
+    }
+}
+
+fn is_target_key(name: &str) -> bool {
+    matches!(
+        name,
+        "target_arch"
+            | "target_os"
+            | "target_env"
+            | "target_vendor"
+            | "target_family"
+            | "target_endian"
+            | "target_pointer_width"
+            | "target_feature"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetSpec;
+
+    // Output of `RUSTFLAGS="--cfg my_cfg --cfg 'my_key=\"value\"' -C target-feature=+avx2" rustc
+    // --print cfg`, trimmed down.
+    static LINUX_CFG: &str = r#"debug_assertions
+my_cfg
+my_key="value"
+panic="unwind"
+target_arch="x86_64"
+target_endian="little"
+target_env="gnu"
+target_family="unix"
+target_feature="avx2"
+target_feature="fxsr"
+target_feature="sse"
+target_feature="sse2"
+target_has_atomic="64"
+target_has_atomic="ptr"
+target_os="linux"
+target_pointer_width="64"
+target_vendor="unknown"
+unix
+"#;
+
+    #[test]
+    fn rustc_cfg() {
+        let platform =
+            Platform::from_rustc_cfg("x86_64-unknown-linux-gnu", LINUX_CFG).expect("valid cfg");
+        check_linux(&platform);
+
+        let bad_line = "target_arch=x86_64";
+        assert_eq!(
+            Platform::from_rustc_cfg("foo", bad_line),
+            Err(Error::InvalidPlatformCfg(bad_line.to_string())),
+        );
+        assert_eq!(
+            Platform::from_rustc_cfg("foo", "target_arch=\"x86_64\"\ntarget_endian=\"little\""),
+            Err(Error::MissingPlatformCfg("target_pointer_width")),
+        );
+    }
+
+    #[test]
+    fn cargo_cfg_vars() {
+        let vars = vec![
+            ("CARGO_CFG_DEBUG_ASSERTIONS", ""),
+            ("CARGO_CFG_MY_CFG", ""),
+            ("CARGO_CFG_MY_KEY", "value"),
+            ("CARGO_CFG_TARGET_FAMILY", ""),
+            ("CARGO_CFG_PANIC", "unwind"),
+            ("CARGO_CFG_TARGET_ARCH", "x86_64"),
+            ("CARGO_CFG_TARGET_ENDIAN", "little"),
+            ("CARGO_CFG_TARGET_ENV", "gnu"),
+            ("CARGO_CFG_TARGET_FAMILY", "unix"),
+            ("CARGO_CFG_TARGET_FEATURE", "avx2,fxsr,sse,sse2"),
+            ("CARGO_CFG_TARGET_HAS_ATOMIC", "64,ptr"),
+            ("CARGO_CFG_TARGET_OS", "linux"),
+            ("CARGO_CFG_TARGET_POINTER_WIDTH", "64"),
+            ("CARGO_CFG_TARGET_VENDOR", "unknown"),
+            ("CARGO_CFG_UNIX", ""),
+            ("CARGO_MANIFEST_DIR", "/foo/bar"),
+        ];
+        let platform = Platform::from_cargo_cfg_vars("x86_64-unknown-linux-gnu", vars)
+            .expect("valid variables");
+        check_linux(&platform);
+
+        let from_rustc =
+            Platform::from_rustc_cfg("x86_64-unknown-linux-gnu", LINUX_CFG).expect("valid cfg");
+        assert_eq!(
+            platform, from_rustc,
+            "both sources produce the same platform"
+        );
+    }
+
+    fn check_linux(platform: &Platform<'_>) {
+        assert!(platform.is_custom(), "platform is custom");
+        assert_eq!(platform.triple(), "x86_64-unknown-linux-gnu");
+
+        let target_info = platform.target_info();
+        assert_eq!(target_info.arch, Arch::x86_64);
+        assert_eq!(target_info.os, Some(Os::linux));
+        assert_eq!(target_info.env, Some(Env::gnu));
+        assert_eq!(target_info.vendor, Some(Vendor::unknown));
+        assert_eq!(target_info.family, Some(Family::unix));
+        assert_eq!(target_info.pointer_width, 64);
+        assert_eq!(target_info.endian, Endian::little);
+
+        assert_eq!(
+            platform.target_features(),
+            &TargetFeatures::features(vec!["avx2", "fxsr", "sse", "sse2"]),
+        );
+        assert_eq!(platform.flags().collect::<Vec<_>>(), vec!["my_cfg"]);
+        assert_eq!(
+            platform.key_values().collect::<Vec<_>>(),
+            vec![
+                ("my_key", "value"),
+                ("panic", "unwind"),
+                ("target_has_atomic", "64"),
+                ("target_has_atomic", "ptr"),
+            ],
+        );
+
+        for (spec, expected) in &[
+            ("cfg(all(unix, target_arch = \"x86_64\"))", true),
+            ("cfg(target_feature = \"avx2\")", true),
+            ("cfg(target_feature = \"avx512f\")", false),
+            ("cfg(my_cfg)", true),
+            ("x86_64-unknown-linux-gnu", true),
+        ] {
+            let spec: TargetSpec = spec.parse().expect("valid spec");
+            assert_eq!(spec.eval(platform), Some(*expected), "for spec {:?}", spec);
+        }
+    }
+}
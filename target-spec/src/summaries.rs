@@ -8,9 +8,15 @@
 //!
 //! Summaries require the `summaries` feature to be enabled.
 
-use crate::{Error, Platform, TargetFeatures};
+use crate::{
+    custom_platforms::{Arch, Endian, Env, Family, Os, TargetInfo, Vendor},
+    Error, Platform, TargetFeatures,
+};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::BTreeSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 /// An owned, serializable version of `Platform`.
 ///
@@ -19,6 +25,7 @@ use std::{borrow::Cow, collections::BTreeSet};
 /// Requires the `summaries` feature to be enabled.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
 pub struct PlatformSummary {
     /// The platform triple.
     pub triple: String,
@@ -30,34 +37,159 @@ pub struct PlatformSummary {
     /// The flags enabled.
     #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
     pub flags: BTreeSet<String>,
+
+    /// Whether target features are matched exactly, without taking implied features into account.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub exact_target_features: bool,
+
+    /// The key-value pairs enabled, as a map of keys to values.
+    ///
+    /// Keys that are known but don't have any values map to an empty set.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub key_values: BTreeMap<String, BTreeSet<String>>,
+
+    /// The target information for custom platforms, or `None` for builtin platforms.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub custom: Option<CustomTargetSummary>,
 }
 
 impl PlatformSummary {
     /// Creates a new `PlatformSummary` instance from a platform.
     ///
-    /// Returns an error if this is a custom platform. Serializing custom platforms is currently
-    /// unsupported.
+    /// Custom platforms store their target information in `custom`.
     pub fn new(platform: &Platform<'_>) -> Result<Self, Error> {
-        if platform.is_custom() {
-            return Err(Error::CustomPlatformSummary);
+        let custom = if platform.is_custom() {
+            Some(CustomTargetSummary::new(&platform.target_info()))
+        } else {
+            None
         };
+        // Known keys without values are stored as empty sets.
+        let mut key_values: BTreeMap<String, BTreeSet<String>> = platform
+            .known_keys()
+            .map(|key| (key.to_string(), BTreeSet::new()))
+            .collect();
+        for (key, value) in platform.key_values() {
+            key_values
+                .entry(key.to_string())
+                .or_default()
+                .insert(value.to_string());
+        }
         Ok(Self {
             triple: platform.triple().to_string(),
             target_features: TargetFeaturesSummary::new(platform.target_features()),
             flags: platform.flags().map(|flag| flag.to_string()).collect(),
+            exact_target_features: platform.exact_target_features(),
+            key_values,
+            custom,
         })
     }
 
     /// Converts `self` to a `Platform`.
     ///
-    /// Returns an `Error` if the platform was unknown.
+    /// Returns an `Error` if the platform was unknown, or if the custom target information was
+    /// invalid.
     pub fn to_platform(&self) -> Result<Platform<'static>, Error> {
-        let mut platform = Platform::new(&self.triple, self.target_features.to_target_features())?;
+        let target_features = self.target_features.to_target_features();
+        let mut platform = match &self.custom {
+            Some(custom) => {
+                let target_info = custom.to_target_info(&self.triple)?;
+                Platform::custom_owned(&target_info, target_features)
+            }
+            None => Platform::new(&self.triple, target_features)?,
+        };
         platform.add_flags(self.flags.iter().cloned());
+        platform.set_exact_target_features(self.exact_target_features);
+        platform.add_known_keys(self.key_values.keys().cloned());
+        platform.add_key_values(self.key_values.iter().flat_map(|(key, values)| {
+            values.iter().map(move |value| (key.clone(), value.clone()))
+        }));
         Ok(platform)
     }
 }
 
+/// An owned, serializable version of the `TargetInfo` for a custom platform.
+///
+/// This structure can be serialized and deserialized using `serde`.
+///
+/// Requires the `summaries` feature to be enabled.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct CustomTargetSummary {
+    /// The target architecture.
+    pub arch: String,
+
+    /// The target OS, if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub os: Option<String>,
+
+    /// The target environment, if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub env: Option<String>,
+
+    /// The target vendor, if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub vendor: Option<String>,
+
+    /// The target family (`unix` or `windows`), if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub family: Option<String>,
+
+    /// The pointer width, in bits.
+    pub pointer_width: u8,
+
+    /// The endianness (`little` or `big`).
+    pub endian: String,
+}
+
+impl CustomTargetSummary {
+    /// Creates a new `CustomTargetSummary` from a `TargetInfo`.
+    pub fn new(target_info: &TargetInfo<'_>) -> Self {
+        Self {
+            arch: target_info.arch.0.to_string(),
+            os: target_info.os.map(|os| os.0.to_string()),
+            env: target_info.env.map(|env| env.0.to_string()),
+            vendor: target_info.vendor.map(|vendor| vendor.0.to_string()),
+            family: target_info.family.map(|family| match family {
+                Family::unix => "unix".to_string(),
+                Family::windows => "windows".to_string(),
+            }),
+            pointer_width: target_info.pointer_width,
+            endian: match target_info.endian {
+                Endian::little => "little".to_string(),
+                Endian::big => "big".to_string(),
+            },
+        }
+    }
+
+    /// Converts `self` to a `TargetInfo` with the given triple.
+    ///
+    /// Returns an error if the family or endianness was invalid.
+    pub fn to_target_info<'a>(&'a self, triple: &'a str) -> Result<TargetInfo<'a>, Error> {
+        let family =
+            match &self.family {
+                Some(family) => Some(family.parse::<Family>().map_err(|_| {
+                    Error::InvalidCustomTargetSummary(format!("family {}", family))
+                })?),
+                None => None,
+            };
+        let endian = self
+            .endian
+            .parse::<Endian>()
+            .map_err(|_| Error::InvalidCustomTargetSummary(format!("endian {}", self.endian)))?;
+        Ok(TargetInfo {
+            triple,
+            os: self.os.as_deref().map(Os),
+            arch: Arch(&self.arch),
+            env: self.env.as_deref().map(Env),
+            vendor: self.vendor.as_deref().map(Vendor),
+            family,
+            pointer_width: self.pointer_width,
+            endian,
+        })
+    }
+}
+
 /// An owned, serializable version of `TargetFeatures`.
 ///
 /// This type can be serialized and deserialized using `serde`.
@@ -170,4 +302,49 @@ mod tests {
             assert_eq!(platform.flags().collect::<HashSet<_>>(), platform2.flags().collect::<HashSet<_>>(), "flags match");
         }
     }
+
+    #[test]
+    fn custom_summary_roundtrip() {
+        let target_info = TargetInfo {
+            triple: "armv7-custom-linux-gnueabihf",
+            os: Some(Os("linux")),
+            arch: Arch("arm"),
+            env: Some(Env("gnueabihf")),
+            vendor: Some(Vendor("custom")),
+            family: Some(Family::unix),
+            pointer_width: 32,
+            endian: Endian::little,
+        };
+        let mut platform = Platform::custom(&target_info, TargetFeatures::features(vec!["neon"]));
+        platform.add_flags(vec!["cargo_web"]);
+        platform.set_exact_target_features(true);
+        platform.add_key_values(vec![
+            ("target_has_atomic", "8"),
+            ("target_has_atomic", "ptr"),
+        ]);
+
+        let summary = PlatformSummary::new(&platform).expect("custom platforms are supported");
+        let serialized = toml::ser::to_string(&summary).expect("serialization succeeded");
+        let deserialized: PlatformSummary =
+            toml::from_str(&serialized).expect("deserialization succeeded");
+        assert_eq!(
+            summary, deserialized,
+            "summary and deserialized should match"
+        );
+
+        let platform2 = deserialized
+            .to_platform()
+            .expect("conversion to Platform succeeded");
+        assert!(platform2.is_custom(), "custom platform round-tripped");
+        assert_eq!(platform, platform2, "platforms match");
+
+        let mut invalid = summary;
+        invalid.custom.as_mut().expect("custom is set").endian = "middle".to_string();
+        assert_eq!(
+            invalid.to_platform(),
+            Err(Error::InvalidCustomTargetSummary(
+                "endian middle".to_string()
+            )),
+        );
+    }
 }
@@ -0,0 +1,409 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A command-line frontend for the [determinator](https://docs.rs/determinator).
+//!
+//! `cargo-determinator` figures out which packages in a workspace changed between two commits,
+//! taking care of building the old and new package graphs, reading rules and changed paths, and
+//! printing out the results in a format suitable for CI.
+//!
+//! # Installing
+//!
+//! `cargo-determinator` is currently a work in progress, and not yet on `crates.io`. To install it,
+//! ensure you have `cargo` installed (preferably through [rustup](https://rustup.rs/)), then run:
+//!
+//! ```bash
+//! cargo install --git https://github.com/facebookincubator/cargo-guppy --branch main cargo-determinator
+//! ```
+//!
+//! This will make the `cargo determinator` command available.
+//!
+//! # Inputs
+//!
+//! The old package graph is read from one of:
+//! * `--old-metadata <FILE>`: a JSON file produced by `cargo metadata --format-version 1`.
+//! * `--old-rev <REV>`: a Git revision. The revision is checked out into a temporary worktree and
+//!   `cargo metadata` is run there.
+//! * If neither is specified and `--git-diff` is, the merge base with the upstream revision.
+//!
+//! The new package graph is similarly read from `--new-metadata` or `--new-rev`, and defaults to
+//! the workspace in the current directory (or the one specified by `--manifest-path`).
+//!
+//! Changed paths are read from one of:
+//! * `--paths0 <FILE>`: a file with null-separated paths relative to the workspace root, e.g. the
+//!   output of `git diff -z --name-only`. Use `-` to read from standard input.
+//! * `--git-diff <UPSTREAM>`: the files changed in the current workspace since its merge base
+//!   with the given revision, including uncommitted changes.
+//!
+//! Custom rules can be passed in with `--rules <FILE>`, in the format described in
+//! [the determinator's `rules` module](https://docs.rs/determinator/*/determinator/rules/).
+//! Alternatively, `--workspace-rules` loads rules from `[workspace.metadata.determinator]` and
+//! `determinator.toml` files in the new workspace.
+//!
+//! # Output formats
+//!
+//! The affected packages are printed out in the format specified by `--format`:
+//! * `names` (default): one package name per line, sorted by name.
+//! * `json`: a JSON object with the affected packages and the reasons they were affected.
+//! * `cargo-test`: a list of arguments that can be passed to `cargo test`, e.g. `-p foo -p bar`.
+//!   Nothing is printed if no packages were affected.
+//!
+//! An example of JSON output:
+//!
+//! ```json
+//! {
+//!   "all-affected": false,
+//!   "path-changed": ["fixtures"],
+//!   "summary-changed": [],
+//!   "affected": ["fixtures", "guppy"],
+//!   "causes": {
+//!     "fixtures": {
+//!       "cause": "path-changed",
+//!       "path": "fixtures/src/details.rs",
+//!       "matched-by": { "kind": "nearest-package" }
+//!     },
+//!     "guppy": { "cause": "dependency", "chain": ["guppy", "fixtures"] }
+//!   }
+//! }
+//! ```
+//!
+//! # Exit codes
+//!
+//! `cargo determinator` exits with 0 on success and 1 on errors. If `--fail-if-all-affected` is
+//! passed in and every workspace package is affected, it prints out its results and exits with 2.
+//! CI systems can use this to fall back to a full build.
+
+use anyhow::{anyhow, bail, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use determinator::{
+    rules::DeterminatorRules, AffectedCauses, Determinator, DeterminatorSet, FileDependencies,
+    GitDiff, Utf8Paths0,
+};
+use guppy::{
+    graph::{feature::StandardFeatures, DependencyDirection, PackageGraph, PackageSet},
+    MetadataCommand,
+};
+use guppy_cmdlib::CargoMetadataOptions;
+use serde::Serialize;
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    process::Command,
+    str::FromStr,
+};
+use structopt::StructOpt;
+
+/// The exit code returned if `--fail-if-all-affected` is passed in and every workspace package is
+/// affected.
+pub const ALL_AFFECTED_EXIT_CODE: i32 = 2;
+
+/// Options for `cargo determinator`.
+#[derive(Debug, StructOpt)]
+pub struct DeterminatorApp {
+    /// Old metadata, as a JSON file produced by `cargo metadata`
+    #[structopt(long, conflicts_with = "old-rev")]
+    old_metadata: Option<Utf8PathBuf>,
+
+    /// Git revision to compute old metadata at (default: merge base with --git-diff)
+    #[structopt(long)]
+    old_rev: Option<String>,
+
+    /// New metadata, as a JSON file produced by `cargo metadata`
+    #[structopt(long, conflicts_with = "new-rev")]
+    new_metadata: Option<Utf8PathBuf>,
+
+    /// Git revision to compute new metadata at (default: current workspace)
+    #[structopt(long)]
+    new_rev: Option<String>,
+
+    #[structopt(flatten)]
+    metadata_opts: CargoMetadataOptions,
+
+    /// Determinator rules, as a TOML file (default: the default rules)
+    #[structopt(long, conflicts_with = "workspace-rules")]
+    rules: Option<Utf8PathBuf>,
+
+    /// Load rules from workspace metadata and determinator.toml files in the new workspace
+    #[structopt(long)]
+    workspace_rules: bool,
+
+    /// Read null-separated changed paths from this file, or "-" for standard input
+    #[structopt(long, required_unless = "git-diff", conflicts_with = "git-diff")]
+    paths0: Option<String>,
+
+    /// Read changed paths since the merge base with this Git revision
+    #[structopt(long)]
+    git_diff: Option<String>,
+
+    /// Include untracked files in changed paths read through --git-diff
+    #[structopt(long, requires = "git-diff")]
+    include_untracked: bool,
+
+    /// Scan workspace source code for files outside packages that they depend on
+    #[structopt(long)]
+    scan_file_deps: bool,
+
+    /// Packages that take part in feature unification, e.g. workspace-hack packages
+    #[structopt(long, number_of_values = 1)]
+    features_only: Vec<String>,
+
+    /// Output format
+    #[structopt(long, default_value = "names", possible_values = OutputFormat::VARIANTS)]
+    format: OutputFormat,
+
+    /// Exit with status 2 if every workspace package is affected
+    #[structopt(long)]
+    fail_if_all_affected: bool,
+}
+
+impl DeterminatorApp {
+    /// Executes this command, printing out results to standard output.
+    ///
+    /// Returns the exit code for the process.
+    pub fn exec(&self) -> Result<i32> {
+        // The current workspace is used as the new graph by default. Its root is also where Git
+        // changes are read from and old revisions are checked out: this may be different from the
+        // current directory or the directory of --manifest-path, e.g. if run from a member.
+        let needs_workspace_root =
+            self.git_diff.is_some() || self.old_rev.is_some() || self.new_rev.is_some();
+        let mut current_graph = if needs_workspace_root || self.new_metadata.is_none() {
+            Some(
+                self.metadata_opts
+                    .make_command()
+                    .build_graph()
+                    .context("computing metadata for the current workspace failed")?,
+            )
+        } else {
+            None
+        };
+        let workspace_root = current_graph
+            .as_ref()
+            .map(|graph| graph.workspace().root().to_path_buf());
+        let workspace_root = || {
+            workspace_root
+                .as_deref()
+                .expect("current workspace is computed if its root is needed")
+        };
+
+        // Read changed paths first: with --git-diff, the merge base is used as the default old
+        // revision.
+        let mut merge_base = None;
+        let changed_paths: Vec<Utf8PathBuf> = match (&self.paths0, &self.git_diff) {
+            (Some(paths0), _) => read_paths0(paths0)?
+                .iter()
+                .map(|path| path.to_path_buf())
+                .collect(),
+            (None, Some(upstream)) => {
+                let changes = GitDiff::new(workspace_root(), upstream)
+                    .set_include_untracked(self.include_untracked)
+                    .compute()
+                    .with_context(|| format!("reading changes since {} failed", upstream))?;
+                merge_base = Some(changes.merge_base().to_owned());
+                changes.paths().map(|path| path.to_path_buf()).collect()
+            }
+            (None, None) => bail!("one of --paths0 or --git-diff must be specified"),
+        };
+
+        let old_graph = match (&self.old_metadata, &self.old_rev, &merge_base) {
+            (Some(path), _, _) => read_metadata(path)?,
+            (None, Some(rev), _) | (None, None, Some(rev)) => {
+                metadata_at_rev(workspace_root(), rev)?
+            }
+            (None, None, None) => bail!("one of --old-metadata or --old-rev must be specified"),
+        };
+        let new_graph = match (&self.new_metadata, &self.new_rev) {
+            (Some(path), _) => read_metadata(path)?,
+            (None, Some(rev)) => metadata_at_rev(workspace_root(), rev)?,
+            (None, None) => current_graph
+                .take()
+                .expect("current workspace is computed if --new-metadata isn't specified"),
+        };
+
+        let mut determinator = Determinator::new(&old_graph, &new_graph);
+        if let Some(rules_path) = &self.rules {
+            let rules = fs::read_to_string(rules_path)
+                .with_context(|| format!("reading rules file {} failed", rules_path))?;
+            let rules = DeterminatorRules::parse(&rules)
+                .with_context(|| format!("parsing rules file {} failed", rules_path))?;
+            determinator.set_rules(&rules)?;
+        } else if self.workspace_rules {
+            let rules = DeterminatorRules::load_workspace(&new_graph)?;
+            determinator.set_rules(&rules)?;
+        }
+        if !self.features_only.is_empty() {
+            determinator.set_features_only(
+                self.features_only.iter().map(|name| name.as_str()),
+                StandardFeatures::Default,
+            )?;
+        }
+        if self.scan_file_deps {
+            let file_deps = FileDependencies::scan(&new_graph)?;
+            determinator.set_file_dependencies(file_deps);
+        }
+        determinator.add_changed_paths(&changed_paths);
+
+        let determinator_set = determinator.compute();
+        let all_affected =
+            determinator_set.affected_set.len() == new_graph.workspace().member_count();
+        self.format.print(&determinator_set, all_affected)?;
+
+        if self.fail_if_all_affected && all_affected {
+            Ok(ALL_AFFECTED_EXIT_CODE)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+/// Computes metadata for the workspace at `workspace_root` at the given Git revision.
+///
+/// The revision is checked out into a temporary worktree, which is removed afterwards.
+fn metadata_at_rev(workspace_root: &Utf8Path, rev: &str) -> Result<PackageGraph> {
+    let git_root = git(workspace_root, &["rev-parse", "--show-toplevel"])?;
+    let rel_dir = workspace_root
+        .strip_prefix(git_root.trim_end())
+        .with_context(|| format!("workspace {} is not in a Git repository", workspace_root))?;
+
+    let temp_dir = tempfile::tempdir().context("creating temporary directory failed")?;
+    let worktree = Utf8Path::from_path(temp_dir.path())
+        .ok_or_else(|| anyhow!("temporary directory is not valid UTF-8"))?
+        .join("worktree");
+    git(
+        workspace_root,
+        &["worktree", "add", "--detach", "-q", worktree.as_str(), rev],
+    )?;
+
+    let graph = MetadataCommand::new()
+        .current_dir(worktree.join(rel_dir))
+        .build_graph()
+        .with_context(|| format!("computing metadata at {} failed", rev));
+    // Clean up the worktree even if computing metadata failed.
+    let remove_res = git(
+        workspace_root,
+        &["worktree", "remove", "--force", worktree.as_str()],
+    );
+    let graph = graph?;
+    remove_res?;
+    Ok(graph)
+}
+
+/// The format to print out results in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One package name per line.
+    Names,
+    /// JSON, including the reasons packages were affected.
+    Json,
+    /// Arguments to `cargo test`.
+    CargoTest,
+}
+
+impl OutputFormat {
+    /// The list of output formats, as accepted on the command line.
+    pub const VARIANTS: &'static [&'static str] = &["names", "json", "cargo-test"];
+
+    fn print(self, determinator_set: &DeterminatorSet<'_>, all_affected: bool) -> Result<()> {
+        let affected = sorted_names(&determinator_set.affected_set);
+        match self {
+            OutputFormat::Names => {
+                for name in affected {
+                    println!("{}", name);
+                }
+            }
+            OutputFormat::Json => {
+                let output = JsonOutput {
+                    all_affected,
+                    path_changed: sorted_names(&determinator_set.path_changed_set),
+                    summary_changed: sorted_names(&determinator_set.summary_changed_set),
+                    affected,
+                    causes: &determinator_set.affected_causes,
+                };
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            OutputFormat::CargoTest => {
+                if !affected.is_empty() {
+                    let args: Vec<_> = affected.iter().map(|name| format!("-p {}", name)).collect();
+                    println!("{}", args.join(" "));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "names" => Ok(OutputFormat::Names),
+            "json" => Ok(OutputFormat::Json),
+            "cargo-test" => Ok(OutputFormat::CargoTest),
+            other => Err(format!("unknown output format: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Names => write!(f, "names"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::CargoTest => write!(f, "cargo-test"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonOutput<'a, 'g> {
+    all_affected: bool,
+    path_changed: Vec<&'g str>,
+    summary_changed: Vec<&'g str>,
+    affected: Vec<&'g str>,
+    causes: &'a AffectedCauses<'g>,
+}
+
+fn sorted_names<'g>(package_set: &PackageSet<'g>) -> Vec<&'g str> {
+    let mut names: Vec<_> = package_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.name())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+fn read_metadata(path: &Utf8Path) -> Result<PackageGraph> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("reading metadata file {} failed", path))?;
+    PackageGraph::from_json(&json).with_context(|| format!("parsing metadata file {} failed", path))
+}
+
+fn read_paths0(source: &str) -> Result<Utf8Paths0> {
+    let mut buf = vec![];
+    if source == "-" {
+        io::stdin()
+            .read_to_end(&mut buf)
+            .context("reading paths from standard input failed")?;
+    } else {
+        buf = fs::read(source).with_context(|| format!("reading paths file {} failed", source))?;
+    }
+    Utf8Paths0::from_bytes(buf)
+        .map_err(|(_, err)| anyhow!("changed paths are not valid UTF-8: {}", err))
+}
+
+fn git(dir: &Utf8Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("running git failed")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    String::from_utf8(output.stdout).context("git output is not valid UTF-8")
+}
@@ -0,0 +1,186 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tests that run `cargo-determinator` against a temporary Git repository.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_determinator::ALL_AFFECTED_EXIT_CODE;
+use serde_json::Value;
+use std::{fs, process::Command};
+use tempfile::TempDir;
+
+/// A workspace with three members, where `b` depends on `a`, checked into a Git repository with an
+/// `upstream` branch pointing at the initial commit.
+struct TestWorkspace {
+    // Held to keep the directory alive.
+    _dir: TempDir,
+    root: Utf8PathBuf,
+}
+
+impl TestWorkspace {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().expect("temp dir created");
+        let root =
+            Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).expect("temp dir is valid UTF-8");
+        let workspace = Self { _dir: dir, root };
+
+        workspace.git(&["init", "-q"]);
+        workspace.write(".gitignore", "/target\nCargo.lock\n");
+        workspace.write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\", \"b\", \"c\"]\n",
+        );
+        workspace.write("a/Cargo.toml", &manifest("a", ""));
+        workspace.write("a/src/lib.rs", "");
+        workspace.write("b/Cargo.toml", &manifest("b", "a = { path = \"../a\" }\n"));
+        workspace.write("b/src/lib.rs", "");
+        workspace.write("c/Cargo.toml", &manifest("c", ""));
+        workspace.write("c/src/lib.rs", "");
+        workspace.git(&["add", "-A"]);
+        workspace.git(&[
+            "-c",
+            "user.name=Test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "initial",
+        ]);
+        workspace.git(&["branch", "upstream"]);
+        workspace
+    }
+
+    fn write(&self, path: &str, contents: &str) {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().expect("path has parent")).expect("dir created");
+        fs::write(path, contents).expect("file written");
+    }
+
+    fn git(&self, args: &[&str]) {
+        let status = Command::new("git")
+            .current_dir(&self.root)
+            .args(args)
+            .status()
+            .expect("git ran");
+        assert!(status.success(), "git {:?} succeeded", args);
+    }
+
+    /// Runs `cargo-determinator` from the given directory relative to the workspace root, and
+    /// returns its exit code and standard output.
+    fn run(&self, dir: &str, args: &[&str]) -> (i32, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_cargo-determinator"))
+            .current_dir(self.root.join(dir))
+            .args(args)
+            .output()
+            .expect("cargo-determinator ran");
+        let stdout = String::from_utf8(output.stdout).expect("stdout is valid UTF-8");
+        let code = output.status.code().expect("process exited normally");
+        assert!(
+            code == 0 || code == ALL_AFFECTED_EXIT_CODE,
+            "cargo-determinator {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr),
+        );
+        (code, stdout)
+    }
+
+    fn root(&self) -> &Utf8Path {
+        &self.root
+    }
+}
+
+fn manifest(name: &str, dependencies: &str) -> String {
+    format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[dependencies]\n{}",
+        name, dependencies
+    )
+}
+
+#[test]
+fn output_formats() {
+    let workspace = TestWorkspace::new();
+    workspace.write("a/src/lib.rs", "pub fn changed() {}\n");
+
+    let (code, names) = workspace.run(".", &["--git-diff", "upstream"]);
+    assert_eq!(code, 0, "not every package is affected");
+    assert_eq!(names, "a\nb\n", "a changed and b depends on it");
+
+    let (code, cargo_test) =
+        workspace.run(".", &["--git-diff", "upstream", "--format", "cargo-test"]);
+    assert_eq!(code, 0);
+    assert_eq!(cargo_test, "-p a -p b\n");
+
+    let (code, json) = workspace.run(".", &["--git-diff", "upstream", "--format", "json"]);
+    assert_eq!(code, 0);
+    let json: Value = serde_json::from_str(&json).expect("output is valid JSON");
+    assert_eq!(json["all-affected"], false);
+    assert_eq!(json["path-changed"], serde_json::json!(["a"]));
+    assert_eq!(json["affected"], serde_json::json!(["a", "b"]));
+    assert_eq!(json["causes"]["a"]["cause"], "path-changed");
+    assert_eq!(json["causes"]["a"]["path"], "a/src/lib.rs");
+    assert_eq!(json["causes"]["b"]["cause"], "dependency");
+}
+
+#[test]
+fn from_member_directory() {
+    let workspace = TestWorkspace::new();
+    // Changes outside the member the command is run from must be seen, with paths relative to the
+    // workspace root.
+    workspace.write("a/src/lib.rs", "pub fn changed() {}\n");
+
+    let (code, names) = workspace.run("b", &["--git-diff", "upstream"]);
+    assert_eq!(code, 0);
+    assert_eq!(names, "a\nb\n", "changes in a are seen from b");
+
+    let (code, names) = workspace.run("c/src", &["--git-diff", "upstream"]);
+    assert_eq!(code, 0);
+    assert_eq!(names, "a\nb\n", "changes in a are seen from c/src");
+
+    let manifest_path = workspace.root().join("c/Cargo.toml");
+    let (code, names) = workspace.run(
+        ".",
+        &[
+            "--git-diff",
+            "upstream",
+            "--manifest-path",
+            manifest_path.as_str(),
+        ],
+    );
+    assert_eq!(code, 0);
+    assert_eq!(
+        names, "a\nb\n",
+        "changes in a are seen with --manifest-path"
+    );
+}
+
+#[test]
+fn fail_if_all_affected() {
+    let workspace = TestWorkspace::new();
+    // Files outside packages cause every package to be affected.
+    workspace.write("scripts/build.sh", "echo build\n");
+
+    let args = &["--git-diff", "upstream", "--include-untracked"];
+    let (code, names) = workspace.run("b", args);
+    assert_eq!(code, 0, "without --fail-if-all-affected, exits with 0");
+    assert_eq!(names, "a\nb\nc\n");
+
+    let (code, names) = workspace.run(
+        "b",
+        &[
+            "--git-diff",
+            "upstream",
+            "--include-untracked",
+            "--fail-if-all-affected",
+        ],
+    );
+    assert_eq!(code, ALL_AFFECTED_EXIT_CODE);
+    assert_eq!(names, "a\nb\nc\n", "results are printed out");
+
+    // If only some packages are affected, --fail-if-all-affected doesn't change the exit code.
+    let workspace = TestWorkspace::new();
+    workspace.write("c/src/lib.rs", "pub fn changed() {}\n");
+    let (code, names) = workspace.run("b", &["--git-diff", "upstream", "--fail-if-all-affected"]);
+    assert_eq!(code, 0);
+    assert_eq!(names, "c\n");
+}
@@ -0,0 +1,351 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::explain::SummaryChange;
+use guppy::{
+    graph::{
+        cargo::{CargoOptions, CargoSet},
+        feature::{FeatureEdge, FeatureFilter, FeatureId, FeatureSet, StandardFeatures},
+        summaries::{CargoOptionsSummary, Summary, SummaryId, SummarySource},
+        DependencyDirection, EnabledStatus, PackageGraph, PackageLink, PackageMetadata, PackageSet,
+        PlatformStatus,
+    },
+    DependencyKind,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, hash::Hasher};
+use twox_hash::XxHash64;
+
+/// The results of simulated Cargo builds for every package in a workspace.
+///
+/// Simulating builds is usually the most expensive part of a determinator run. A `BuildCache` can
+/// be computed once for a package graph, serialized, and passed into later runs through
+/// [`Determinator::add_build_cache`](crate::Determinator::add_build_cache). For example, the
+/// cache for the main branch can be computed in a post-merge job and reused by every pull
+/// request based on it.
+///
+/// A cache is keyed by a hash of the package graph and by the Cargo options used for the builds.
+/// It also serves as the source for the determinator's reverse index of workspace dependencies.
+///
+/// # Examples
+///
+/// ```rust
+/// use determinator::{BuildCache, Determinator};
+/// use guppy::CargoMetadata;
+///
+/// let old_metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/guppy/metadata_guppy_78cb7e8.json")).unwrap();
+/// let old = old_metadata.build_graph().unwrap();
+/// let new_metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/guppy/metadata_guppy_869476c.json")).unwrap();
+/// let new = new_metadata.build_graph().unwrap();
+///
+/// // Compute the cache for the old graph, e.g. in a job that runs on the main branch.
+/// let cache = Determinator::new(&old, &old).new_build_cache();
+/// let serialized = cache.to_string().unwrap();
+///
+/// // Later runs can load the cache instead of simulating builds against the old graph.
+/// let cache = BuildCache::parse(&serialized).unwrap();
+/// let mut determinator = Determinator::new(&old, &new);
+/// determinator.add_build_cache(&cache);
+/// determinator.add_changed_paths(vec!["guppy/src/lib.rs"]);
+/// let determinator_set = determinator.compute();
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildCache {
+    /// A hash of the package graph the builds were simulated against, as returned by
+    /// [`graph_hash`](Self::graph_hash).
+    pub graph_hash: String,
+
+    /// The Cargo options the builds were simulated with.
+    pub cargo_options: CargoOptionsSummary,
+
+    /// Build summaries for each workspace package, keyed by package name.
+    #[serde(rename = "package")]
+    pub packages: BTreeMap<String, PackageBuildSummaries>,
+}
+
+impl BuildCache {
+    /// Simulates builds for every workspace package in `graph` with the given options.
+    ///
+    /// `features_only` is used for feature unification: see the documentation for
+    /// [`Determinator::set_features_only`](crate::Determinator::set_features_only).
+    pub fn new<'g>(
+        graph: &'g PackageGraph,
+        cargo_options: &CargoOptions<'_>,
+        features_only: &FeatureSet<'g>,
+    ) -> Self {
+        let packages = graph
+            .workspace()
+            .par_iter_by_name()
+            .map(|(name, package)| {
+                let summaries = PackageBuildSummaries::new(package, cargo_options, features_only);
+                (name.to_owned(), summaries)
+            })
+            .collect();
+        Self {
+            graph_hash: Self::graph_hash(graph),
+            cargo_options: options_summary(graph, cargo_options, features_only),
+            packages,
+        }
+    }
+
+    /// Deserializes a build cache from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Serializes this build cache to a TOML string.
+    pub fn to_string(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    /// Returns a hash of everything in `graph` that may affect simulated builds.
+    ///
+    /// The hash is stable across runs and machines: it doesn't depend on the location of the
+    /// workspace or on the order in which packages are listed in the metadata. It may change across
+    /// versions of this crate.
+    pub fn graph_hash(graph: &PackageGraph) -> String {
+        let mut lines = vec![format!("determinator {}", env!("CARGO_PKG_VERSION"))];
+        for package in graph.packages() {
+            let mut features: Vec<_> = package.named_features().collect();
+            features.sort_unstable();
+            lines.push(format!(
+                "package {} proc-macro={} features={:?}",
+                package_desc(package),
+                package.is_proc_macro(),
+                features,
+            ));
+            lines.extend(package.direct_links().map(link_desc));
+        }
+        // Feature links cover features enabling other features, both within a package and across
+        // dependencies. The platforms they're enabled on are covered by package links.
+        lines.extend(
+            graph
+                .feature_graph()
+                .resolve_all()
+                .links(DependencyDirection::Forward)
+                .map(|(from, to, edge)| {
+                    let edge = match edge {
+                        FeatureEdge::FeatureToBase => "to-base",
+                        FeatureEdge::CrossPackage(_) => "cross-package",
+                        FeatureEdge::FeatureDependency => "feature-dependency",
+                    };
+                    format!(
+                        "feature-link {} -> {} {}",
+                        feature_desc(graph, from),
+                        feature_desc(graph, to),
+                        edge
+                    )
+                }),
+        );
+        lines.sort_unstable();
+
+        // Use a fixed seed to ensure stable hashes.
+        let mut hasher = XxHash64::default();
+        for line in &lines {
+            hasher.write(line.as_bytes());
+            hasher.write_u8(b'\n');
+        }
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Returns true if this cache was computed for `graph` with the given options.
+    pub(crate) fn matches(&self, graph_hash: &str, cargo_options: &CargoOptionsSummary) -> bool {
+        self.graph_hash == graph_hash && &self.cargo_options == cargo_options
+    }
+}
+
+/// Build summaries for a workspace package, as part of a [`BuildCache`].
+///
+/// Summaries don't include metadata: the options they were computed with are stored in the
+/// `BuildCache`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackageBuildSummaries {
+    /// The build with no features enabled.
+    pub none: Summary,
+
+    /// The build with default features enabled.
+    pub default: Summary,
+
+    /// The build with all features enabled.
+    pub all: Summary,
+}
+
+impl PackageBuildSummaries {
+    pub(crate) fn new<'g>(
+        package: PackageMetadata<'g>,
+        cargo_options: &CargoOptions<'_>,
+        features_only: &FeatureSet<'g>,
+    ) -> Self {
+        let summary = |filter| make_summary(&package, filter, cargo_options, features_only);
+        let (none, (default, all)) = rayon::join(
+            || summary(StandardFeatures::None),
+            || {
+                rayon::join(
+                    || summary(StandardFeatures::Default),
+                    || summary(StandardFeatures::All),
+                )
+            },
+        );
+
+        Self { none, default, all }
+    }
+
+    /// Returns the workspace packages built in any of the simulated builds.
+    pub(crate) fn workspace_set<'g>(&self, graph: &'g PackageGraph) -> PackageSet<'g> {
+        let workspace = graph.workspace();
+        let ids = self
+            .summaries()
+            .flat_map(|summary| {
+                summary
+                    .target_packages
+                    .keys()
+                    .chain(summary.host_packages.keys())
+            })
+            .filter_map(|summary_id| match &summary_id.source {
+                SummarySource::Workspace { workspace_path } => {
+                    workspace.member_by_path(workspace_path).ok()
+                }
+                _ => None,
+            })
+            .map(|package| package.id());
+        graph.resolve_ids(ids).expect("package IDs are valid")
+    }
+
+    /// Returns the packages that changed from `old` across all simulated builds, or an empty list
+    /// if there are no changes.
+    pub(crate) fn changes(&self, old: &PackageBuildSummaries) -> Vec<SummaryChange> {
+        let mut changes: BTreeMap<SummaryId, _> = BTreeMap::new();
+        for (new_summary, old_summary) in self.summaries().zip(old.summaries()) {
+            let diff = old_summary.diff(new_summary);
+            for (summary_id, status) in diff
+                .target_packages
+                .changed
+                .iter()
+                .chain(&diff.host_packages.changed)
+            {
+                changes
+                    .entry((*summary_id).clone())
+                    .or_insert_with(|| status.tag());
+            }
+        }
+        changes
+            .into_iter()
+            .map(|(package, change)| SummaryChange { package, change })
+            .collect()
+    }
+
+    fn summaries(&self) -> impl Iterator<Item = &Summary> {
+        std::iter::once(&self.none)
+            .chain(std::iter::once(&self.default))
+            .chain(std::iter::once(&self.all))
+    }
+}
+
+/// Describes a package without its package ID, which contains absolute paths for local packages.
+///
+/// Local package sources are relative to the workspace root.
+fn package_desc(package: PackageMetadata<'_>) -> String {
+    format!(
+        "{} {} {}",
+        package.name(),
+        package.version(),
+        package.source()
+    )
+}
+
+fn feature_desc(graph: &PackageGraph, feature_id: FeatureId<'_>) -> String {
+    let package = graph
+        .metadata(feature_id.package_id())
+        .expect("valid package ID");
+    format!(
+        "{}/{}",
+        package_desc(package),
+        feature_id.feature().unwrap_or("[base]")
+    )
+}
+
+/// Describes a link and the platforms each kind of dependency is enabled on.
+fn link_desc(link: PackageLink<'_>) -> String {
+    let mut desc = format!(
+        "link {} -> {} as {}",
+        package_desc(link.from()),
+        package_desc(link.to()),
+        link.resolved_name()
+    );
+    for &kind in DependencyKind::VALUES {
+        let req = link.req_for_kind(kind);
+        if !req.is_present() {
+            continue;
+        }
+        desc.push_str(&format!(
+            " {}: status={} default-features={} no-default-features={}",
+            kind,
+            status_desc(req.status()),
+            status_desc(req.default_features()),
+            status_desc(req.no_default_features()),
+        ));
+        let mut features: Vec<_> = req.features().collect();
+        features.sort_unstable();
+        features.dedup();
+        for feature in features {
+            desc.push_str(&format!(
+                " feature {}={}",
+                feature,
+                status_desc(req.feature_status(feature))
+            ));
+        }
+    }
+    desc
+}
+
+fn status_desc(status: EnabledStatus<'_>) -> String {
+    format!(
+        "(required {}, optional {})",
+        platform_status_desc(status.required_status()),
+        platform_status_desc(status.optional_status()),
+    )
+}
+
+fn platform_status_desc(status: PlatformStatus<'_>) -> String {
+    match status {
+        PlatformStatus::Never => "never".to_owned(),
+        PlatformStatus::Always => "always".to_owned(),
+        PlatformStatus::PlatformDependent { eval } => {
+            let mut specs: Vec<_> = eval.target_specs().map(|spec| spec.to_string()).collect();
+            specs.sort_unstable();
+            specs.join(" | ")
+        }
+    }
+}
+
+/// Returns the summary of the options used for builds, used as part of the cache key.
+pub(crate) fn options_summary(
+    graph: &PackageGraph,
+    cargo_options: &CargoOptions<'_>,
+    features_only: &FeatureSet<'_>,
+) -> CargoOptionsSummary {
+    CargoOptionsSummary::new(graph, features_only, cargo_options)
+        .expect("platform summaries are always valid")
+}
+
+fn make_summary<'x>(
+    package: &PackageMetadata<'x>,
+    filter: impl FeatureFilter<'x>,
+    cargo_options: &CargoOptions<'_>,
+    features_only: &FeatureSet<'x>,
+) -> Summary {
+    let package_set = package.to_package_set();
+    let initials = package_set.to_feature_set(filter);
+
+    let cargo_set =
+        CargoSet::new(initials, features_only.clone(), cargo_options).expect("valid cargo options");
+    let mut summary = cargo_set
+        .to_summary(cargo_options)
+        .expect("platform summaries are always valid");
+    // The options are stored once in the BuildCache.
+    summary.metadata = None;
+    summary
+}
@@ -2,26 +2,30 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    build_cache::{options_summary, BuildCache, PackageBuildSummaries},
     errors::RulesError,
+    explain::{AffectedCause, AffectedCauses, PathMatchedBy, SummaryChange},
+    file_deps::FileDependencies,
     rules::{
         DeterminatorPostRule, DeterminatorRules, MarkChangedImpl, PathMatch, PathRuleImpl,
-        RulesImpl,
+        RuleIndex, RulesImpl,
     },
+    targets::{narrow_to_targets, DeterminatorTargetSet},
 };
 use camino::Utf8Path;
 use globset::Candidate;
 use guppy::{
     graph::{
-        cargo::{CargoOptions, CargoSet},
-        feature::{FeatureFilter, FeatureSet, StandardFeatures},
+        cargo::CargoOptions,
+        feature::{FeatureSet, StandardFeatures},
         DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace,
     },
     PackageId, Platform,
 };
-use itertools::Itertools;
 use petgraph::{graphmap::GraphMap, Directed};
 use rayon::prelude::*;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 /// Determine target dependencies from changed files and packages in a workspace.
 ///
@@ -41,6 +45,8 @@ pub struct Determinator<'g, 'a> {
     old_features_only: Option<FeatureSet<'a>>,
     new_features_only: Option<FeatureSet<'g>>,
     changed_paths: Vec<&'a Utf8Path>,
+    file_deps: Option<FileDependencies<'g>>,
+    build_caches: Vec<&'a BuildCache>,
 }
 
 impl<'g, 'a> Determinator<'g, 'a> {
@@ -55,6 +61,8 @@ impl<'g, 'a> Determinator<'g, 'a> {
             old_features_only: None,
             new_features_only: None,
             changed_paths: vec![],
+            file_deps: None,
+            build_caches: vec![],
         }
     }
 
@@ -65,7 +73,8 @@ impl<'g, 'a> Determinator<'g, 'a> {
     /// canonical separator for the platform (e.g. `/` on Unix platforms and `\` on Windows).
     ///
     /// [`Paths0`](crate::Paths0) in this crate provides a convenient way to handle null-separated
-    /// paths as produced by source control systems.
+    /// paths as produced by source control systems. For Git repositories,
+    /// [`GitDiff`](crate::GitDiff) reads changed paths directly.
     ///
     /// # Should you include untracked and ignored files?
     ///
@@ -113,11 +122,17 @@ impl<'g, 'a> Determinator<'g, 'a> {
         path: impl AsRef<Utf8Path>,
         match_cb: impl FnMut(&'g PackageId),
     ) -> PathMatch {
+        let mut match_cb = match_cb;
         process_path(
             path.as_ref(),
             &self.new.workspace(),
             &self.rules.path_rules,
-            match_cb,
+            self.file_deps.as_ref(),
+            |claim| {
+                if let PathClaim::Package(id, _) = claim {
+                    match_cb(id);
+                }
+            },
         )
     }
 
@@ -141,6 +156,59 @@ impl<'g, 'a> Determinator<'g, 'a> {
         self
     }
 
+    /// Configures file dependencies found by scanning workspace source code.
+    ///
+    /// A changed path that a package depends on, for example through `include_str!` or
+    /// `cargo:rerun-if-changed`, marks that package as changed. This is applied after path rules,
+    /// and in addition to matching the path to its nearest ancestor package. A path rule that
+    /// skips further processing also skips file dependencies.
+    ///
+    /// For more, see the documentation for [`FileDependencies`](FileDependencies).
+    pub fn set_file_dependencies(&mut self, file_deps: FileDependencies<'g>) -> &mut Self {
+        self.file_deps = Some(file_deps);
+        self
+    }
+
+    /// Adds a cache of previously simulated builds.
+    ///
+    /// A cache is used for the old or new package graph if it was computed for that graph with
+    /// the same Cargo options and features-only packages. Caches that don't match either graph
+    /// are ignored, and builds are simulated as usual.
+    ///
+    /// For more, see the documentation for [`BuildCache`](BuildCache).
+    pub fn add_build_cache(&mut self, build_cache: &'a BuildCache) -> &mut Self {
+        self.build_caches.push(build_cache);
+        self
+    }
+
+    /// Simulates builds against the old package graph and returns a cache of the results.
+    ///
+    /// The cache uses the Cargo options and features-only packages currently configured.
+    pub fn old_build_cache(&self) -> BuildCache {
+        let default_options = Self::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+        let default_features_only = self.old.feature_graph().resolve_none();
+        let features_only = self
+            .old_features_only
+            .as_ref()
+            .unwrap_or(&default_features_only);
+        BuildCache::new(self.old, cargo_options, features_only)
+    }
+
+    /// Simulates builds against the new package graph and returns a cache of the results.
+    ///
+    /// The cache uses the Cargo options and features-only packages currently configured.
+    pub fn new_build_cache(&self) -> BuildCache {
+        let default_options = Self::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+        let default_features_only = self.new.feature_graph().resolve_none();
+        let features_only = self
+            .new_features_only
+            .as_ref()
+            .unwrap_or(&default_features_only);
+        BuildCache::new(self.new, cargo_options, features_only)
+    }
+
     /// Returns the default Cargo options used by the determinator.
     ///
     /// These are the same as the defaults returned by [`CargoOptions::new`](CargoOptions::new),
@@ -161,7 +229,7 @@ impl<'g, 'a> Determinator<'g, 'a> {
     /// pseudo-packages or workspace-hack packages, including those generated by tools like
     /// [Hakari](https://docs.rs/hakari).
     ///
-    /// For more about `features_only`, see the documentation for [`CargoSet::new`](CargoSet::new).
+    /// For more about `features_only`, see the documentation for [`CargoSet::new`](guppy::graph::cargo::CargoSet::new).
     ///
     /// The package names are expected to be present in the new graph, but may not be present in the
     /// old `PackageGraph`.
@@ -202,52 +270,217 @@ impl<'g, 'a> Determinator<'g, 'a> {
     /// Uses the old and new sets and the list of changed files to compute the list
     /// of projects that is affected.
     pub fn compute(&self) -> DeterminatorSet<'g> {
-        let mut build_state = BuildState::new(self);
+        let default_options = Self::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+        match self.process_paths() {
+            Ok(path_state) => self.compute_build(path_state, cargo_options),
+            Err(cause) => self.all_changed(&cause),
+        }
+    }
+
+    /// Computes the list of affected projects separately for each target platform.
+    ///
+    /// Returns one `DeterminatorSet` per platform, in the same order as `platforms`.
+    ///
+    /// For each platform, the Cargo options passed into [`set_cargo_options`](Self::set_cargo_options)
+    /// (or the defaults) are used with the target platform set to that platform. The host platform
+    /// is not changed. This means that a change to a dependency that is only used on a particular
+    /// platform only affects the results for that platform.
+    ///
+    /// Changed paths are processed once, and build simulations are run once per platform. This is
+    /// equivalent to, but faster than, calling [`compute`](Self::compute) once for each platform.
+    pub fn compute_platforms<'p>(
+        &self,
+        platforms: impl IntoIterator<Item = &'p Platform<'p>>,
+    ) -> Vec<DeterminatorSet<'g>> {
+        let default_options = Self::default_cargo_options();
+        let base_options = self.cargo_options.unwrap_or(&default_options);
+        let path_state = self.process_paths();
+
+        platforms
+            .into_iter()
+            .map(|platform| match &path_state {
+                Ok(path_state) => {
+                    let mut cargo_options: CargoOptions<'_> = base_options.clone();
+                    cargo_options.set_target_platform(Some(platform));
+                    self.compute_build(path_state.clone(), &cargo_options)
+                }
+                // Every package is affected on every platform.
+                Err(cause) => self.all_changed(cause),
+            })
+            .collect()
+    }
+
+    /// Computes the list of affected projects at the granularity of build targets.
+    ///
+    /// Changed paths that are matched to their nearest package, and that belong to test,
+    /// benchmark, example or binary targets, only affect those targets: packages that depend on
+    /// the package aren't affected. For example, a change to `tests/foo.rs` only affects the `foo`
+    /// test. Changes to integration test helpers such as `tests/common/mod.rs` affect every test in
+    /// `tests`, and changes to binaries also affect the package's tests and benchmarks.
+    ///
+    /// All other changes, such as to `src/lib.rs`, `build.rs` or `Cargo.toml`, affect every build
+    /// target in the package and fan out to dependents, just like in [`compute`](Self::compute).
+    pub fn compute_targets(&self) -> DeterminatorTargetSet<'g> {
+        let workspace = self.new.workspace();
+        let mut package_paths = vec![];
+        let mut target_changed: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+
+        for &path in &self.changed_paths {
+            let mut claims = vec![];
+            process_path(
+                path,
+                &workspace,
+                &self.rules.path_rules,
+                self.file_deps.as_ref(),
+                |claim| claims.push(claim),
+            );
+
+            // Only paths solely matched to their nearest package can be narrowed down.
+            let narrowed = match claims.as_slice() {
+                [PathClaim::Package(id, PathMatchedBy::NearestPackage)] => {
+                    let package = self.new.metadata(id).expect("valid package ID");
+                    narrow_to_targets(package, workspace.root(), path)
+                        .map(|targets| (package.id(), targets))
+                }
+                _ => None,
+            };
+            match narrowed {
+                Some((id, targets)) => target_changed.entry(id).or_default().extend(targets),
+                None => package_paths.push(path),
+            }
+        }
+
+        let mut determinator = self.clone();
+        determinator.changed_paths = package_paths;
+        let package_set = determinator.compute();
+        // Packages affected through other changes have all their targets affected.
+        let target_changed = target_changed
+            .into_iter()
+            .filter(|(id, _)| {
+                !package_set
+                    .affected_set
+                    .contains(id)
+                    .expect("valid package ID")
+                    && !self
+                        .rules
+                        .remove_affected
+                        .contains(id)
+                        .expect("valid package ID")
+            })
+            .collect();
+
+        DeterminatorTargetSet {
+            package_set,
+            target_changed,
+            graph: self.new,
+        }
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    /// Processes every changed path.
+    ///
+    /// Returns an error with the cause if every package is changed.
+    fn process_paths(&self) -> Result<PathState<'g>, AffectedCause> {
+        let mut path_state = PathState::default();
 
         // 1-2. Process every changed path.
         for path in &self.changed_paths {
-            build_state = match build_state.process_path(path) {
-                Some(build_state) => build_state,
-                None => {
-                    // The build state was discarded, which means that the entire workspace is
-                    // changed and affected.
-                    let path_changed_set = self.new.resolve_workspace();
-                    let affected_set = path_changed_set.clone();
-                    return DeterminatorSet {
-                        path_changed_set,
-                        // This is an empty set.
-                        summary_changed_set: self.new.resolve_none(),
-                        affected_set,
-                    };
+            path_state.process_path(self, path).map_err(|matched_by| {
+                AffectedCause::PathChanged {
+                    path: path.to_path_buf(),
+                    matched_by,
                 }
-            }
+            })?;
+        }
+
+        Ok(path_state)
+    }
+
+    /// Returns a result with the entire workspace changed and affected.
+    fn all_changed(&self, cause: &AffectedCause) -> DeterminatorSet<'g> {
+        let path_changed_set = self.new.resolve_workspace();
+        // Packages removed through package rules are never affected.
+        let affected_set = path_changed_set.difference(&self.rules.remove_affected);
+        let affected_causes = affected_set
+            .package_ids(DependencyDirection::Forward)
+            .map(|id| (id, cause.clone()))
+            .collect();
+        DeterminatorSet {
+            path_changed_set,
+            // This is an empty set.
+            summary_changed_set: self.new.resolve_none(),
+            affected_set,
+            affected_causes: AffectedCauses::new(self.new, affected_causes),
         }
+    }
+
+    /// Returns the first build cache that was computed for `graph` with the given options.
+    fn matching_build_cache<'x>(
+        &self,
+        graph: &'x PackageGraph,
+        cargo_options: &CargoOptions<'_>,
+        features_only: &FeatureSet<'x>,
+    ) -> Option<&'a BuildCache> {
+        if self.build_caches.is_empty() {
+            // Avoid hashing the graph if there are no caches to look at.
+            return None;
+        }
+        let graph_hash = BuildCache::graph_hash(graph);
+        let options = options_summary(graph, cargo_options, features_only);
+        self.build_caches
+            .iter()
+            .copied()
+            .find(|cache| cache.matches(&graph_hash, &options))
+    }
+
+    /// Computes the rest of the result using simulated Cargo builds with the given options.
+    fn compute_build(
+        &self,
+        path_state: PathState<'g>,
+        cargo_options: &CargoOptions<'_>,
+    ) -> DeterminatorSet<'g> {
+        let mut build_state = BuildState::new(self, cargo_options);
 
         // 3. Construct the path changed set from the given IDs.
         let path_changed_set = self
             .new
-            .resolve_ids(build_state.path_changed_ids.iter().copied())
+            .resolve_ids(path_state.path_changed_ids.iter().copied())
             .expect("package IDs are all valid");
 
         // 4. Use build summaries as another source of changes.
-        build_state.process_build_summaries();
+        build_state.process_build_summaries(&path_state.path_changed_ids);
         let summary_changed_set = self
             .new
-            .resolve_ids(build_state.summary_changed_ids.iter().copied())
+            .resolve_ids(build_state.summary_changed.keys().copied())
             .expect("package IDs are all valid");
 
         // 5. The affected set is the transitive closure of the graph constructed by looking at both
         // the build cache and Cargo rules.
+        let mut causes = path_state.path_causes;
+        causes.extend(
+            build_state
+                .summary_changed
+                .into_iter()
+                .map(|(id, changed_packages)| {
+                    (id, AffectedCause::SummaryChanged { changed_packages })
+                }),
+        );
         let affected_set = build_state.reverse_index.affected_closure(
             self.new,
-            &build_state.path_changed_ids,
-            &build_state.summary_changed_ids,
+            &path_state.path_changed_ids,
+            &self.rules.remove_affected,
+            &mut causes,
         );
 
         DeterminatorSet {
             path_changed_set,
             summary_changed_set,
             affected_set,
+            affected_causes: AffectedCauses::new(self.new, causes),
         }
     }
 }
@@ -256,12 +489,14 @@ impl<'g, 'a> Determinator<'g, 'a> {
 ///
 /// The lifetime `'g` is tied to the *new* `PackageGraph` passed to a `Determinator`.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct DeterminatorSet<'g> {
     /// The packages that were affected, directly or indirectly. This set is what most consumers
     /// care about.
     ///
     /// A package is in this set if it was marked changed due to a path or summaries changing, or if
-    /// a simulated Cargo build or package rule indicated that it is affected.
+    /// a simulated Cargo build or package rule indicated that it is affected. Packages removed by
+    /// a package rule with `remove-affected = true` are never in this set.
     pub affected_set: PackageSet<'g>,
 
     /// The packages that were marked changed because a file changed.
@@ -275,104 +510,184 @@ pub struct DeterminatorSet<'g> {
     /// This does not include packages marked changed through a path. For example, if a path rule
     /// caused all packages to be marked changed, further steps aren't run and this set is empty.
     pub summary_changed_set: PackageSet<'g>,
+
+    /// The reason each package in `affected_set` was affected.
+    ///
+    /// This can be used to explain to users why a package was marked affected, and can be
+    /// serialized to JSON and other formats through `serde`.
+    pub affected_causes: AffectedCauses<'g>,
 }
 
 // ---
 // Private structures
 // ---
 
+/// Packages marked changed through changed paths.
+///
+/// This doesn't depend on Cargo options, so it is shared across platforms.
+#[derive(Clone, Debug, Default)]
+struct PathState<'g> {
+    path_changed_ids: HashSet<&'g PackageId>,
+    // The first changed path to mark each package changed.
+    path_causes: HashMap<&'g PackageId, AffectedCause>,
+}
+
+impl<'g> PathState<'g> {
+    // An error stands for all packages in the workspace changed.
+    fn process_path(
+        &mut self,
+        determinator: &Determinator<'g, '_>,
+        path: &Utf8Path,
+    ) -> Result<(), PathMatchedBy> {
+        let mut all_matched_by = None;
+        process_path(
+            path,
+            &determinator.new.workspace(),
+            &determinator.rules.path_rules,
+            determinator.file_deps.as_ref(),
+            |claim| match claim {
+                PathClaim::Package(id, matched_by) => {
+                    self.path_changed_ids.insert(id);
+                    self.path_causes
+                        .entry(id)
+                        .or_insert_with(|| AffectedCause::PathChanged {
+                            path: path.to_path_buf(),
+                            matched_by,
+                        });
+                }
+                PathClaim::All(matched_by) => all_matched_by = Some(matched_by),
+            },
+        );
+        match all_matched_by {
+            Some(matched_by) => Err(matched_by),
+            None => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct BuildState<'g, 'a, 'b> {
     determinator: &'b Determinator<'g, 'a>,
-    path_changed_ids: HashSet<&'g PackageId>,
-    summary_changed_ids: HashSet<&'g PackageId>,
-    build_cache: CargoBuildCache<'g>,
+    cargo_options: &'b CargoOptions<'b>,
+    summary_changed: HashMap<&'g PackageId, Vec<SummaryChange>>,
+    build_cache: CargoBuildCache<'g, 'a>,
+    old_build_cache: Option<&'a BuildCache>,
     reverse_index: ReverseIndex<'g>,
 }
 
 impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
-    fn new(determinator: &'b Determinator<'g, 'a>) -> Self {
-        let build_cache = CargoBuildCache::new(determinator);
+    fn new(determinator: &'b Determinator<'g, 'a>, cargo_options: &'b CargoOptions<'b>) -> Self {
+        let default_features_only = determinator.new.feature_graph().resolve_none();
+        let new_features_only = determinator
+            .new_features_only
+            .as_ref()
+            .unwrap_or(&default_features_only);
+        let new_build_cache =
+            determinator.matching_build_cache(determinator.new, cargo_options, new_features_only);
+
+        let default_features_only = determinator.old.feature_graph().resolve_none();
+        let old_features_only = determinator
+            .old_features_only
+            .as_ref()
+            .unwrap_or(&default_features_only);
+        let old_build_cache =
+            determinator.matching_build_cache(determinator.old, cargo_options, old_features_only);
+
+        let build_cache = CargoBuildCache::new(
+            determinator,
+            cargo_options,
+            new_features_only,
+            new_build_cache,
+        );
         let reverse_index = ReverseIndex::new(determinator, &build_cache);
         Self {
             determinator,
-            path_changed_ids: HashSet::new(),
-            summary_changed_ids: HashSet::new(),
+            cargo_options,
+            summary_changed: HashMap::new(),
             build_cache,
+            old_build_cache,
             reverse_index,
         }
     }
 
-    // A return value of None stands for all packages in the workspace changed.
-    fn process_path(mut self, path: &Utf8Path) -> Option<Self> {
-        let status = process_path(
-            path,
-            &self.determinator.new.workspace(),
-            &self.determinator.rules.path_rules,
-            |id| {
-                self.path_changed_ids.insert(id);
-            },
-        );
-        match status {
-            PathMatch::RuleMatchedAll | PathMatch::NoMatches => None,
-            PathMatch::RuleMatched(_) | PathMatch::AncestorMatched => Some(self),
-        }
-    }
-
-    fn process_build_summaries(&mut self) {
+    fn process_build_summaries(&mut self, path_changed_ids: &HashSet<&'g PackageId>) {
         // For each workspace package, if its build summaries have changed mark it changed.
-        let summary_changed_ids: Vec<_> = self
+        let summary_changed: Vec<_> = self
             .determinator
             .new
             .workspace()
             .par_iter_by_name()
             .filter_map(|(name, package)| {
                 // Don't include packages already marked as changed through paths. (This is documented.)
-                if !self.path_changed_ids.contains(package.id())
-                    && self.build_summaries_changed(name, package)
-                {
-                    Some(package.id())
-                } else {
-                    None
+                if path_changed_ids.contains(package.id()) {
+                    return None;
                 }
+                let changes = self.build_summaries_changed(name, package)?;
+                Some((package.id(), changes))
             })
             .collect();
-        self.summary_changed_ids.extend(summary_changed_ids);
+        self.summary_changed.extend(summary_changed);
     }
 
-    fn build_summaries_changed(&self, name: &str, package: PackageMetadata<'g>) -> bool {
+    // Returns the list of changes to the build summaries, or None if there are no changes.
+    fn build_summaries_changed(
+        &self,
+        name: &str,
+        package: PackageMetadata<'g>,
+    ) -> Option<Vec<SummaryChange>> {
         // Look up the package in the old metadata by path. (Workspace packages are uniquely
         // identified by both name and path -- this could be done by name as well).
         let old_workspace = self.determinator.old.workspace();
         let old_package = match old_workspace.member_by_name(name) {
             Ok(package) => package,
             Err(_) => {
-                // Member not found: this is new or renamed.
-                return true;
+                // Member not found: this is new or renamed. There are no specific packages to
+                // point to.
+                return Some(vec![]);
             }
         };
 
-        let default_options = Determinator::default_cargo_options();
-        let cargo_options = self.determinator.cargo_options.unwrap_or(&default_options);
-
-        let default_features_only = self.determinator.old.feature_graph().resolve_none();
-        let features_only = self
-            .determinator
-            .old_features_only
-            .as_ref()
-            .unwrap_or(&default_features_only);
-
-        let old_result = BuildResult::new(old_package, cargo_options, features_only);
+        let cached = self
+            .old_build_cache
+            .and_then(|cache| cache.packages.get(name));
+        let old_result = match cached {
+            Some(summaries) => Cow::Borrowed(summaries),
+            None => {
+                let default_features_only = self.determinator.old.feature_graph().resolve_none();
+                let features_only = self
+                    .determinator
+                    .old_features_only
+                    .as_ref()
+                    .unwrap_or(&default_features_only);
+                Cow::Owned(PackageBuildSummaries::new(
+                    old_package,
+                    self.cargo_options,
+                    features_only,
+                ))
+            }
+        };
         let new_result = &self.build_cache.result_cache[package.id()];
-        new_result.is_changed(&old_result, cargo_options)
+        let changes = new_result.changes(&old_result);
+        if changes.is_empty() {
+            None
+        } else {
+            Some(changes)
+        }
     }
 }
 
+/// A package, or all packages, claimed by a path.
+enum PathClaim<'g> {
+    Package(&'g PackageId, PathMatchedBy),
+    All(PathMatchedBy),
+}
+
 fn process_path<'g>(
     path: &Utf8Path,
     workspace: &Workspace<'g>,
     path_rules: &[PathRuleImpl<'g>],
-    mut match_cb: impl FnMut(&'g PackageId),
+    file_deps: Option<&FileDependencies<'g>>,
+    mut match_cb: impl FnMut(PathClaim<'g>),
 ) -> PathMatch {
     let candidate = Candidate::new(path);
 
@@ -383,11 +698,17 @@ fn process_path<'g>(
             match &rule.mark_changed {
                 MarkChangedImpl::Packages(packages) => {
                     for package in packages {
-                        match_cb(package.id());
+                        let matched_by = PathMatchedBy::Rule {
+                            rule_index: rule.rule_index,
+                        };
+                        match_cb(PathClaim::Package(package.id(), matched_by));
                     }
                 }
                 MarkChangedImpl::All => {
                     // Mark all packages changed.
+                    match_cb(PathClaim::All(PathMatchedBy::RuleMatchedAll {
+                        rule_index: rule.rule_index,
+                    }));
                     return PathMatch::RuleMatchedAll;
                 }
             }
@@ -411,41 +732,65 @@ fn process_path<'g>(
         }
     }
 
-    // 2. Map the path to its nearest ancestor package.
+    // 2. Mark packages that depend on this path through their source code as changed.
+    let mut file_dep_matched = false;
+    if let Some(file_deps) = file_deps {
+        for dep in file_deps.dependencies_for(path) {
+            file_dep_matched = true;
+            match_cb(PathClaim::Package(
+                dep.package.id(),
+                PathMatchedBy::FileDependency {
+                    dependency_kind: dep.kind,
+                },
+            ));
+        }
+    }
+
+    // 3. Map the path to its nearest ancestor package.
     for ancestor in path.ancestors() {
         if let Ok(package) = workspace.member_by_path(ancestor) {
-            match_cb(package.id());
+            match_cb(PathClaim::Package(
+                package.id(),
+                PathMatchedBy::NearestPackage,
+            ));
             return PathMatch::AncestorMatched;
         }
     }
+    if file_dep_matched {
+        return PathMatch::FileDependencyMatched;
+    }
 
-    // 3. If a file didn't match anything so far, rebuild everything.
+    // 4. If a file didn't match anything so far, rebuild everything.
+    match_cb(PathClaim::All(PathMatchedBy::NoMatches));
     PathMatch::NoMatches
 }
 
 /// Stores a build cache of every package in a workspace.
 #[derive(Debug)]
-struct CargoBuildCache<'g> {
-    result_cache: HashMap<&'g PackageId, BuildResult<'g>>,
+struct CargoBuildCache<'g, 'a> {
+    result_cache: HashMap<&'g PackageId, Cow<'a, PackageBuildSummaries>>,
 }
 
-impl<'g> CargoBuildCache<'g> {
-    fn new(determinator: &Determinator<'g, '_>) -> Self {
-        let default_options = Determinator::default_cargo_options();
-        let cargo_options = determinator.cargo_options.unwrap_or(&default_options);
-
+impl<'g, 'a> CargoBuildCache<'g, 'a> {
+    fn new(
+        determinator: &Determinator<'g, 'a>,
+        cargo_options: &CargoOptions<'_>,
+        features_only: &FeatureSet<'g>,
+        build_cache: Option<&'a BuildCache>,
+    ) -> Self {
         let workspace = determinator.new.workspace();
-        let default_features_only = determinator.new.feature_graph().resolve_none();
-        let features_only = determinator
-            .new_features_only
-            .as_ref()
-            .unwrap_or(&default_features_only);
-
         let result_cache: HashMap<_, _> = workspace
-            .par_iter()
-            .map(|package| {
+            .par_iter_by_name()
+            .map(|(name, package)| {
                 let id = package.id();
-                let build_result = BuildResult::new(package, cargo_options, features_only);
+                let build_result = match build_cache.and_then(|cache| cache.packages.get(name)) {
+                    Some(summaries) => Cow::Borrowed(summaries),
+                    None => Cow::Owned(PackageBuildSummaries::new(
+                        package,
+                        cargo_options,
+                        features_only,
+                    )),
+                };
                 (id, build_result)
             })
             .collect();
@@ -454,105 +799,6 @@ impl<'g> CargoBuildCache<'g> {
     }
 }
 
-#[derive(Debug)]
-struct BuildResult<'g> {
-    none: CargoSet<'g>,
-    default: CargoSet<'g>,
-    all: CargoSet<'g>,
-    // TODO: add support for more feature sets?
-}
-
-impl<'g> BuildResult<'g> {
-    fn new(
-        package: PackageMetadata<'g>,
-        cargo_options: &CargoOptions<'_>,
-        features_only: &FeatureSet<'g>,
-    ) -> Self {
-        let (none, (default, all)) = rayon::join(
-            || {
-                make_cargo_set(
-                    &package,
-                    StandardFeatures::None,
-                    cargo_options,
-                    features_only,
-                )
-            },
-            || {
-                rayon::join(
-                    || {
-                        make_cargo_set(
-                            &package,
-                            StandardFeatures::Default,
-                            cargo_options,
-                            features_only,
-                        )
-                    },
-                    || {
-                        make_cargo_set(
-                            &package,
-                            StandardFeatures::All,
-                            cargo_options,
-                            features_only,
-                        )
-                    },
-                )
-            },
-        );
-
-        Self { none, default, all }
-    }
-
-    /// Returns the unified set of workspace dependencies.
-    fn unified_workspace_set(&self, workspace_set: &PackageSet<'g>) -> PackageSet<'g> {
-        let target_set = self
-            .all_cargo_sets()
-            .map(|x| x.target_features().to_package_set())
-            .fold1(|a, b| a.union(&b))
-            .expect("at least one set");
-        let host_set = self
-            .all_cargo_sets()
-            .map(|x| x.host_features().to_package_set())
-            .fold1(|a, b| a.union(&b))
-            .expect("at least one set");
-
-        target_set.union(&host_set).intersection(workspace_set)
-    }
-
-    fn is_changed(&self, other: &BuildResult<'_>, cargo_options: &CargoOptions<'_>) -> bool {
-        for (a, b) in self.all_cargo_sets().zip(other.all_cargo_sets()) {
-            let a_summary = a
-                .to_summary(cargo_options)
-                .expect("custom platforms currently unsupported");
-            let b_summary = b
-                .to_summary(cargo_options)
-                .expect("custom platforms currently unsupported");
-            let diff = a_summary.diff(&b_summary);
-            if diff.is_changed() {
-                return true;
-            }
-        }
-        false
-    }
-
-    fn all_cargo_sets<'a>(&'a self) -> impl Iterator<Item = &'a CargoSet<'g>> + 'a {
-        std::iter::once(&self.none)
-            .chain(std::iter::once(&self.default))
-            .chain(std::iter::once(&self.all))
-    }
-}
-
-fn make_cargo_set<'x>(
-    package: &PackageMetadata<'x>,
-    filter: impl FeatureFilter<'x>,
-    cargo_options: &CargoOptions<'_>,
-    features_only: &FeatureSet<'x>,
-) -> CargoSet<'x> {
-    let package_set = package.to_package_set();
-    let initials = package_set.to_feature_set(filter);
-
-    CargoSet::new(initials, features_only.clone(), cargo_options).expect("valid cargo options")
-}
-
 /// A reverse index of if a package is affected -> what else gets marked changed or affected.
 #[derive(Debug)]
 struct ReverseIndex<'g> {
@@ -564,22 +810,20 @@ struct ReverseIndex<'g> {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ReverseIndexEdge {
     /// This edge was added as a package rule. This always takes precedence over `CargoBuild`.
-    PackageRule,
+    PackageRule(RuleIndex),
     /// This edge was added through the Cargo build cache.
     CargoBuild,
 }
 
 impl<'g> ReverseIndex<'g> {
-    fn new(determinator: &Determinator<'g, '_>, build_cache: &CargoBuildCache<'g>) -> Self {
+    fn new(determinator: &Determinator<'g, '_>, build_cache: &CargoBuildCache<'g, '_>) -> Self {
         let mut reverse_index = GraphMap::new();
 
-        let workspace_set = determinator.new.resolve_workspace();
-
         // First, look at the result cache and add edges based on that.
         for (id, build_result) in &build_cache.result_cache {
             reverse_index.extend(
                 build_result
-                    .unified_workspace_set(&workspace_set)
+                    .workspace_set(determinator.new)
                     .package_ids(DependencyDirection::Forward)
                     .map(|dep_id| (Some(dep_id), Some(*id), ReverseIndexEdge::CargoBuild)),
             );
@@ -599,7 +843,7 @@ impl<'g> ReverseIndex<'g> {
                             (
                                 Some(on_affected),
                                 Some(package.id()),
-                                ReverseIndexEdge::PackageRule,
+                                ReverseIndexEdge::PackageRule(package_rule.rule_index),
                             )
                         }));
                     }
@@ -608,7 +852,7 @@ impl<'g> ReverseIndex<'g> {
                         reverse_index.add_edge(
                             Some(on_affected),
                             None,
-                            ReverseIndexEdge::PackageRule,
+                            ReverseIndexEdge::PackageRule(package_rule.rule_index),
                         );
                     }
                 }
@@ -618,12 +862,25 @@ impl<'g> ReverseIndex<'g> {
         Self { reverse_index }
     }
 
+    /// Computes the affected closure.
+    ///
+    /// `causes` must contain a cause for every directly changed package: these are used as the
+    /// initial set. Causes are added for every other affected package.
+    ///
+    /// Packages in `remove_affected` are never affected, and edges out of them aren't followed.
+    /// Their causes are removed.
     fn affected_closure(
         &self,
         package_graph: &'g PackageGraph,
         path_changed: &HashSet<&'g PackageId>,
-        summary_changed: &HashSet<&'g PackageId>,
+        remove_affected: &PackageSet<'g>,
+        causes: &mut HashMap<&'g PackageId, AffectedCause>,
     ) -> PackageSet<'g> {
+        let removed: HashSet<_> = remove_affected
+            .package_ids(DependencyDirection::Forward)
+            .collect();
+        causes.retain(|id, _| !removed.contains(id));
+
         // This is a *really* interesting DFS, in that there's one restriction: you can't follow
         // two CargoBuild edges consecutively. Also, in the initial set, path_changed allows
         // CargoBuild to be followed once while summary_changed doesn't allow it to be followed.
@@ -638,10 +895,16 @@ impl<'g> ReverseIndex<'g> {
 
         // The order of what goes in the stack doesn't matter for correctness, but putting Allowed
         // at the end (and therefore popping it first) lowers the chance of an upgrade re-traversal.
-        let mut stack: Vec<_> = summary_changed
-            .iter()
+        let mut stack: Vec<_> = causes
+            .keys()
+            .filter(|id| !path_changed.contains(*id))
             .map(|id| (*id, NotAllowed))
-            .chain(path_changed.iter().map(|id| (*id, Allowed)))
+            .chain(
+                path_changed
+                    .iter()
+                    .filter(|id| !removed.contains(*id))
+                    .map(|id| (*id, Allowed)),
+            )
             .collect();
 
         // Do a DFS with two maps, in case there are cycles (can happen with dev deps).
@@ -679,11 +942,21 @@ impl<'g> ReverseIndex<'g> {
                         continue;
                     }
                     match neighbor {
+                        Some(neighbor) if removed.contains(neighbor) => {
+                            // Removed packages act as barriers.
+                        }
                         Some(neighbor) => {
                             let neighbor_follow = match edge {
                                 ReverseIndexEdge::CargoBuild => NotAllowed,
-                                ReverseIndexEdge::PackageRule => Allowed,
+                                ReverseIndexEdge::PackageRule(_) => Allowed,
                             };
+                            // The first edge a package is reached through is used as its cause.
+                            // Packages are never reached before the package they're reached
+                            // from, so causes can always be traced back to a changed package.
+                            if !causes.contains_key(neighbor) {
+                                let cause = edge_cause(package_graph, id, neighbor, edge);
+                                causes.insert(neighbor, cause);
+                            }
 
                             match (discovered.get(&neighbor), neighbor_follow) {
                                 (None, _) => {
@@ -702,7 +975,15 @@ impl<'g> ReverseIndex<'g> {
                         }
                         None => {
                             // Build everything, can just exit here.
-                            return package_graph.resolve_workspace();
+                            let workspace_set = package_graph
+                                .resolve_workspace()
+                                .difference(remove_affected);
+                            for other in workspace_set.package_ids(DependencyDirection::Forward) {
+                                causes
+                                    .entry(other)
+                                    .or_insert_with(|| edge_cause(package_graph, id, other, edge));
+                            }
+                            return workspace_set;
                         }
                     }
                 }
@@ -718,3 +999,74 @@ impl<'g> ReverseIndex<'g> {
             .expect("all IDs are valid")
     }
 }
+
+/// Returns the cause for a package reached through an edge in the reverse index.
+fn edge_cause(
+    package_graph: &PackageGraph,
+    from: &PackageId,
+    to: &PackageId,
+    edge: ReverseIndexEdge,
+) -> AffectedCause {
+    match edge {
+        ReverseIndexEdge::PackageRule(rule_index) => AffectedCause::PackageRule {
+            rule_index,
+            on_affected: package_name(package_graph, from),
+        },
+        ReverseIndexEdge::CargoBuild => AffectedCause::Dependency {
+            chain: dependency_chain(package_graph, to, from),
+        },
+    }
+}
+
+/// Finds a shortest dependency path from `from` to `to`, returning the names of the packages along
+/// it.
+///
+/// Dev-dependencies are only followed from `from`, matching Cargo.
+fn dependency_chain(package_graph: &PackageGraph, from: &PackageId, to: &PackageId) -> Vec<String> {
+    let mut parents: HashMap<&PackageId, &PackageId> = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(id) = queue.pop_front() {
+        if id == to {
+            break;
+        }
+        let package = package_graph.metadata(id).expect("valid package ID");
+        for link in package.direct_links() {
+            if link.dev_only() && id != from {
+                continue;
+            }
+            let dep_id = link.to().id();
+            if dep_id != from && !parents.contains_key(dep_id) {
+                parents.insert(dep_id, id);
+                queue.push_back(dep_id);
+            }
+        }
+    }
+
+    // Walk back from `to`. If no path was found (which shouldn't happen), fall back to listing
+    // both ends.
+    let mut chain = vec![package_name(package_graph, to)];
+    let mut current = to;
+    while current != from {
+        match parents.get(current) {
+            Some(&parent) => {
+                chain.push(package_name(package_graph, parent));
+                current = parent;
+            }
+            None => {
+                chain.push(package_name(package_graph, from));
+                break;
+            }
+        }
+    }
+    chain.reverse();
+    chain
+}
+
+fn package_name(package_graph: &PackageGraph, id: &PackageId) -> String {
+    package_graph
+        .metadata(id)
+        .expect("valid package ID")
+        .name()
+        .to_owned()
+}
@@ -3,13 +3,15 @@
 
 //! Error types returned by the determinator.
 
-use crate::rules::RuleIndex;
-use std::{error, fmt};
+use crate::rules::{RuleIndex, RuleLocation};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{error, fmt, io};
 
 /// An error that occurred while resolving a set of determinator rules.
 #[derive(Debug)]
 pub struct RulesError {
     rule_index: RuleIndex,
+    location: Option<RuleLocation>,
     kind: RulesErrorKind,
 }
 
@@ -19,6 +21,14 @@ impl RulesError {
         self.rule_index
     }
 
+    /// Returns the file and index within that file of the determinator rule that failed to parse.
+    ///
+    /// Returns `None` if the rule wasn't loaded from a file, e.g. if the rules were parsed with
+    /// [`DeterminatorRules::parse`](crate::rules::DeterminatorRules::parse).
+    pub fn location(&self) -> Option<&RuleLocation> {
+        self.location.as_ref()
+    }
+
     /// Returns the kind of error that occurred.
     pub fn kind(&self) -> &RulesErrorKind {
         &self.kind
@@ -28,29 +38,49 @@ impl RulesError {
     // Internal constructors
     // ---
 
-    pub(crate) fn resolve_ref(rule_index: RuleIndex, err: guppy::Error) -> Self {
+    pub(crate) fn resolve_ref(
+        rule_index: RuleIndex,
+        location: Option<RuleLocation>,
+        err: guppy::Error,
+    ) -> Self {
         Self {
             rule_index,
+            location,
             kind: RulesErrorKind::ResolveRef(err),
         }
     }
 
-    pub(crate) fn glob_parse(rule_index: RuleIndex, err: globset::Error) -> Self {
+    pub(crate) fn glob_parse(
+        rule_index: RuleIndex,
+        location: Option<RuleLocation>,
+        err: globset::Error,
+    ) -> Self {
         let kind = RulesErrorKind::GlobParse {
             glob: err.glob().map(|s| s.to_owned()),
             err: Box::new(err),
         };
-        Self { rule_index, kind }
+        Self {
+            rule_index,
+            location,
+            kind,
+        }
     }
 }
 
 impl fmt::Display for RulesError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "error while resolving determinator rules: {}: {}",
-            self.rule_index, self.kind
-        )
+        match &self.location {
+            Some(location) => write!(
+                f,
+                "error while resolving determinator rules: {} ({}): {}",
+                self.rule_index, location, self.kind
+            ),
+            None => write!(
+                f,
+                "error while resolving determinator rules: {}: {}",
+                self.rule_index, self.kind
+            ),
+        }
     }
 }
 
@@ -93,3 +123,245 @@ impl fmt::Display for RulesErrorKind {
         }
     }
 }
+
+/// An error that occurred while loading determinator rules from files.
+///
+/// Returned by [`DeterminatorRules::read_file`](crate::rules::DeterminatorRules::read_file) and
+/// [`DeterminatorRules::load_workspace`](crate::rules::DeterminatorRules::load_workspace).
+#[derive(Debug)]
+pub struct RulesLoadError {
+    path: Utf8PathBuf,
+    kind: RulesLoadErrorKind,
+}
+
+impl RulesLoadError {
+    /// Returns the path of the file that failed to load.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Returns the kind of error that occurred.
+    pub fn kind(&self) -> &RulesLoadErrorKind {
+        &self.kind
+    }
+
+    // ---
+    // Internal constructors
+    // ---
+
+    pub(crate) fn new(path: impl Into<Utf8PathBuf>, kind: RulesLoadErrorKind) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for RulesLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "error while loading determinator rules from {}: {}",
+            self.path, self.kind
+        )
+    }
+}
+
+impl error::Error for RulesLoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            RulesLoadErrorKind::Read(err) => Some(err),
+            RulesLoadErrorKind::Parse(err) => Some(err),
+            RulesLoadErrorKind::MetadataParse(err) => Some(err),
+            RulesLoadErrorKind::OutsideWorkspace { .. } => None,
+        }
+    }
+}
+
+/// The kind of error that occurred while loading determinator rules from files.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RulesLoadErrorKind {
+    /// An error occurred while reading the file.
+    Read(io::Error),
+
+    /// An error occurred while parsing the file as TOML.
+    Parse(toml::de::Error),
+
+    /// An error occurred while parsing the `workspace.metadata.determinator` section of the
+    /// workspace's `Cargo.toml`.
+    MetadataParse(serde_json::Error),
+
+    /// A rules file, either passed in directly or through `include`, is outside the workspace.
+    OutsideWorkspace {
+        /// The path of the rules file, as specified.
+        rules_path: String,
+    },
+}
+
+impl fmt::Display for RulesLoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulesLoadErrorKind::Read(_) => write!(f, "error while reading file"),
+            RulesLoadErrorKind::Parse(_) => write!(f, "error while parsing rules"),
+            RulesLoadErrorKind::MetadataParse(_) => {
+                write!(f, "error while parsing [workspace.metadata.determinator]")
+            }
+            RulesLoadErrorKind::OutsideWorkspace { rules_path } => {
+                write!(f, "rules file {} is outside the workspace", rules_path)
+            }
+        }
+    }
+}
+
+/// An error that occurred while reading changes from a Git repository.
+///
+/// Returned by [`GitDiff::compute`](crate::GitDiff::compute).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GitError {
+    /// An error occurred while spawning the `git` binary.
+    Spawn(io::Error),
+
+    /// A Git command exited with a non-zero status.
+    CommandFailed {
+        /// The arguments passed to `git`.
+        args: Vec<String>,
+        /// The standard error output of the command.
+        stderr: String,
+    },
+
+    /// The output of a Git command could not be parsed.
+    Parse(String),
+}
+
+impl GitError {
+    // ---
+    // Internal constructors
+    // ---
+
+    pub(crate) fn parse(message: impl Into<String>) -> Self {
+        GitError::Parse(message.into())
+    }
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Spawn(_) => write!(f, "error while spawning git"),
+            GitError::CommandFailed { args, stderr } => {
+                write!(f, "git {} failed: {}", args.join(" "), stderr.trim_end())
+            }
+            GitError::Parse(message) => write!(f, "error while parsing git output: {}", message),
+        }
+    }
+}
+
+impl error::Error for GitError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            GitError::Spawn(err) => Some(err),
+            GitError::CommandFailed { .. } | GitError::Parse(_) => None,
+        }
+    }
+}
+
+/// An error that occurred while scanning source files for file dependencies.
+///
+/// Returned by [`FileDependencies::scan`](crate::FileDependencies::scan).
+#[derive(Debug)]
+pub struct ScanError {
+    path: Utf8PathBuf,
+    err: io::Error,
+}
+
+impl ScanError {
+    /// Returns the path of the source file that could not be read.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    // ---
+    // Internal constructors
+    // ---
+
+    pub(crate) fn new(path: impl Into<Utf8PathBuf>, err: io::Error) -> Self {
+        Self {
+            path: path.into(),
+            err,
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error while reading source file {}", self.path)
+    }
+}
+
+impl error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+/// An error that occurred while reading per-package costs for sharding.
+///
+/// Returned by the parsing methods on [`ShardCosts`](crate::ShardCosts).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ShardCostsError {
+    /// An error occurred while reading the file.
+    Read {
+        /// The path of the file.
+        path: Utf8PathBuf,
+        /// The underlying error.
+        err: io::Error,
+    },
+
+    /// An error occurred while parsing TOML.
+    TomlParse(toml::de::Error),
+
+    /// An error occurred while parsing JSON.
+    JsonParse(serde_json::Error),
+
+    /// A cost was negative, infinite or NaN.
+    InvalidCost {
+        /// The package the cost was specified for, or `None` for the default cost.
+        package: Option<String>,
+        /// The invalid cost.
+        cost: f64,
+    },
+}
+
+impl fmt::Display for ShardCostsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShardCostsError::Read { path, .. } => {
+                write!(f, "error while reading shard costs from {}", path)
+            }
+            ShardCostsError::TomlParse(_) | ShardCostsError::JsonParse(_) => {
+                write!(f, "error while parsing shard costs")
+            }
+            ShardCostsError::InvalidCost {
+                package: Some(package),
+                cost,
+            } => write!(f, "invalid cost {} for package {}", cost, package),
+            ShardCostsError::InvalidCost {
+                package: None,
+                cost,
+            } => write!(f, "invalid default cost {}", cost),
+        }
+    }
+}
+
+impl error::Error for ShardCostsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ShardCostsError::Read { err, .. } => Some(err),
+            ShardCostsError::TomlParse(err) => Some(err),
+            ShardCostsError::JsonParse(err) => Some(err),
+            ShardCostsError::InvalidCost { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{file_deps::FileDependencyKind, rules::RuleIndex};
+use camino::Utf8PathBuf;
+use guppy::{
+    graph::{
+        summaries::{diff::SummaryDiffTag, SummaryId},
+        PackageGraph, PackageMetadata,
+    },
+    PackageId,
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// The reasons packages in a [`DeterminatorSet`](crate::DeterminatorSet) were marked affected.
+///
+/// Every package in the affected set has exactly one cause. If there are several reasons a package
+/// may be affected, one of them is picked: a package that was directly changed is always
+/// attributed to that change.
+///
+/// # Serialization
+///
+/// `AffectedCauses` serializes to a map from workspace package names to causes, sorted by name.
+/// An example of JSON-serialized output:
+///
+/// ```json
+/// {
+///   "fixtures": {
+///     "cause": "path-changed",
+///     "path": "fixtures/src/details.rs",
+///     "matched-by": { "kind": "nearest-package" }
+///   },
+///   "guppy": {
+///     "cause": "dependency",
+///     "chain": ["guppy", "fixtures"]
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AffectedCauses<'g> {
+    graph: &'g PackageGraph,
+    causes: HashMap<&'g PackageId, AffectedCause>,
+}
+
+impl<'g> AffectedCauses<'g> {
+    pub(crate) fn new(
+        graph: &'g PackageGraph,
+        causes: HashMap<&'g PackageId, AffectedCause>,
+    ) -> Self {
+        Self { graph, causes }
+    }
+
+    /// Returns the cause for the given package ID, or `None` if the package wasn't affected.
+    pub fn get(&self, package_id: &PackageId) -> Option<&AffectedCause> {
+        self.causes.get(package_id)
+    }
+
+    /// Returns the number of affected packages.
+    pub fn len(&self) -> usize {
+        self.causes.len()
+    }
+
+    /// Returns true if no packages were affected.
+    pub fn is_empty(&self) -> bool {
+        self.causes.is_empty()
+    }
+
+    /// Iterates over affected packages and their causes, sorted by package name.
+    pub fn iter<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, &'a AffectedCause)> + 'a {
+        let graph = self.graph;
+        let mut sorted: Vec<_> = self
+            .causes
+            .iter()
+            .map(|(id, cause)| (graph.metadata(id).expect("valid package ID"), cause))
+            .collect();
+        sorted.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        sorted.into_iter()
+    }
+}
+
+impl<'g> Serialize for AffectedCauses<'g> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.causes.len()))?;
+        for (package, cause) in self.iter() {
+            map.serialize_entry(package.name(), cause)?;
+        }
+        map.end()
+    }
+}
+
+/// The reason a package was marked affected.
+///
+/// Part of [`AffectedCauses`](AffectedCauses).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "cause", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum AffectedCause {
+    /// A changed path was matched to this package.
+    #[serde(rename_all = "kebab-case")]
+    PathChanged {
+        /// The changed path.
+        path: Utf8PathBuf,
+
+        /// How the path was matched to this package.
+        matched_by: PathMatchedBy,
+    },
+
+    /// A simulated Cargo build of this package had different results between the old and new
+    /// package graphs.
+    #[serde(rename_all = "kebab-case")]
+    SummaryChanged {
+        /// The packages in the build that changed, typically third-party dependencies.
+        ///
+        /// Changes are combined across all simulated builds.
+        changed_packages: Vec<SummaryChange>,
+    },
+
+    /// A package rule marked this package as changed, because another package was affected.
+    #[serde(rename_all = "kebab-case")]
+    PackageRule {
+        /// The index of the package rule.
+        rule_index: RuleIndex,
+
+        /// The name of the affected package that caused the rule to be applied.
+        on_affected: String,
+    },
+
+    /// This package depends on an affected package.
+    #[serde(rename_all = "kebab-case")]
+    Dependency {
+        /// The names of the packages along a dependency path, starting with this package and
+        /// ending with a package that was marked changed directly or through a package rule.
+        ///
+        /// Intermediate packages may be third-party dependencies.
+        chain: Vec<String>,
+    },
+}
+
+/// How a changed path was matched to a package.
+///
+/// Part of [`AffectedCause::PathChanged`](AffectedCause::PathChanged).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum PathMatchedBy {
+    /// A path rule listed this package in `mark-changed`.
+    #[serde(rename_all = "kebab-case")]
+    Rule {
+        /// The index of the path rule.
+        rule_index: RuleIndex,
+    },
+
+    /// This package is the nearest ancestor of the path.
+    NearestPackage,
+
+    /// This package depends on the path through its source code.
+    ///
+    /// See [`FileDependencies`](crate::FileDependencies) for more.
+    #[serde(rename_all = "kebab-case")]
+    FileDependency {
+        /// How the package depends on the path.
+        dependency_kind: FileDependencyKind,
+    },
+
+    /// A path rule with `mark-changed = "all"` caused every package to be marked changed.
+    #[serde(rename_all = "kebab-case")]
+    RuleMatchedAll {
+        /// The index of the path rule.
+        rule_index: RuleIndex,
+    },
+
+    /// The path did not match any rules or packages, so every package was marked changed.
+    NoMatches,
+}
+
+/// A package whose build changed, as part of
+/// [`AffectedCause::SummaryChanged`](AffectedCause::SummaryChanged).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct SummaryChange {
+    /// The package that changed.
+    #[serde(flatten)]
+    pub package: SummaryId,
+
+    /// Whether the package was added, removed, or modified in the build.
+    pub change: SummaryDiffTag,
+}
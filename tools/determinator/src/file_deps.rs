@@ -0,0 +1,633 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::errors::ScanError;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use guppy::{
+    graph::{BuildTargetId, PackageGraph, PackageMetadata},
+    PackageId,
+};
+use std::{collections::BTreeSet, fs, io, iter::Peekable, str::CharIndices};
+
+/// Files that workspace packages depend on, as found by scanning their source code.
+///
+/// The determinator matches changed paths to their nearest package. However, packages may also
+/// depend on files outside their directories, for example shared `.proto` files, through:
+/// * `include_str!` and `include_bytes!`, either with a path relative to the source file or with
+///   `concat!(env!("CARGO_MANIFEST_DIR"), "...")`
+/// * `#[path = "..."]` attributes on modules
+/// * `cargo:rerun-if-changed=` instructions in build scripts, if the path is a string literal
+///
+/// `FileDependencies` finds these files by scanning the source code of every build target in the
+/// workspace, starting from the target's root file and following `mod` declarations. Pass it into
+/// [`Determinator::set_file_dependencies`](crate::Determinator::set_file_dependencies) to have
+/// a change to one of these files mark the packages that depend on it as changed.
+///
+/// Scanning is best-effort: it doesn't evaluate macros or `cfg` attributes, and files that don't
+/// exist are skipped.
+#[derive(Clone, Debug)]
+pub struct FileDependencies<'g> {
+    deps: Vec<FileDependency<'g>>,
+}
+
+impl<'g> FileDependencies<'g> {
+    /// Scans the source code of every build target in the workspace.
+    ///
+    /// The source files are read from the paths in the `PackageGraph`, so the graph should reflect
+    /// the current state of the workspace on disk (typically the *new* graph passed to a
+    /// `Determinator`).
+    ///
+    /// Returns an error if a source file could not be read for a reason other than it not existing.
+    pub fn scan(graph: &'g PackageGraph) -> Result<Self, ScanError> {
+        let workspace = graph.workspace();
+        let workspace_root = workspace.root();
+        let mut deps = BTreeSet::new();
+        for package in workspace.iter() {
+            scan_package(package, workspace_root, &mut deps)?;
+        }
+        Ok(Self {
+            deps: deps
+                .into_iter()
+                .map(|(path, package_id, kind)| FileDependency {
+                    path,
+                    package: graph.metadata(package_id).expect("valid package ID"),
+                    kind,
+                })
+                .collect(),
+        })
+    }
+
+    /// Iterates over the file dependencies found, sorted by path.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a FileDependency<'g>> + 'a {
+        self.deps.iter()
+    }
+
+    /// Returns the file dependencies that cover the given path, relative to the workspace root.
+    ///
+    /// A file dependency covers a path if it is the same path, or a directory containing it.
+    pub fn dependencies_for<'a>(
+        &'a self,
+        path: &'a Utf8Path,
+    ) -> impl Iterator<Item = &'a FileDependency<'g>> + 'a {
+        self.deps
+            .iter()
+            .filter(move |dep| path.starts_with(&dep.path))
+    }
+}
+
+/// A file that a workspace package depends on.
+///
+/// Part of [`FileDependencies`](FileDependencies).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FileDependency<'g> {
+    /// The path of the file, relative to the workspace root.
+    ///
+    /// For `cargo:rerun-if-changed` instructions, this may be a directory.
+    pub path: Utf8PathBuf,
+
+    /// The package that depends on this file.
+    pub package: PackageMetadata<'g>,
+
+    /// How the package depends on this file.
+    pub kind: FileDependencyKind,
+}
+
+/// How a package depends on a file.
+///
+/// Part of [`FileDependency`](FileDependency).
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum FileDependencyKind {
+    /// Through `include_str!`.
+    IncludeStr,
+    /// Through `include_bytes!`.
+    IncludeBytes,
+    /// Through a `#[path = "..."]` attribute on a module.
+    PathAttribute,
+    /// Through a `cargo:rerun-if-changed=` instruction in a build script.
+    RerunIfChanged,
+}
+
+// ---
+// Helper functions
+// ---
+
+type DepSet<'g> = BTreeSet<(Utf8PathBuf, &'g PackageId, FileDependencyKind)>;
+
+fn scan_package<'g>(
+    package: PackageMetadata<'g>,
+    workspace_root: &Utf8Path,
+    deps: &mut DepSet<'g>,
+) -> Result<(), ScanError> {
+    let manifest_dir = package
+        .manifest_path()
+        .parent()
+        .expect("manifest path has a parent");
+    let mut add_dep = |path: &Utf8Path, kind| {
+        let path = normalize(path);
+        // Paths inside the package are already matched to it, and paths outside the workspace can
+        // never be matched.
+        if path.starts_with(manifest_dir) {
+            return;
+        }
+        if let Ok(rel_path) = path.strip_prefix(workspace_root) {
+            deps.insert((rel_path.to_path_buf(), package.id(), kind));
+        }
+    };
+
+    for target in package.build_targets() {
+        // Build targets may share modules, but only build scripts can emit rerun-if-changed.
+        let mut visited = BTreeSet::new();
+        let is_build_script = target.id() == BuildTargetId::BuildScript;
+        let mut stack = vec![(normalize(target.path()), true)];
+        while let Some((file, is_mod_rs)) = stack.pop() {
+            if !visited.insert(file.clone()) {
+                continue;
+            }
+            let source = match fs::read_to_string(&file) {
+                Ok(source) => source,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(ScanError::new(file, err)),
+            };
+            let dir = file.parent().expect("source file has a parent");
+            let found = scan_source(&source);
+
+            for (kind, path) in found.includes {
+                let path = match path {
+                    IncludePath::RelativeToFile(path) => dir.join(path),
+                    IncludePath::RelativeToManifest(path) => {
+                        manifest_dir.join(path.trim_start_matches(&['/', '\\'][..]))
+                    }
+                };
+                add_dep(&path, kind);
+            }
+            if is_build_script {
+                for path in found.rerun_if_changed {
+                    add_dep(&manifest_dir.join(path), FileDependencyKind::RerunIfChanged);
+                }
+            }
+
+            // Modules declared in a non-mod-rs file foo.rs live in the directory foo.
+            let mod_dir = if is_mod_rs {
+                dir.to_path_buf()
+            } else {
+                dir.join(file.file_stem().expect("source file has a stem"))
+            };
+            for module in found.modules {
+                match module.path_attr {
+                    Some(path) => {
+                        let path = dir.join(path);
+                        add_dep(&path, FileDependencyKind::PathAttribute);
+                        // Modules declared in a file loaded through #[path] behave like mod-rs files.
+                        stack.push((normalize(&path), true));
+                    }
+                    None => {
+                        let file = mod_dir.join(format!("{}.rs", module.name));
+                        if file.exists() {
+                            stack.push((file, false));
+                        } else {
+                            stack.push((mod_dir.join(&module.name).join("mod.rs"), true));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Lexically normalizes a path, resolving `.` and `..` components.
+pub(crate) fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_str()),
+        }
+    }
+    normalized
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct ScannedSource {
+    includes: Vec<(FileDependencyKind, IncludePath)>,
+    modules: Vec<ModuleDecl>,
+    rerun_if_changed: Vec<String>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum IncludePath {
+    RelativeToFile(String),
+    RelativeToManifest(String),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct ModuleDecl {
+    name: String,
+    path_attr: Option<String>,
+}
+
+/// Scans a source file for file references and module declarations.
+fn scan_source(source: &str) -> ScannedSource {
+    use Token::*;
+
+    let tokens = tokenize(source);
+    let mut scanned = ScannedSource::default();
+    let mut path_attr = None;
+
+    let mut idx = 0;
+    while idx < tokens.len() {
+        let rest = &tokens[idx..];
+        match rest {
+            // include_str!("path") or include_bytes!("path")
+            [Ident(name), Punct('!'), Punct('('), Str(path), ..]
+                if name == "include_str" || name == "include_bytes" =>
+            {
+                let kind = include_kind(name);
+                scanned
+                    .includes
+                    .push((kind, IncludePath::RelativeToFile(path.clone())));
+                idx += 4;
+            }
+            // include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "path"))
+            [Ident(name), Punct('!'), Punct('('), Ident(concat), Punct('!'), Punct('('), Ident(env), Punct('!'), Punct('('), Str(var), Punct(')'), Punct(','), Str(path), ..]
+                if (name == "include_str" || name == "include_bytes")
+                    && concat == "concat"
+                    && env == "env"
+                    && var == "CARGO_MANIFEST_DIR" =>
+            {
+                let kind = include_kind(name);
+                scanned
+                    .includes
+                    .push((kind, IncludePath::RelativeToManifest(path.clone())));
+                idx += 13;
+            }
+            // #[path = "path"]
+            [Punct('#'), Punct('['), Ident(attr), Punct('='), Str(path), Punct(']'), ..]
+                if attr == "path" =>
+            {
+                path_attr = Some(path.clone());
+                idx += 6;
+            }
+            // mod name;
+            [Ident(keyword), Ident(name), Punct(';'), ..] if keyword == "mod" => {
+                scanned.modules.push(ModuleDecl {
+                    name: name.clone(),
+                    path_attr: path_attr.take(),
+                });
+                idx += 3;
+            }
+            [Str(s), ..] => {
+                for prefix in &["cargo:rerun-if-changed=", "cargo::rerun-if-changed="] {
+                    if let Some(path) = s.strip_prefix(prefix) {
+                        // Skip format strings, since their arguments can't be evaluated.
+                        if !path.is_empty() && !path.contains('{') {
+                            scanned.rerun_if_changed.push(path.to_owned());
+                        }
+                    }
+                }
+                idx += 1;
+            }
+            [Punct(';'), ..] | [Punct('{'), ..] | [Punct('}'), ..] => {
+                // A path attribute only applies to the item right after it.
+                path_attr = None;
+                idx += 1;
+            }
+            _ => idx += 1,
+        }
+    }
+
+    scanned
+}
+
+fn include_kind(name: &str) -> FileDependencyKind {
+    if name == "include_str" {
+        FileDependencyKind::IncludeStr
+    } else {
+        FileDependencyKind::IncludeBytes
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+/// A minimal Rust tokenizer that skips comments, and understands enough about string and
+/// character literals to not be confused by them.
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if next_is(&mut chars, '/') => {
+                // Line comment.
+                for (_, c) in &mut chars {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if next_is(&mut chars, '*') => {
+                // Block comment, possibly nested.
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some((_, '/')) if next_is(&mut chars, '*') => depth += 1,
+                        Some((_, '*')) if next_is(&mut chars, '/') => depth -= 1,
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+            }
+            '"' => tokens.push(Token::Str(read_str(&mut chars))),
+            'r' if matches!(chars.peek(), Some((_, '"')) | Some((_, '#'))) => {
+                // Possibly a raw string.
+                let mut hashes = 0;
+                while next_is(&mut chars, '#') {
+                    hashes += 1;
+                }
+                if next_is(&mut chars, '"') {
+                    tokens.push(Token::Str(read_raw_str(&mut chars, hashes)));
+                } else {
+                    // Raw identifier like r#foo.
+                    tokens.push(Token::Ident(read_ident(source, &mut chars, start)));
+                }
+            }
+            'b' if matches!(chars.peek(), Some((_, '"'))) => {
+                chars.next();
+                tokens.push(Token::Str(read_str(&mut chars)));
+            }
+            '\'' => {
+                // Either a character literal or a lifetime.
+                let mut lookahead = chars.clone();
+                match (lookahead.next(), lookahead.next()) {
+                    (Some((_, '\\')), _) => {
+                        chars.next();
+                        chars.next();
+                        for (_, c) in &mut chars {
+                            if c == '\'' {
+                                break;
+                            }
+                        }
+                    }
+                    (Some(_), Some((_, '\''))) => {
+                        chars.next();
+                        chars.next();
+                    }
+                    _ => {
+                        // Lifetime: skip the identifier.
+                        while matches!(chars.peek(), Some((_, c)) if is_ident_char(*c)) {
+                            chars.next();
+                        }
+                    }
+                }
+            }
+            c if is_ident_char(c) => {
+                tokens.push(Token::Ident(read_ident(source, &mut chars, start)))
+            }
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+
+    tokens
+}
+
+fn next_is(chars: &mut Peekable<CharIndices<'_>>, expected: char) -> bool {
+    if matches!(chars.peek(), Some((_, c)) if *c == expected) {
+        chars.next();
+        true
+    } else {
+        false
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+fn read_ident(source: &str, chars: &mut Peekable<CharIndices<'_>>, start: usize) -> String {
+    let mut end = source.len();
+    while let Some(&(idx, c)) = chars.peek() {
+        if is_ident_char(c) || c == '#' {
+            chars.next();
+        } else {
+            end = idx;
+            break;
+        }
+    }
+    source[start..end].trim_start_matches("r#").to_owned()
+}
+
+fn read_str(chars: &mut Peekable<CharIndices<'_>>) -> String {
+    let mut s = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some((_, 'n')) => s.push('\n'),
+                Some((_, 't')) => s.push('\t'),
+                Some((_, 'r')) => s.push('\r'),
+                Some((_, '0')) => s.push('\0'),
+                Some((_, '\n')) => {
+                    // Line continuation: skip leading whitespace on the next line.
+                    while matches!(chars.peek(), Some((_, c)) if c.is_whitespace()) {
+                        chars.next();
+                    }
+                }
+                // Unicode and hex escapes are rare in paths: keep them as-is.
+                Some((_, c @ 'u')) | Some((_, c @ 'x')) => {
+                    s.push('\\');
+                    s.push(c);
+                }
+                Some((_, c)) => s.push(c),
+                None => break,
+            },
+            c => s.push(c),
+        }
+    }
+    s
+}
+
+fn read_raw_str(chars: &mut Peekable<CharIndices<'_>>, hashes: usize) -> String {
+    let mut s = String::new();
+    while let Some((_, c)) = chars.next() {
+        if c == '"' {
+            let mut lookahead = chars.clone();
+            if (0..hashes).all(|_| matches!(lookahead.next(), Some((_, '#')))) {
+                for _ in 0..hashes {
+                    chars.next();
+                }
+                break;
+            }
+        }
+        s.push(c);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::PathMatch,
+        test_helpers::{manifest, temp_dir, write},
+        AffectedCause, Determinator, PathMatchedBy,
+    };
+    use guppy::{graph::DependencyDirection, MetadataCommand};
+
+    #[test]
+    fn scan() {
+        let source = r##"
+            // include_str!("commented-out.txt")
+            /* include_bytes!("also /* nested */ commented-out.txt") */
+            const A: &str = include_str!("../shared/a.txt");
+            const B: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/../shared/b.bin"));
+            const C: char = '"';
+            const D: &str = r#"include_str!("in-a-raw-string.txt")"#;
+            fn lifetime<'a>(x: &'a str) -> &'a str { x }
+
+            #[path = "../shared/module.rs"]
+            mod shared;
+            mod inline {
+                fn foo() {}
+            }
+            mod regular;
+
+            fn main() {
+                println!("cargo:rerun-if-changed=../proto");
+                println!("cargo:rerun-if-changed={}", "ignored");
+            }
+        "##;
+
+        let expected = ScannedSource {
+            includes: vec![
+                (
+                    FileDependencyKind::IncludeStr,
+                    IncludePath::RelativeToFile("../shared/a.txt".to_owned()),
+                ),
+                (
+                    FileDependencyKind::IncludeBytes,
+                    IncludePath::RelativeToManifest("/../shared/b.bin".to_owned()),
+                ),
+            ],
+            modules: vec![
+                ModuleDecl {
+                    name: "shared".to_owned(),
+                    path_attr: Some("../shared/module.rs".to_owned()),
+                },
+                ModuleDecl {
+                    name: "regular".to_owned(),
+                    path_attr: None,
+                },
+            ],
+            rerun_if_changed: vec!["../proto".to_owned()],
+        };
+        assert_eq!(scan_source(source), expected, "scanned source matches");
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(
+            normalize("/a/b/../c/./d".into()),
+            Utf8PathBuf::from("/a/c/d")
+        );
+        assert_eq!(normalize("a/../../b".into()), Utf8PathBuf::from("b"));
+    }
+
+    #[test]
+    fn temp_workspace() {
+        let (_dir, root) = temp_dir();
+        write(
+            &root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\", \"b\", \"c\"]\n",
+        );
+        write(&root, "proto/x.proto", "");
+        write(&root, "shared/helpers.rs", "");
+        write(&root, "a/Cargo.toml", &manifest("a"));
+        write(
+            &root,
+            "a/src/lib.rs",
+            "mod inner;\n#[path = \"../../shared/helpers.rs\"]\nmod helpers;\n",
+        );
+        write(
+            &root,
+            "a/src/inner.rs",
+            "mod nested;\nconst _: &str = include_str!(\"../../proto/x.proto\");\n",
+        );
+        write(
+            &root,
+            "a/src/inner/nested.rs",
+            "const _: &[u8] = include_bytes!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/README.md\"));\n",
+        );
+        write(&root, "b/Cargo.toml", &manifest("b"));
+        write(&root, "b/src/lib.rs", "");
+        write(
+            &root,
+            "b/build.rs",
+            "fn main() { println!(\"cargo:rerun-if-changed=../proto\"); }\n",
+        );
+        write(&root, "c/Cargo.toml", &manifest("c"));
+        write(&root, "c/src/lib.rs", "");
+
+        let graph = MetadataCommand::new()
+            .current_dir(&root)
+            .build_graph()
+            .expect("package graph built");
+        let file_deps = FileDependencies::scan(&graph).expect("scan succeeded");
+        let actual: Vec<_> = file_deps
+            .iter()
+            .map(|dep| (dep.path.as_str(), dep.package.name(), dep.kind))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                ("proto", "b", FileDependencyKind::RerunIfChanged),
+                ("proto/x.proto", "a", FileDependencyKind::IncludeStr),
+                ("shared/helpers.rs", "a", FileDependencyKind::PathAttribute),
+            ],
+            "file dependencies match"
+        );
+
+        // A change to the shared proto file marks its consumers changed, rather than everything.
+        let mut determinator = Determinator::new(&graph, &graph);
+        determinator.set_file_dependencies(file_deps);
+        assert_eq!(
+            determinator.match_path("proto/x.proto", |_| {}),
+            PathMatch::FileDependencyMatched,
+        );
+        determinator.add_changed_paths(vec!["proto/x.proto"]);
+        let determinator_set = determinator.compute();
+        let changed: Vec<_> = determinator_set
+            .path_changed_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect();
+        assert_eq!(changed.len(), 2, "a and b changed");
+        assert!(changed.contains(&"a") && changed.contains(&"b"));
+        let cause = determinator_set
+            .affected_causes
+            .get(graph.workspace().member_by_path("b").unwrap().id())
+            .expect("b is affected");
+        assert_eq!(
+            cause,
+            &AffectedCause::PathChanged {
+                path: "proto/x.proto".into(),
+                matched_by: PathMatchedBy::FileDependency {
+                    dependency_kind: FileDependencyKind::RerunIfChanged,
+                },
+            }
+        );
+    }
+}
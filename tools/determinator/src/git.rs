@@ -0,0 +1,421 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::errors::GitError;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::process::Command;
+
+/// Reads changed paths directly from a local Git repository.
+///
+/// `GitDiff` computes the [merge base](https://git-scm.com/docs/git-merge-base) of `HEAD` and an
+/// upstream revision, then collects the paths changed since that merge base. This handles the
+/// edge cases described in the documentation for [`Utf8Paths0`](crate::Utf8Paths0):
+/// * uncommitted changes, both staged and unstaged, are included by default
+/// * untracked files can optionally be included
+/// * renames are detected, and both the old and new paths are reported
+///
+/// `GitDiff` runs the `git` binary found in `PATH`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use determinator::{Determinator, GitDiff};
+/// use guppy::MetadataCommand;
+///
+/// let old = MetadataCommand::new().build_graph().unwrap();
+/// let new = MetadataCommand::new().build_graph().unwrap();
+///
+/// // Compare the working directory against the merge base with origin/main.
+/// let changes = GitDiff::new(new.workspace().root(), "origin/main")
+///     .compute()
+///     .unwrap();
+///
+/// let mut determinator = Determinator::new(&old, &new);
+/// determinator.add_changed_paths(changes.paths());
+/// let determinator_set = determinator.compute();
+/// ```
+#[derive(Clone, Debug)]
+pub struct GitDiff<'a> {
+    root: &'a Utf8Path,
+    upstream: &'a str,
+    include_uncommitted: bool,
+    include_untracked: bool,
+}
+
+impl<'a> GitDiff<'a> {
+    /// Creates a new `GitDiff` for the repository containing `root`, comparing against the merge
+    /// base of `HEAD` and the `upstream` revision.
+    ///
+    /// Changed paths are returned relative to `root`, which is typically the workspace root.
+    /// Changes outside `root` are not reported.
+    pub fn new(root: &'a Utf8Path, upstream: &'a str) -> Self {
+        Self {
+            root,
+            upstream,
+            include_uncommitted: true,
+            include_untracked: false,
+        }
+    }
+
+    /// If set to true, includes changes in the working directory and the index that haven't
+    /// been committed yet. If set to false, only `HEAD` is compared against the merge base.
+    ///
+    /// The default is true.
+    pub fn set_include_uncommitted(&mut self, include_uncommitted: bool) -> &mut Self {
+        self.include_uncommitted = include_uncommitted;
+        self
+    }
+
+    /// If set to true, includes untracked files in the working directory. Ignored files are
+    /// never included.
+    ///
+    /// This has no effect if uncommitted changes aren't included. The default is false: for a
+    /// discussion of the tradeoffs involved, see the documentation for
+    /// [`Determinator::add_changed_paths`](crate::Determinator::add_changed_paths).
+    pub fn set_include_untracked(&mut self, include_untracked: bool) -> &mut Self {
+        self.include_untracked = include_untracked;
+        self
+    }
+
+    /// Runs Git and collects the list of changes.
+    pub fn compute(&self) -> Result<GitChanges, GitError> {
+        let merge_base = self.run(&["merge-base", self.upstream, "HEAD"])?;
+        let merge_base = String::from_utf8(merge_base)
+            .map_err(|_| GitError::parse("merge base was not valid UTF-8"))?
+            .trim()
+            .to_owned();
+
+        let mut args = vec![
+            "diff",
+            "-z",
+            "--name-status",
+            "--find-renames",
+            "--no-ext-diff",
+            "--relative",
+            merge_base.as_str(),
+        ];
+        if !self.include_uncommitted {
+            args.push("HEAD");
+        }
+        let mut changes = parse_name_status(&self.run(&args)?)?;
+
+        if self.include_uncommitted && self.include_untracked {
+            let untracked = self.run(&["ls-files", "-z", "--others", "--exclude-standard"])?;
+            for path in split_paths(&untracked)? {
+                changes.push(GitChange {
+                    path,
+                    kind: GitChangeKind::Untracked,
+                });
+            }
+        }
+
+        Ok(GitChanges {
+            merge_base,
+            changes,
+        })
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn run(&self, args: &[&str]) -> Result<Vec<u8>, GitError> {
+        let output = Command::new("git")
+            .current_dir(self.root)
+            .args(args)
+            .output()
+            .map_err(GitError::Spawn)?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(GitError::CommandFailed {
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            })
+        }
+    }
+}
+
+/// The list of changes computed by a [`GitDiff`](GitDiff).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GitChanges {
+    merge_base: String,
+    changes: Vec<GitChange>,
+}
+
+impl GitChanges {
+    /// Returns the commit hash of the merge base that changes were computed against.
+    pub fn merge_base(&self) -> &str {
+        &self.merge_base
+    }
+
+    /// Returns the list of changes, in the order Git reported them.
+    pub fn changes(&self) -> &[GitChange] {
+        &self.changes
+    }
+
+    /// Iterates over all changed paths, suitable for passing into
+    /// [`Determinator::add_changed_paths`](crate::Determinator::add_changed_paths).
+    ///
+    /// For renamed files, both the old and the new paths are returned, so that the packages owning
+    /// either of them are marked changed.
+    pub fn paths<'a>(&'a self) -> impl Iterator<Item = &'a Utf8Path> + 'a {
+        self.changes.iter().flat_map(|change| {
+            let old_path = match &change.kind {
+                GitChangeKind::Renamed { old_path } => Some(old_path.as_path()),
+                _ => None,
+            };
+            old_path.into_iter().chain(Some(change.path.as_path()))
+        })
+    }
+}
+
+/// A single change reported by Git.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct GitChange {
+    /// The path that changed. For renames, this is the new path.
+    pub path: Utf8PathBuf,
+
+    /// The kind of change.
+    pub kind: GitChangeKind,
+}
+
+/// The kind of change a [`GitChange`](GitChange) represents.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum GitChangeKind {
+    /// The path was added.
+    Added,
+
+    /// The path was modified. This includes changes to the file type or mode, and unmerged paths.
+    Modified,
+
+    /// The path was deleted.
+    Deleted,
+
+    /// The path was renamed, possibly with changes to its contents.
+    Renamed {
+        /// The path before the rename.
+        old_path: Utf8PathBuf,
+    },
+
+    /// The path is not tracked by Git.
+    Untracked,
+}
+
+/// Parses the output of `git diff -z --name-status`.
+fn parse_name_status(buf: &[u8]) -> Result<Vec<GitChange>, GitError> {
+    let mut fields = split_paths(buf)?.into_iter();
+    let mut changes = vec![];
+    while let Some(status) = fields.next() {
+        let mut next_path = || {
+            fields
+                .next()
+                .ok_or_else(|| GitError::parse(format!("missing path for status '{}'", status)))
+        };
+        let (path, kind) = match status.as_str().chars().next() {
+            Some('A') => (next_path()?, GitChangeKind::Added),
+            Some('M') | Some('T') | Some('U') => (next_path()?, GitChangeKind::Modified),
+            Some('D') => (next_path()?, GitChangeKind::Deleted),
+            Some('R') => {
+                let old_path = next_path()?;
+                (next_path()?, GitChangeKind::Renamed { old_path })
+            }
+            // Copies aren't detected unless asked for, but treat them as additions just in case.
+            Some('C') => {
+                next_path()?;
+                (next_path()?, GitChangeKind::Added)
+            }
+            _ => return Err(GitError::parse(format!("unknown status '{}'", status))),
+        };
+        changes.push(GitChange { path, kind });
+    }
+    Ok(changes)
+}
+
+/// Splits a null-separated buffer into paths, converting `/` to the platform separator.
+fn split_paths(buf: &[u8]) -> Result<Vec<Utf8PathBuf>, GitError> {
+    let buf =
+        std::str::from_utf8(buf).map_err(|_| GitError::parse("output was not valid UTF-8"))?;
+    Ok(buf
+        .split_terminator('\0')
+        .map(|path| {
+            if std::path::MAIN_SEPARATOR == '\\' {
+                Utf8PathBuf::from(path.replace('/', "\\"))
+            } else {
+                Utf8PathBuf::from(path)
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{temp_dir, write};
+
+    #[test]
+    fn parse() {
+        let changes =
+            parse_name_status(b"M\0a/b.rs\0A\0c.rs\0D\0d/e.rs\0R087\0f/g.rs\0h/g.rs\0T\0i\0")
+                .expect("output parsed");
+        let expected = vec![
+            change("a/b.rs", GitChangeKind::Modified),
+            change("c.rs", GitChangeKind::Added),
+            change("d/e.rs", GitChangeKind::Deleted),
+            change(
+                "h/g.rs",
+                GitChangeKind::Renamed {
+                    old_path: "f/g.rs".into(),
+                },
+            ),
+            change("i", GitChangeKind::Modified),
+        ];
+        assert_eq!(changes, expected, "changes match");
+
+        parse_name_status(b"R100\0f/g.rs\0").expect_err("missing new path");
+        parse_name_status(b"X\0a\0").expect_err("unknown status");
+    }
+
+    #[test]
+    fn temp_repo() {
+        let (_dir, root) = temp_dir();
+        git(&root, &["init", "-q"]);
+        write(&root, "a/lib.rs", "a");
+        write(&root, "b/lib.rs", "b");
+        write(
+            &root,
+            "b/rename.rs",
+            "the contents of this file are long enough for renames\n",
+        );
+        write(&root, "c/lib.rs", "c");
+        commit(&root, "initial");
+        git(&root, &["branch", "upstream"]);
+
+        // Committed changes since the merge base.
+        write(&root, "a/lib.rs", "a2");
+        git(&root, &["mv", "b/rename.rs", "c/rename.rs"]);
+        git(&root, &["rm", "-q", "c/lib.rs"]);
+        commit(&root, "second");
+
+        // A commit on upstream that isn't on HEAD should be ignored.
+        git(&root, &["checkout", "-q", "upstream"]);
+        write(&root, "d/lib.rs", "d");
+        commit(&root, "upstream");
+        git(&root, &["checkout", "-q", "-"]);
+
+        // Uncommitted and untracked changes.
+        write(&root, "b/lib.rs", "b2");
+        write(&root, "e/lib.rs", "e");
+
+        let rename = change(
+            "c/rename.rs",
+            GitChangeKind::Renamed {
+                old_path: "b/rename.rs".into(),
+            },
+        );
+
+        let changes = GitDiff::new(&root, "upstream")
+            .set_include_uncommitted(false)
+            .compute()
+            .expect("changes computed");
+        assert_eq!(
+            sorted(changes.changes()),
+            vec![
+                change("a/lib.rs", GitChangeKind::Modified),
+                change("c/lib.rs", GitChangeKind::Deleted),
+                rename.clone(),
+            ],
+            "committed changes match"
+        );
+        let paths: Vec<_> = changes.paths().map(|path| path.as_str()).collect();
+        assert!(
+            paths.contains(&"b/rename.rs") && paths.contains(&"c/rename.rs"),
+            "both sides of the rename are returned"
+        );
+
+        let changes = GitDiff::new(&root, "upstream")
+            .compute()
+            .expect("changes computed");
+        assert_eq!(
+            sorted(changes.changes()),
+            vec![
+                change("a/lib.rs", GitChangeKind::Modified),
+                change("b/lib.rs", GitChangeKind::Modified),
+                change("c/lib.rs", GitChangeKind::Deleted),
+                rename.clone(),
+            ],
+            "uncommitted changes match"
+        );
+
+        let changes = GitDiff::new(&root, "upstream")
+            .set_include_untracked(true)
+            .compute()
+            .expect("changes computed");
+        assert_eq!(
+            sorted(changes.changes()),
+            vec![
+                change("a/lib.rs", GitChangeKind::Modified),
+                change("b/lib.rs", GitChangeKind::Modified),
+                change("c/lib.rs", GitChangeKind::Deleted),
+                rename,
+                change("e/lib.rs", GitChangeKind::Untracked),
+            ],
+            "untracked changes match"
+        );
+
+        // Paths are relative to the root passed in.
+        let changes = GitDiff::new(&root.join("a"), "upstream")
+            .compute()
+            .expect("changes computed");
+        assert_eq!(
+            changes.changes(),
+            &[change("lib.rs", GitChangeKind::Modified)],
+            "paths are relative to subdirectory"
+        );
+
+        GitDiff::new(&root, "nonexistent")
+            .compute()
+            .expect_err("unknown revision");
+    }
+
+    fn change(path: &str, kind: GitChangeKind) -> GitChange {
+        GitChange {
+            path: path.into(),
+            kind,
+        }
+    }
+
+    fn sorted(changes: &[GitChange]) -> Vec<GitChange> {
+        let mut changes = changes.to_vec();
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes
+    }
+
+    fn commit(root: &Utf8Path, message: &str) {
+        git(root, &["add", "-A"]);
+        git(
+            root,
+            &[
+                "-c",
+                "user.name=Test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                message,
+            ],
+        );
+    }
+
+    fn git(root: &Utf8Path, args: &[&str]) {
+        let status = Command::new("git")
+            .current_dir(root)
+            .args(args)
+            .status()
+            .expect("git ran");
+        assert!(status.success(), "git {:?} succeeded", args);
+    }
+}
@@ -57,7 +57,9 @@
 //! * The build or test environment.
 //!
 //! The determinator gathers data from several sources, and processes it through
-//! [guppy](https://docs.rs/guppy), to figure out which packages need to be re-tested.
+//! [guppy](https://docs.rs/guppy), to figure out which packages need to be re-tested. The reason
+//! each package was affected is recorded in
+//! [`DeterminatorSet::affected_causes`](crate::DeterminatorSet::affected_causes).
 //!
 //! ## File changes
 //!
@@ -67,9 +69,14 @@
 //! * If the file is outside a package, the determinator assumes that everything needs to be
 //!   rebuilt.
 //!
+//! With [`Determinator::compute_targets`](crate::Determinator::compute_targets), files that
+//! belong to a test, benchmark, example or binary target only mark that target as changed.
+//!
 //! The list of file changes can be obtained from a source control system such as Git. This crate
 //! provides a helper which simplifies the process of enumerating file lists while handling some
 //! gnarly edge cases. For more information, see the documentation for [`Paths0`](crate::Paths0).
+//! For Git repositories, [`GitDiff`](crate::GitDiff) can also read changes directly, including
+//! uncommitted changes and renames.
 //!
 //! These simple rules may need to be customized for particular scenarios (e.g. to ignore certain
 //! files, or mark a package changed if a file outside of it changes). For those situations, the
@@ -98,6 +105,11 @@
 //! If any of these simulated builds indicates that a workspace package has had any dependency
 //! changes, then it is marked changed.
 //!
+//! If CI runs separate jobs for several target platforms,
+//! [`Determinator::compute_platforms`](crate::Determinator::compute_platforms) runs these
+//! simulations once per platform and returns a separate result for each one. A change to a
+//! dependency that is only used on Windows, for example, only affects the Windows result.
+//!
 //! ## Environment changes
 //!
 //! The *environment* of a build or test run is anything not part of the source code that may
@@ -129,6 +141,7 @@
 //! * Some files should be ignored.
 //! * If some files or packages change, a full test run may be necessary.
 //! * *Virtual dependencies* that Cargo isn't aware of may need to be inserted.
+//! * Some packages, such as documentation-only ones, should never be considered affected.
 //!
 //! For these situations, the determinator allows for custom *rules* to be specified. The
 //! determinator also ships with
@@ -137,6 +150,23 @@
 //!
 //! For more about custom rules, see the documentation for the [`rules` module](crate::rules).
 //!
+//! # Splitting test runs across machines
+//!
+//! Once the affected set is computed, the tests for it can be split across several CI machines with
+//! a [`Sharder`](crate::Sharder). Shards are balanced by per-package costs, such as historical test
+//! durations, read from a TOML or JSON file into [`ShardCosts`](crate::ShardCosts). Packages that
+//! should be tested together can be listed as coupled, and sharding is deterministic so that
+//! reruns pick the same shards.
+//!
+//! # Caching build results
+//!
+//! Simulating Cargo builds is usually the slowest part of a determinator run. The results for a
+//! package graph can be saved as a [`BuildCache`](crate::BuildCache) and passed back in with
+//! [`Determinator::add_build_cache`](crate::Determinator::add_build_cache): for example, a
+//! post-merge job can save the cache for the main branch for every pull request to reuse. Caches are
+//! keyed by a hash of the package graph and by the Cargo options used, and are ignored if either
+//! doesn't match.
+//!
 //! # Limitations
 //!
 //! While the determinator can bring significant benefits to CI and local workflows, its model is
@@ -154,6 +184,13 @@
 //! output `rerun-if-changed` or `rerun-if-env-changed` instructions in build scripts. These
 //! instructions must be duplicated through custom rules.
 //!
+//! [`FileDependencies`](crate::FileDependencies) can recover some of this information by scanning
+//! workspace source code for `include_str!`, `include_bytes!`, `#[path]` attributes and
+//! `cargo:rerun-if-changed=` string literals in build scripts. Pass it into
+//! `Determinator::set_file_dependencies` to have changes to, say, a shared `.proto` file mark the
+//! packages that depend on it as changed. Since this is a best-effort textual scan, paths that are
+//! computed at build time must still be duplicated through custom rules.
+//!
 //! **The determinator doesn't track the [`include` and `exclude` fields in `Cargo.toml`][include].**
 //! This is because the determinator's view of what's changed doesn't always align with these fields.
 //! For example, packages typically include `README` files, but the determinator has a default rule
@@ -246,9 +283,20 @@
 //! This determinator is inspired by, and shares its name with, the target determinator used in
 //! Facebook's main source repository.
 
+mod build_cache;
 mod determinator;
 pub mod errors;
+mod explain;
+mod file_deps;
+mod git;
 mod paths0;
 pub mod rules;
+mod shards;
+mod targets;
+#[cfg(test)]
+mod test_helpers;
 
-pub use crate::{determinator::*, paths0::*};
+pub use crate::{
+    build_cache::*, determinator::*, explain::*, file_deps::*, git::*, paths0::*, shards::*,
+    targets::*,
+};
@@ -14,16 +14,48 @@
 //! * build everything if certain files or packages have changed
 //! * add *virtual dependencies* that Cargo may not know about: if a package changes, also consider
 //!   certain other packages as changed.
+//! * never consider certain packages, such as documentation-only ones, as affected.
 //!
 //! These custom behaviors can be specified through *determinator rules*.
 //!
 //! There are two sorts of determinator rules:
 //! * **Path rules** match on changed paths, and are applied **in order**, before regular matches.
 //! * **Package rules** match based on changed packages, and are applied as required until
-//!   exhausted (i.e. a fixpoint is reached).
+//!   exhausted (i.e. a fixpoint is reached). Package rules that remove packages from the affected
+//!   set take precedence over all other rules.
 //!
 //! Determinator rules are a configuration file format and can be read from a TOML file.
 //!
+//! # Loading rules from files
+//!
+//! Rules can be parsed from a single TOML string with [`DeterminatorRules::parse`]. For larger
+//! workspaces, [`DeterminatorRules::load_workspace`] combines rules from several places:
+//! * `determinator.toml` files anywhere in the workspace, other than in hidden directories and the
+//!   target directory.
+//! * The `[workspace.metadata.determinator]` section of the workspace's `Cargo.toml`.
+//!
+//! **Globs are relative to the directory containing the file they're in.** For example, a rule in
+//! `protos/determinator.toml` with `globs = ["**/*.proto"]` matches
+//! `protos/foo/bar.proto`, but not `other/baz.proto`. The same applies to `on-affected-paths` in
+//! package rules.
+//!
+//! Rules from deeper directories are applied first, so more specific rules take precedence. Rules
+//! in `Cargo.toml` are applied after all `determinator.toml` files, and default rules are applied
+//! last. `use-default-rules = false` in any of these files disables the default rules.
+//!
+//! Other files can be included explicitly, with paths relative to the including file:
+//!
+//! ```toml
+//! include = ["../common/determinator-rules.toml"]
+//! ```
+//!
+//! Included rules are applied right after the rules in the including file. Each file is loaded at
+//! most once, even if it is included several times. [`DeterminatorRules::read_file`] can also be
+//! used to load a single file along with its includes.
+//!
+//! Errors while resolving loaded rules point to the file and rule that failed, through
+//! [`RulesError::location`](crate::errors::RulesError::location).
+//!
 //! # Default path rules
 //!
 //! The determinator ships with a set of default path rules for common files such as `.gitignore`
@@ -103,13 +135,57 @@
 //! on-affected = ["guppy-benchmarks"]
 //! mark-changed = "all"
 //! ```
+//!
+//! Packages can also be matched by name globs, by globs on their paths within the workspace, or by
+//! keys in their `package.metadata` tables. A rule applies if any of these match:
+//!
+//! ```toml
+//! [[package-rule]]
+//! on-affected-globs = ["*-macros"]
+//! on-affected-paths = ["codegen/**"]
+//! on-affected-metadata = ["my-tool.codegen"]
+//! mark-changed = ["codegen-tests"]
+//! ```
+//!
+//! To never consider documentation-only packages affected, and to not test anything that depends
+//! on them when they change:
+//!
+//! ```toml
+//! [[package-rule]]
+//! on-affected-metadata = ["my-tool.docs-only"]
+//! remove-affected = true
+//! ```
+//!
+//! # How package rules are applied
+//!
+//! Package rules with `mark-changed` are applied until a fixpoint is reached: if a package marked
+//! changed by a rule matches another rule, that rule is applied as well. Rules can be applied in
+//! any order and cycles between rules are fine, since packages are only ever added.
+//!
+//! Packages matched by rules with `remove-affected = true` are excluded before this process
+//! starts, and always take precedence over every other rule:
+//! * They are never in the affected set, even if they were changed directly, marked changed by a
+//!   path or package rule, or if every package was marked changed.
+//! * Changes to them don't affect their dependents, and don't trigger other package rules.
+//!
+//! Packages depending on a removed package can still be affected through other changes. Removed
+//! packages are still reported in
+//! [`DeterminatorSet::path_changed_set`](crate::DeterminatorSet::path_changed_set) and
+//! [`summary_changed_set`](crate::DeterminatorSet::summary_changed_set).
 
-use crate::errors::RulesError;
+use crate::{
+    errors::{RulesError, RulesLoadError, RulesLoadErrorKind},
+    file_deps::normalize,
+};
+use camino::{Utf8Path, Utf8PathBuf};
 use globset::{Glob, GlobSet, GlobSetBuilder};
-use guppy::graph::{PackageGraph, PackageMetadata, PackageSet, Workspace};
+use guppy::{
+    graph::{DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace},
+    JsonValue,
+};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{collections::HashSet, fmt, fs};
 
 /// Rules for the target determinator.
 ///
@@ -125,6 +201,14 @@ pub struct DeterminatorRules {
     #[serde(default = "default_true", rename = "use-default-rules")]
     use_default_rules: bool,
 
+    /// Other rules files to include, relative to the directory containing this file.
+    ///
+    /// Includes are followed by [`read_file`](Self::read_file) and
+    /// [`load_workspace`](Self::load_workspace). They are ignored by
+    /// [`Determinator::set_rules`](crate::Determinator::set_rules).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
     /// A list of rules that each changed file path is matched against.
     #[serde(default, rename = "path-rule")]
     pub path_rules: Vec<PathRule>,
@@ -135,6 +219,10 @@ pub struct DeterminatorRules {
     /// packages here act as "virtual dependencies" for the determinator.
     #[serde(default, rename = "package-rule")]
     pub package_rules: Vec<PackageRule>,
+
+    /// The files that custom rules were loaded from.
+    #[serde(skip)]
+    locations: RuleLocations,
 }
 
 /// The `Default` impl is the set of custom rules used by the determinator if
@@ -145,8 +233,10 @@ impl Default for DeterminatorRules {
     fn default() -> Self {
         Self {
             use_default_rules: true,
+            include: vec![],
             path_rules: vec![],
             package_rules: vec![],
+            locations: RuleLocations::default(),
         }
     }
 }
@@ -165,11 +255,85 @@ macro_rules! doc_comment {
 }
 
 impl DeterminatorRules {
+    /// The name of per-directory rules files loaded by [`load_workspace`](Self::load_workspace).
+    pub const FILE_NAME: &'static str = "determinator.toml";
+
     /// Deserializes determinator rules from the given TOML string.
+    ///
+    /// Globs are relative to the workspace root, and `include` is not followed.
     pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
         toml::from_str(s)
     }
 
+    /// Reads determinator rules from the given TOML file, following `include` directives.
+    ///
+    /// `path` is relative to `workspace_root`. Globs in this file and in included files are
+    /// relative to the directory containing each file.
+    ///
+    /// For more, see [the module-level documentation](index.html#loading-rules-from-files).
+    pub fn read_file(
+        workspace_root: &Utf8Path,
+        path: impl AsRef<Utf8Path>,
+    ) -> Result<Self, RulesLoadError> {
+        let mut loader = RulesLoader::new(workspace_root);
+        let path = loader.relative_path(path.as_ref())?;
+        loader.load_file(&path)?;
+        Ok(loader.rules)
+    }
+
+    /// Loads determinator rules for a workspace from `determinator.toml` files and the
+    /// `[workspace.metadata.determinator]` section of the workspace's `Cargo.toml`.
+    ///
+    /// For more, see [the module-level documentation](index.html#loading-rules-from-files).
+    pub fn load_workspace(graph: &PackageGraph) -> Result<Self, RulesLoadError> {
+        let workspace = graph.workspace();
+        let workspace_root = workspace.root();
+        let mut loader = RulesLoader::new(workspace_root);
+
+        let mut files = vec![];
+        find_rules_files(
+            workspace_root,
+            Utf8Path::new(""),
+            workspace.target_directory(),
+            &mut files,
+        )?;
+        // Deeper directories come first.
+        files.sort_by(|a, b| {
+            let a_depth = a.components().count();
+            let b_depth = b.components().count();
+            b_depth.cmp(&a_depth).then_with(|| a.cmp(b))
+        });
+        for file in files {
+            loader.load_file(&file)?;
+        }
+
+        if let Some(table) = workspace.metadata_table().get("determinator") {
+            let manifest_path = Utf8Path::new("Cargo.toml");
+            let rules = DeterminatorRules::deserialize(table).map_err(|err| {
+                RulesLoadError::new(
+                    workspace_root.join(manifest_path),
+                    RulesLoadErrorKind::MetadataParse(err),
+                )
+            })?;
+            loader.add_rules(rules, manifest_path)?;
+        }
+
+        Ok(loader.rules)
+    }
+
+    /// Returns the file that the custom rule at the given index was loaded from.
+    ///
+    /// Returns `None` for default rules, and for rules that weren't loaded through
+    /// [`read_file`](Self::read_file) or [`load_workspace`](Self::load_workspace).
+    pub fn location(&self, rule_index: RuleIndex) -> Option<&RuleLocation> {
+        let location = match rule_index {
+            RuleIndex::CustomPath(index) => self.locations.path_rules.get(index),
+            RuleIndex::DefaultPath(_) => None,
+            RuleIndex::Package(index) => self.locations.package_rules.get(index),
+        };
+        location.and_then(|location| location.as_ref())
+    }
+
     doc_comment! {
         concat!("\
 Contains the default rules in a TOML file format.
@@ -302,7 +466,12 @@ impl Default for DeterminatorPostRule {
 /// Package-based rules for the determinator.
 ///
 /// These rules customize the behavior of the determinator based on affected packages, and can be
-/// used to insert "virtual dependencies" that Cargo may not be aware of.
+/// used to insert "virtual dependencies" that Cargo may not be aware of, or to remove packages
+/// from the affected set.
+///
+/// A package rule matches a workspace package if any of `on-affected`, `on-affected-globs`,
+/// `on-affected-paths` or `on-affected-metadata` match it. At least one of these must be
+/// specified.
 ///
 /// # Examples
 ///
@@ -314,12 +483,13 @@ impl Default for DeterminatorPostRule {
 ///
 /// For more examples, see [the module-level documentation](index.html).
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
-#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+#[serde(
+    try_from = "package_rule_impl::PackageRuleSerde",
+    into = "package_rule_impl::PackageRuleSerde"
+)]
 pub struct PackageRule {
     /// The package names to match against.
     ///
-    /// If any of the packages in this list is affected, the given packages will be marked changed.
-    ///
     /// # Examples
     ///
     /// In TOML format, specified as an array of workspace package names:
@@ -329,11 +499,65 @@ pub struct PackageRule {
     /// ```
     pub on_affected: Vec<String>,
 
-    /// The set of packages to mark as changed.
+    /// Globs to match package names against.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, specified as an array of [`globset`](https://docs.rs/globset/0.4) globs:
+    ///
+    /// ```toml
+    /// on-affected-globs = ["guppy-*"]
+    /// ```
+    pub on_affected_globs: Vec<String>,
+
+    /// Globs to match the paths of workspace packages against.
+    ///
+    /// Paths are relative to the workspace root, or to the directory containing the rules file if
+    /// rules were loaded from a file.
     ///
     /// # Examples
     ///
-    /// In TOML format, this may be the string `"all"`:
+    /// In TOML format, specified as an array of [`globset`](https://docs.rs/globset/0.4) globs:
+    ///
+    /// ```toml
+    /// on-affected-paths = ["tools/**"]
+    /// ```
+    pub on_affected_paths: Vec<String>,
+
+    /// Keys to look for in each package's `package.metadata` table.
+    ///
+    /// Nested keys are separated by `.`. A package matches if the key is present and isn't `false`
+    /// or `null`.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, specified as an array of keys:
+    ///
+    /// ```toml
+    /// on-affected-metadata = ["my-tool.docs-only"]
+    /// ```
+    ///
+    /// This matches packages with this in their `Cargo.toml`:
+    ///
+    /// ```toml
+    /// [package.metadata.my-tool]
+    /// docs-only = true
+    /// ```
+    pub on_affected_metadata: Vec<String>,
+
+    /// What to do with matched packages.
+    pub action: PackageRuleAction,
+}
+
+/// What a [`PackageRule`] does with the packages it matches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PackageRuleAction {
+    /// If any of the matched packages is affected, mark the given packages as changed.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, this is specified through `mark-changed`, which may be the string `"all"`:
     ///
     /// ```toml
     /// mark-changed = "all"
@@ -344,8 +568,19 @@ pub struct PackageRule {
     /// ```toml
     /// mark-changed = ["guppy", "determinator"]
     /// ```
-    #[serde(with = "mark_changed_impl")]
-    pub mark_changed: DeterminatorMarkChanged,
+    MarkChanged(DeterminatorMarkChanged),
+
+    /// Remove the matched packages from the affected set. Removed packages don't cause any other
+    /// packages to be affected.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, specified as:
+    ///
+    /// ```toml
+    /// remove-affected = true
+    /// ```
+    RemoveAffected,
 }
 
 /// The set of packages to mark as changed.
@@ -388,6 +623,7 @@ pub enum DeterminatorMarkChanged {
 ///
 /// Returned by `Determinator::match_path`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum PathMatch {
     /// The path matched a rule, causing everything to be rebuilt.
     RuleMatchedAll,
@@ -397,6 +633,11 @@ pub enum PathMatch {
     RuleMatched(RuleIndex),
     /// The path was matched to a package through inspecting the parent directories of each path.
     AncestorMatched,
+    /// The path wasn't in a package, but packages depend on it through their source code.
+    ///
+    /// This is only returned if file dependencies were configured through
+    /// `Determinator::set_file_dependencies`.
+    FileDependencyMatched,
     /// The path wasn't matched to a rule or a nearby package, causing everything to be rebuilt.
     NoMatches,
 }
@@ -404,7 +645,8 @@ pub enum PathMatch {
 /// The index of a rule.
 ///
 /// Used in `PathMatch` and while returning errors.
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum RuleIndex {
     /// The custom path rule at this index.
     CustomPath(usize),
@@ -426,15 +668,213 @@ impl fmt::Display for RuleIndex {
     }
 }
 
+/// The file a determinator rule was loaded from.
+///
+/// Returned by [`DeterminatorRules::location`] and [`RulesError::location`](crate::errors::RulesError::location).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuleLocation {
+    path: Utf8PathBuf,
+    index_in_file: RuleIndex,
+}
+
+impl RuleLocation {
+    /// Returns the path of the file, relative to the workspace root.
+    ///
+    /// This is `Cargo.toml` for rules in `[workspace.metadata.determinator]`.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Returns the index of the rule within the file.
+    ///
+    /// This is either a `CustomPath` or a `Package` index.
+    pub fn index_in_file(&self) -> RuleIndex {
+        self.index_in_file
+    }
+}
+
+impl fmt::Display for RuleLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in {}", self.index_in_file, self.path)
+    }
+}
+
 // ---
 // Private types
 // ---
 
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct RuleLocations {
+    path_rules: Vec<Option<RuleLocation>>,
+    package_rules: Vec<Option<RuleLocation>>,
+}
+
+/// Combines rules from several files into one set of rules.
+struct RulesLoader<'a> {
+    workspace_root: &'a Utf8Path,
+    visited: HashSet<Utf8PathBuf>,
+    rules: DeterminatorRules,
+}
+
+impl<'a> RulesLoader<'a> {
+    fn new(workspace_root: &'a Utf8Path) -> Self {
+        Self {
+            workspace_root,
+            visited: HashSet::new(),
+            rules: DeterminatorRules::default(),
+        }
+    }
+
+    // path is relative to the workspace root.
+    fn load_file(&mut self, path: &Utf8Path) -> Result<(), RulesLoadError> {
+        if !self.visited.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+        let abs_path = self.workspace_root.join(path);
+        let contents = fs::read_to_string(&abs_path)
+            .map_err(|err| RulesLoadError::new(&abs_path, RulesLoadErrorKind::Read(err)))?;
+        let rules = DeterminatorRules::parse(&contents)
+            .map_err(|err| RulesLoadError::new(&abs_path, RulesLoadErrorKind::Parse(err)))?;
+        self.add_rules(rules, path)
+    }
+
+    // source is relative to the workspace root.
+    fn add_rules(
+        &mut self,
+        rules: DeterminatorRules,
+        source: &Utf8Path,
+    ) -> Result<(), RulesLoadError> {
+        let DeterminatorRules {
+            use_default_rules,
+            include,
+            path_rules,
+            package_rules,
+            locations: _,
+        } = rules;
+        let dir = source.parent().unwrap_or_else(|| Utf8Path::new(""));
+        let location = |index_in_file| {
+            Some(RuleLocation {
+                path: source.to_path_buf(),
+                index_in_file,
+            })
+        };
+
+        self.rules.use_default_rules &= use_default_rules;
+        for (idx, mut rule) in path_rules.into_iter().enumerate() {
+            rule.globs = rule
+                .globs
+                .iter()
+                .map(|glob| relative_glob(dir, glob))
+                .collect();
+            self.rules.path_rules.push(rule);
+            self.rules
+                .locations
+                .path_rules
+                .push(location(RuleIndex::CustomPath(idx)));
+        }
+        for (idx, mut rule) in package_rules.into_iter().enumerate() {
+            rule.on_affected_paths = rule
+                .on_affected_paths
+                .iter()
+                .map(|glob| relative_glob(dir, glob))
+                .collect();
+            self.rules.package_rules.push(rule);
+            self.rules
+                .locations
+                .package_rules
+                .push(location(RuleIndex::Package(idx)));
+        }
+
+        for include in include {
+            let path = self.relative_path(&dir.join(&include)).map_err(|_| {
+                RulesLoadError::new(
+                    self.workspace_root.join(source),
+                    RulesLoadErrorKind::OutsideWorkspace {
+                        rules_path: include,
+                    },
+                )
+            })?;
+            self.load_file(&path)?;
+        }
+        Ok(())
+    }
+
+    // Returns a normalized path relative to the workspace root.
+    fn relative_path(&self, path: &Utf8Path) -> Result<Utf8PathBuf, RulesLoadError> {
+        let abs_path = normalize(&self.workspace_root.join(path));
+        match abs_path.strip_prefix(self.workspace_root) {
+            Ok(path) => Ok(path.to_path_buf()),
+            Err(_) => Err(RulesLoadError::new(
+                &abs_path,
+                RulesLoadErrorKind::OutsideWorkspace {
+                    rules_path: path.to_string(),
+                },
+            )),
+        }
+    }
+}
+
+/// Makes a glob relative to the given directory, which is relative to the workspace root.
+fn relative_glob(dir: &Utf8Path, glob: &str) -> String {
+    let mut prefix = String::new();
+    for component in dir.components() {
+        for c in component.as_str().chars() {
+            // Escape glob metacharacters in directory names.
+            match c {
+                '*' | '?' | '[' | ']' | '{' | '}' => {
+                    prefix.push('[');
+                    prefix.push(c);
+                    prefix.push(']');
+                }
+                c => prefix.push(c),
+            }
+        }
+        prefix.push('/');
+    }
+    prefix.push_str(glob);
+    prefix
+}
+
+/// Finds determinator.toml files in the workspace, skipping hidden directories and the target
+/// directory.
+fn find_rules_files(
+    workspace_root: &Utf8Path,
+    dir: &Utf8Path,
+    target_directory: &Utf8Path,
+    files: &mut Vec<Utf8PathBuf>,
+) -> Result<(), RulesLoadError> {
+    let abs_dir = workspace_root.join(dir);
+    let read_err = |err| RulesLoadError::new(&abs_dir, RulesLoadErrorKind::Read(err));
+    for entry in fs::read_dir(&abs_dir).map_err(read_err)? {
+        let entry = entry.map_err(read_err)?;
+        let file_name = match entry.file_name().into_string() {
+            Ok(file_name) => file_name,
+            // Non-UTF-8 paths can't be matched by rules.
+            Err(_) => continue,
+        };
+        // This doesn't follow symlinks, so cycles aren't possible.
+        let file_type = entry.file_type().map_err(read_err)?;
+        let path = dir.join(&file_name);
+        if file_type.is_dir() {
+            if file_name.starts_with('.') || workspace_root.join(&path) == target_directory {
+                continue;
+            }
+            find_rules_files(workspace_root, &path, target_directory, files)?;
+        } else if file_type.is_file() && file_name == DeterminatorRules::FILE_NAME {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
 /// Internal version of determinator rules.
 #[derive(Clone, Debug)]
 pub(crate) struct RulesImpl<'g> {
     pub(crate) path_rules: Vec<PathRuleImpl<'g>>,
+    /// Package rules that mark packages changed.
     pub(crate) package_rules: Vec<PackageRuleImpl<'g>>,
+    /// Packages matched by package rules with `remove-affected = true`.
+    pub(crate) remove_affected: PackageSet<'g>,
 }
 
 impl<'g> RulesImpl<'g> {
@@ -443,6 +883,7 @@ impl<'g> RulesImpl<'g> {
         options: &DeterminatorRules,
     ) -> Result<Self, RulesError> {
         let workspace = graph.workspace();
+        let location = |rule_index| options.location(rule_index).cloned();
 
         let custom_path_rules = options
             .path_rules
@@ -477,18 +918,21 @@ impl<'g> RulesImpl<'g> {
                     // Convert the globs to a globset.
                     let mut builder = GlobSetBuilder::new();
                     for glob in globs {
-                        let glob = Glob::new(glob)
-                            .map_err(|err| RulesError::glob_parse(rule_index, err))?;
+                        let glob = Glob::new(glob).map_err(|err| {
+                            RulesError::glob_parse(rule_index, location(rule_index), err)
+                        })?;
                         builder.add(glob);
                     }
 
-                    let glob_set = builder
-                        .build()
-                        .map_err(|err| RulesError::glob_parse(rule_index, err))?;
+                    let glob_set = builder.build().map_err(|err| {
+                        RulesError::glob_parse(rule_index, location(rule_index), err)
+                    })?;
 
                     // Convert workspace paths to packages.
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    let mark_changed =
+                        MarkChangedImpl::new(&workspace, mark_changed).map_err(|err| {
+                            RulesError::resolve_ref(rule_index, location(rule_index), err)
+                        })?;
 
                     Ok(PathRuleImpl {
                         rule_index,
@@ -500,39 +944,93 @@ impl<'g> RulesImpl<'g> {
             )
             .collect::<Result<Vec<_>, _>>()?;
 
-        let package_rules = options
-            .package_rules
-            .iter()
-            .enumerate()
-            .map(
-                |(
-                    rule_index,
-                    PackageRule {
-                        on_affected,
-                        mark_changed,
-                    },
-                )| {
-                    let rule_index = RuleIndex::Package(rule_index);
-                    let on_affected = graph
-                        .resolve_workspace_names(on_affected)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
-                    Ok(PackageRuleImpl {
+        let mut package_rules = vec![];
+        let mut remove_affected_ids = vec![];
+        for (rule_index, rule) in options.package_rules.iter().enumerate() {
+            let rule_index = RuleIndex::Package(rule_index);
+            let by_name = graph
+                .resolve_workspace_names(&rule.on_affected)
+                .map_err(|err| RulesError::resolve_ref(rule_index, location(rule_index), err))?;
+            let by_pattern = match_packages(graph, rule)
+                .map_err(|err| RulesError::glob_parse(rule_index, location(rule_index), err))?;
+            let on_affected = by_name.union(&by_pattern);
+            match &rule.action {
+                PackageRuleAction::MarkChanged(mark_changed) => {
+                    let mark_changed =
+                        MarkChangedImpl::new(&workspace, mark_changed).map_err(|err| {
+                            RulesError::resolve_ref(rule_index, location(rule_index), err)
+                        })?;
+                    package_rules.push(PackageRuleImpl {
+                        rule_index,
                         on_affected,
                         mark_changed,
-                    })
-                },
-            )
-            .collect::<Result<Vec<_>, _>>()?;
+                    });
+                }
+                PackageRuleAction::RemoveAffected => {
+                    remove_affected_ids
+                        .extend(on_affected.package_ids(DependencyDirection::Forward));
+                }
+            }
+        }
+        let remove_affected = graph
+            .resolve_ids(remove_affected_ids)
+            .expect("package IDs are valid");
 
         Ok(Self {
             path_rules,
             package_rules,
+            remove_affected,
         })
     }
 }
 
+/// Returns the packages matched by a package rule's name globs, path globs and metadata keys.
+fn match_packages<'g>(
+    graph: &'g PackageGraph,
+    rule: &PackageRule,
+) -> Result<PackageSet<'g>, globset::Error> {
+    let name_globs = build_glob_set(&rule.on_affected_globs)?;
+    let path_globs = build_glob_set(&rule.on_affected_paths)?;
+    let workspace = graph.workspace();
+    let matched_ids = workspace.iter_by_path().filter_map(|(path, package)| {
+        let is_match = name_globs.is_match(package.name())
+            || path_globs.is_match(path.as_str())
+            || rule
+                .on_affected_metadata
+                .iter()
+                .any(|key| has_metadata_key(&package, key));
+        if is_match {
+            Some(package.id())
+        } else {
+            None
+        }
+    });
+    Ok(graph
+        .resolve_ids(matched_ids)
+        .expect("package IDs are valid"))
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build()
+}
+
+/// Returns true if the `package.metadata` table has the given dotted key, with a value other than
+/// `false` or `null`.
+fn has_metadata_key(package: &PackageMetadata<'_>, key: &str) -> bool {
+    let mut value = package.metadata_table();
+    for component in key.split('.') {
+        match value.get(component) {
+            Some(next) => value = next,
+            None => return false,
+        }
+    }
+    !matches!(value, JsonValue::Null | JsonValue::Bool(false))
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct PathRuleImpl<'g> {
     pub(crate) rule_index: RuleIndex,
@@ -543,6 +1041,7 @@ pub(crate) struct PathRuleImpl<'g> {
 
 #[derive(Clone, Debug)]
 pub(crate) struct PackageRuleImpl<'g> {
+    pub(crate) rule_index: RuleIndex,
     pub(crate) on_affected: PackageSet<'g>,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
 }
@@ -611,9 +1110,113 @@ mod mark_changed_impl {
     }
 }
 
+mod package_rule_impl {
+    use super::*;
+    use std::convert::TryFrom;
+
+    /// The serialized form of a package rule.
+    #[derive(Deserialize, Serialize)]
+    #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+    pub struct PackageRuleSerde {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        on_affected: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        on_affected_globs: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        on_affected_paths: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        on_affected_metadata: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mark_changed: Option<MarkChangedSerde>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        remove_affected: bool,
+    }
+
+    #[derive(Deserialize, Serialize)]
+    #[serde(transparent)]
+    struct MarkChangedSerde(#[serde(with = "mark_changed_impl")] DeterminatorMarkChanged);
+
+    fn is_false(b: &bool) -> bool {
+        !*b
+    }
+
+    fn non_empty(v: Vec<String>) -> Option<Vec<String>> {
+        if v.is_empty() {
+            None
+        } else {
+            Some(v)
+        }
+    }
+
+    impl TryFrom<PackageRuleSerde> for PackageRule {
+        type Error = String;
+
+        fn try_from(rule: PackageRuleSerde) -> Result<Self, Self::Error> {
+            if rule.on_affected.is_none()
+                && rule.on_affected_globs.is_none()
+                && rule.on_affected_paths.is_none()
+                && rule.on_affected_metadata.is_none()
+            {
+                return Err("package rule must specify at least one of on-affected, \
+                            on-affected-globs, on-affected-paths or on-affected-metadata"
+                    .to_owned());
+            }
+            let action =
+                match (rule.mark_changed, rule.remove_affected) {
+                    (Some(MarkChangedSerde(mark_changed)), false) => {
+                        PackageRuleAction::MarkChanged(mark_changed)
+                    }
+                    (None, true) => PackageRuleAction::RemoveAffected,
+                    (Some(_), true) => {
+                        return Err(
+                            "package rule cannot specify both mark-changed and remove-affected"
+                                .to_owned(),
+                        )
+                    }
+                    (None, false) => return Err(
+                        "package rule must specify either mark-changed or remove-affected = true"
+                            .to_owned(),
+                    ),
+                };
+            Ok(PackageRule {
+                on_affected: rule.on_affected.unwrap_or_default(),
+                on_affected_globs: rule.on_affected_globs.unwrap_or_default(),
+                on_affected_paths: rule.on_affected_paths.unwrap_or_default(),
+                on_affected_metadata: rule.on_affected_metadata.unwrap_or_default(),
+                action,
+            })
+        }
+    }
+
+    impl From<PackageRule> for PackageRuleSerde {
+        fn from(rule: PackageRule) -> Self {
+            let (mark_changed, remove_affected) = match rule.action {
+                PackageRuleAction::MarkChanged(mark_changed) => {
+                    (Some(MarkChangedSerde(mark_changed)), false)
+                }
+                PackageRuleAction::RemoveAffected => (None, true),
+            };
+            // on-affected is always serialized so that the rule can be read back.
+            PackageRuleSerde {
+                on_affected: Some(rule.on_affected),
+                on_affected_globs: non_empty(rule.on_affected_globs),
+                on_affected_paths: non_empty(rule.on_affected_paths),
+                on_affected_metadata: non_empty(rule.on_affected_metadata),
+                mark_changed,
+                remove_affected,
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        test_helpers::{manifest, temp_dir, write},
+        Determinator,
+    };
+    use guppy::MetadataCommand;
 
     #[test]
     fn parse() {
@@ -638,10 +1241,17 @@ mod tests {
         [[package-rule]]
         on-affected = ["test1"]
         mark-changed = "all"
+
+        [[package-rule]]
+        on-affected-globs = ["bar-*"]
+        on-affected-paths = ["tools/**"]
+        on-affected-metadata = ["my-tool.docs-only"]
+        remove-affected = true
         "#;
 
         let expected = DeterminatorRules {
             use_default_rules: true,
+            include: vec![],
             path_rules: vec![
                 PathRule {
                     globs: vec!["all/*".to_owned()],
@@ -662,20 +1272,43 @@ mod tests {
             package_rules: vec![
                 PackageRule {
                     on_affected: vec!["foo".to_string()],
-                    mark_changed: DeterminatorMarkChanged::Packages(vec!["wat".to_string()]),
+                    on_affected_globs: vec![],
+                    on_affected_paths: vec![],
+                    on_affected_metadata: vec![],
+                    action: PackageRuleAction::MarkChanged(DeterminatorMarkChanged::Packages(
+                        vec!["wat".to_string()],
+                    )),
                 },
                 PackageRule {
                     on_affected: vec!["test1".to_string()],
-                    mark_changed: DeterminatorMarkChanged::All,
+                    on_affected_globs: vec![],
+                    on_affected_paths: vec![],
+                    on_affected_metadata: vec![],
+                    action: PackageRuleAction::MarkChanged(DeterminatorMarkChanged::All),
+                },
+                PackageRule {
+                    on_affected: vec![],
+                    on_affected_globs: vec!["bar-*".to_string()],
+                    on_affected_paths: vec!["tools/**".to_string()],
+                    on_affected_metadata: vec!["my-tool.docs-only".to_string()],
+                    action: PackageRuleAction::RemoveAffected,
                 },
             ],
+            locations: RuleLocations::default(),
         };
 
         assert_eq!(
             DeterminatorRules::parse(s),
-            Ok(expected),
+            Ok(expected.clone()),
             "parse() result matches"
         );
+
+        let serialized = toml::to_string(&expected).expect("rules serialized");
+        assert_eq!(
+            DeterminatorRules::parse(&serialized),
+            Ok(expected),
+            "serialized rules round-trip"
+        );
     }
 
     #[test]
@@ -807,6 +1440,32 @@ mod tests {
             r#"[[package-rule]]
             on-affected = ["foo"]
             "#,
+            // on-affected-globs is not a list
+            r#"[[package-rule]]
+            on-affected-globs = "foo-*"
+            mark-changed = []
+            "#,
+            // on-affected-metadata doesn't contain strings
+            r#"[[package-rule]]
+            on-affected-metadata = [123]
+            mark-changed = []
+            "#,
+            // both mark-changed and remove-affected
+            r#"[[package-rule]]
+            on-affected = ["foo"]
+            mark-changed = ["bar"]
+            remove-affected = true
+            "#,
+            // remove-affected is false and mark-changed is missing
+            r#"[[package-rule]]
+            on-affected = ["foo"]
+            remove-affected = false
+            "#,
+            // remove-affected is not a boolean
+            r#"[[package-rule]]
+            on-affected = ["foo"]
+            remove-affected = "yes"
+            "#,
         ];
 
         for &bad in bads {
@@ -821,4 +1480,151 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn relative_globs() {
+        assert_eq!(relative_glob(Utf8Path::new(""), "**/*.rs"), "**/*.rs");
+        assert_eq!(relative_glob(Utf8Path::new("a/b"), "*.rs"), "a/b/*.rs");
+        assert_eq!(
+            relative_glob(Utf8Path::new("a[1]/{b}"), "*.rs"),
+            "a[[]1[]]/[{]b[}]/*.rs"
+        );
+    }
+
+    #[test]
+    fn load_workspace() {
+        let (_dir, root) = temp_dir();
+        write(
+            &root,
+            "Cargo.toml",
+            r#"[workspace]
+            members = ["a", "b"]
+
+            [workspace.metadata.determinator]
+            include = ["common/rules.toml"]
+
+            [[workspace.metadata.determinator.path-rule]]
+            globs = ["rust-toolchain"]
+            mark-changed = "all"
+            "#,
+        );
+        write(&root, "a/Cargo.toml", &manifest("a"));
+        write(&root, "a/src/lib.rs", "");
+        write(
+            &root,
+            "a/determinator.toml",
+            r#"[[path-rule]]
+            globs = ["**/*.proto"]
+            mark-changed = ["b"]
+            "#,
+        );
+        write(
+            &root,
+            "a/sub/determinator.toml",
+            r#"include = ["../../common/rules.toml"]
+
+            [[path-rule]]
+            globs = ["x"]
+            mark-changed = ["a"]
+            "#,
+        );
+        write(&root, "b/Cargo.toml", &manifest("b"));
+        write(&root, "b/src/lib.rs", "");
+        write(
+            &root,
+            "common/rules.toml",
+            r#"[[package-rule]]
+            on-affected = ["a"]
+            mark-changed = ["b"]
+            "#,
+        );
+        // Hidden directories are skipped.
+        write(&root, ".hidden/determinator.toml", "invalid");
+
+        let graph = MetadataCommand::new()
+            .current_dir(&root)
+            .build_graph()
+            .expect("package graph built");
+        let rules = DeterminatorRules::load_workspace(&graph).expect("rules loaded");
+
+        let globs: Vec<_> = rules
+            .path_rules
+            .iter()
+            .map(|rule| rule.globs.as_slice())
+            .collect();
+        assert_eq!(
+            globs,
+            vec![
+                &["a/sub/x".to_owned()][..],
+                &["a/**/*.proto".to_owned()][..],
+                &["rust-toolchain".to_owned()][..],
+            ],
+            "path rules are ordered deepest first and made relative"
+        );
+        assert_eq!(rules.package_rules.len(), 1, "common rules included once");
+
+        let location = |rule_index| {
+            rules
+                .location(rule_index)
+                .map(|location| (location.path().as_str(), location.index_in_file()))
+        };
+        assert_eq!(
+            location(RuleIndex::CustomPath(1)),
+            Some(("a/determinator.toml", RuleIndex::CustomPath(0)))
+        );
+        assert_eq!(
+            location(RuleIndex::CustomPath(2)),
+            Some(("Cargo.toml", RuleIndex::CustomPath(0)))
+        );
+        assert_eq!(
+            location(RuleIndex::Package(0)),
+            Some(("common/rules.toml", RuleIndex::Package(0)))
+        );
+        assert_eq!(location(RuleIndex::DefaultPath(0)), None);
+
+        let mut determinator = Determinator::new(&graph, &graph);
+        determinator.set_rules(&rules).expect("rules are valid");
+        assert_eq!(
+            determinator.match_path("a/foo/bar.proto", |_| {}),
+            PathMatch::RuleMatched(RuleIndex::CustomPath(1)),
+        );
+        assert_eq!(
+            determinator.match_path("b/foo/bar.proto", |_| {}),
+            PathMatch::AncestorMatched,
+        );
+
+        // Errors point to the file and rule that failed.
+        write(
+            &root,
+            "b/determinator.toml",
+            r#"[[path-rule]]
+            globs = ["foo"]
+            mark-changed = []
+
+            [[path-rule]]
+            globs = ["bar"]
+            mark-changed = ["nonexistent"]
+            "#,
+        );
+        let rules = DeterminatorRules::load_workspace(&graph).expect("rules loaded");
+        let err = Determinator::new(&graph, &graph)
+            .set_rules(&rules)
+            .expect_err("nonexistent package is an error");
+        let location = err.location().expect("location is known");
+        assert_eq!(location.path(), "b/determinator.toml");
+        assert_eq!(location.index_in_file(), RuleIndex::CustomPath(1));
+
+        // Includes outside the workspace are errors.
+        write(
+            &root,
+            "b/determinator.toml",
+            r#"include = ["../../outside.toml"]"#,
+        );
+        let err = DeterminatorRules::load_workspace(&graph).expect_err("include is outside");
+        assert!(
+            matches!(err.kind(), RulesLoadErrorKind::OutsideWorkspace { .. }),
+            "unexpected error: {}",
+            err
+        );
+    }
 }
@@ -0,0 +1,342 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::errors::ShardCostsError;
+use camino::Utf8Path;
+use guppy::{
+    graph::{DependencyDirection, PackageMetadata, PackageSet},
+    PackageId,
+};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap, fs};
+
+/// Per-package costs used to balance shards, typically historical test durations.
+///
+/// Costs can be read from a TOML or JSON file. In TOML, this looks like:
+///
+/// ```toml
+/// # The cost for packages not listed below. Defaults to 1.
+/// default-cost = 5.0
+///
+/// # Groups of packages that should be run on the same shard, if
+/// # `Sharder::set_keep_coupled` is true.
+/// coupled = [
+///     ["foo", "foo-integration-tests"],
+/// ]
+///
+/// [packages]
+/// # Costs are keyed by package name, and are in arbitrary but consistent units (e.g. seconds).
+/// foo = 120.5
+/// bar = 30
+/// ```
+///
+/// Costs must be finite and non-negative. This is checked while parsing.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ShardCosts {
+    /// The cost for packages not listed in `packages`.
+    #[serde(default = "default_cost")]
+    pub default_cost: f64,
+
+    /// Groups of package names that should be kept together in the same shard.
+    ///
+    /// Groups that share a package are merged.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coupled: Vec<Vec<String>>,
+
+    /// Costs for individual packages, keyed by package name.
+    #[serde(default)]
+    pub packages: BTreeMap<String, f64>,
+}
+
+impl Default for ShardCosts {
+    fn default() -> Self {
+        Self {
+            default_cost: default_cost(),
+            coupled: vec![],
+            packages: BTreeMap::new(),
+        }
+    }
+}
+
+impl ShardCosts {
+    /// Parses costs from a TOML string.
+    pub fn parse_toml(s: &str) -> Result<Self, ShardCostsError> {
+        let costs: Self = toml::from_str(s).map_err(ShardCostsError::TomlParse)?;
+        costs.validate()?;
+        Ok(costs)
+    }
+
+    /// Parses costs from a JSON string.
+    pub fn parse_json(s: &str) -> Result<Self, ShardCostsError> {
+        let costs: Self = serde_json::from_str(s).map_err(ShardCostsError::JsonParse)?;
+        costs.validate()?;
+        Ok(costs)
+    }
+
+    /// Reads costs from a file.
+    ///
+    /// Files with a `.json` extension are parsed as JSON, and all other files are parsed as TOML.
+    pub fn read_file(path: impl AsRef<Utf8Path>) -> Result<Self, ShardCostsError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| ShardCostsError::Read {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        if path.extension() == Some("json") {
+            Self::parse_json(&contents)
+        } else {
+            Self::parse_toml(&contents)
+        }
+    }
+
+    /// Returns the cost for the given package.
+    pub fn cost(&self, package: &PackageMetadata<'_>) -> f64 {
+        self.packages
+            .get(package.name())
+            .copied()
+            .unwrap_or(self.default_cost)
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn validate(&self) -> Result<(), ShardCostsError> {
+        if !is_valid_cost(self.default_cost) {
+            return Err(ShardCostsError::InvalidCost {
+                package: None,
+                cost: self.default_cost,
+            });
+        }
+        for (name, &cost) in &self.packages {
+            if !is_valid_cost(cost) {
+                return Err(ShardCostsError::InvalidCost {
+                    package: Some(name.clone()),
+                    cost,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a set of packages into shards of roughly equal cost.
+///
+/// Sharding is deterministic: the same package set and costs always produce the same shards.
+///
+/// # Examples
+///
+/// ```rust
+/// use determinator::{ShardCosts, Sharder};
+/// use guppy::CargoMetadata;
+///
+/// let metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/guppy/metadata_guppy_869476c.json")).unwrap();
+/// let graph = metadata.build_graph().unwrap();
+///
+/// let costs = ShardCosts::parse_toml(r#"
+///     [packages]
+///     guppy = 100
+///     target-spec = 20
+/// "#).unwrap();
+///
+/// // This would typically be the affected set computed by a determinator.
+/// let workspace_set = graph.resolve_workspace();
+/// let shards = Sharder::new(&costs).shard(&workspace_set, 2);
+/// assert_eq!(shards.len(), 2);
+/// for shard in &shards {
+///     println!("shard {}: cost {}", shard.index, shard.cost);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sharder<'a> {
+    costs: &'a ShardCosts,
+    keep_coupled: bool,
+}
+
+impl<'a> Sharder<'a> {
+    /// Creates a new `Sharder` with the given costs.
+    ///
+    /// By default, coupled packages are kept together.
+    pub fn new(costs: &'a ShardCosts) -> Self {
+        Self {
+            costs,
+            keep_coupled: true,
+        }
+    }
+
+    /// Sets whether packages listed together in `ShardCosts::coupled` are placed in the same
+    /// shard.
+    pub fn set_keep_coupled(&mut self, keep_coupled: bool) -> &mut Self {
+        self.keep_coupled = keep_coupled;
+        self
+    }
+
+    /// Splits `package_set` into `count` shards.
+    ///
+    /// Exactly `count` shards are returned, ordered by index. Some shards may be empty if there
+    /// are fewer units of work than shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0.
+    pub fn shard<'g>(&self, package_set: &PackageSet<'g>, count: usize) -> Vec<Shard<'g>> {
+        assert!(count > 0, "shard count must be greater than 0");
+
+        // Sort packages by name, then by ID, so that the result doesn't depend on graph indexes.
+        let mut packages: Vec<_> = package_set.packages(DependencyDirection::Forward).collect();
+        packages.sort_by(|a, b| (a.name(), a.id()).cmp(&(b.name(), b.id())));
+
+        let units = self.units(&packages);
+
+        // Assign units greedily, most expensive first, to the shard with the lowest total cost.
+        // This is the "longest processing time" heuristic.
+        let mut shards: Vec<ShardBuilder<'g>> =
+            (0..count).map(|_| ShardBuilder::default()).collect();
+        for unit in units {
+            let shard = shards
+                .iter_mut()
+                .min_by(|a, b| {
+                    cmp_cost(a.cost, b.cost)
+                        .then_with(|| a.package_ids.len().cmp(&b.package_ids.len()))
+                })
+                .expect("count is greater than 0");
+            shard.cost += unit.cost;
+            shard
+                .package_ids
+                .extend(unit.packages.iter().map(|package| package.id()));
+        }
+
+        let graph = package_set
+            .to_package_query(DependencyDirection::Forward)
+            .graph();
+        shards
+            .into_iter()
+            .enumerate()
+            .map(|(index, shard)| Shard {
+                index,
+                package_set: graph
+                    .resolve_ids(shard.package_ids)
+                    .expect("package IDs are valid"),
+                cost: shard.cost,
+            })
+            .collect()
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    /// Groups packages (already sorted) into units, sorted by decreasing cost.
+    fn units<'g>(&self, packages: &[PackageMetadata<'g>]) -> Vec<Unit<'g>> {
+        // parents is a union-find structure over indexes into packages.
+        let mut parents: Vec<usize> = (0..packages.len()).collect();
+        if self.keep_coupled {
+            let mut indexes_by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+            for (idx, package) in packages.iter().enumerate() {
+                indexes_by_name.entry(package.name()).or_default().push(idx);
+            }
+            for group in &self.costs.coupled {
+                let mut indexes = group
+                    .iter()
+                    .filter_map(|name| indexes_by_name.get(name.as_str()))
+                    .flatten();
+                if let Some(&first) = indexes.next() {
+                    for &idx in indexes {
+                        union(&mut parents, first, idx);
+                    }
+                }
+            }
+        }
+
+        // Roots are the smallest index in each unit, so a unit's root is always seen before the
+        // rest of its packages. This also means that units come out ordered by their first package.
+        let mut units: Vec<Unit<'g>> = vec![];
+        let mut unit_indexes = vec![0; packages.len()];
+        for (idx, package) in packages.iter().enumerate() {
+            let root = find(&mut parents, idx);
+            if root == idx {
+                unit_indexes[idx] = units.len();
+                units.push(Unit {
+                    packages: vec![],
+                    cost: 0.0,
+                });
+            }
+            let unit = &mut units[unit_indexes[root]];
+            unit.packages.push(*package);
+            unit.cost += self.costs.cost(package);
+        }
+
+        // This is a stable sort, so units with the same cost stay ordered by their first package.
+        units.sort_by(|a, b| cmp_cost(b.cost, a.cost));
+        units
+    }
+}
+
+/// A shard returned by [`Sharder::shard`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shard<'g> {
+    /// The index of this shard, starting from 0.
+    pub index: usize,
+
+    /// The packages in this shard.
+    pub package_set: PackageSet<'g>,
+
+    /// The total cost of the packages in this shard.
+    pub cost: f64,
+}
+
+// ---
+// Helpers
+// ---
+
+fn default_cost() -> f64 {
+    1.0
+}
+
+fn is_valid_cost(cost: f64) -> bool {
+    cost.is_finite() && cost >= 0.0
+}
+
+fn cmp_cost(a: f64, b: f64) -> Ordering {
+    // Costs parsed from files are validated to be finite. Treat NaNs in manually constructed
+    // costs as equal to everything else.
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+#[derive(Debug)]
+struct Unit<'g> {
+    packages: Vec<PackageMetadata<'g>>,
+    cost: f64,
+}
+
+#[derive(Debug, Default)]
+struct ShardBuilder<'g> {
+    package_ids: Vec<&'g PackageId>,
+    cost: f64,
+}
+
+fn find(parents: &mut [usize], idx: usize) -> usize {
+    let mut root = idx;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // Path compression.
+    let mut idx = idx;
+    while parents[idx] != root {
+        let next = parents[idx];
+        parents[idx] = root;
+        idx = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    // Always use the smaller index as the root.
+    match a.cmp(&b) {
+        Ordering::Less => parents[b] = a,
+        Ordering::Greater => parents[a] = b,
+        Ordering::Equal => {}
+    }
+}
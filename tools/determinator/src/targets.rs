@@ -0,0 +1,150 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::DeterminatorSet;
+use camino::Utf8Path;
+use guppy::{
+    graph::{BuildTarget, BuildTargetId, DependencyDirection, PackageGraph, PackageMetadata},
+    PackageId,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The result of a `Determinator` computation at the granularity of build targets.
+///
+/// Returned by [`Determinator::compute_targets`](crate::Determinator::compute_targets).
+///
+/// The lifetime `'g` is tied to the *new* `PackageGraph` passed to a `Determinator`.
+#[derive(Clone, Debug)]
+pub struct DeterminatorTargetSet<'g> {
+    /// The package-level result, computed without the changes that only affect specific build
+    /// targets.
+    ///
+    /// Every build target of every package in `package_set.affected_set` is affected.
+    pub package_set: DeterminatorSet<'g>,
+
+    /// Build targets that were affected in packages that aren't otherwise affected.
+    ///
+    /// This does not include packages in `package_set.affected_set`.
+    pub target_changed: BTreeMap<&'g PackageId, BTreeSet<BuildTargetId<'g>>>,
+
+    pub(crate) graph: &'g PackageGraph,
+}
+
+impl<'g> DeterminatorTargetSet<'g> {
+    /// Returns true if the given build target in the given package is affected.
+    ///
+    /// Returns false if the package ID is unknown.
+    pub fn is_affected(&self, package_id: &PackageId, target_id: &BuildTargetId<'_>) -> bool {
+        if self
+            .package_set
+            .affected_set
+            .contains(package_id)
+            .unwrap_or(false)
+        {
+            return true;
+        }
+        match self.target_changed.get(package_id) {
+            Some(targets) => targets.contains(target_id),
+            None => false,
+        }
+    }
+
+    /// Iterates over all affected `(package, build target)` pairs.
+    ///
+    /// Packages in `package_set.affected_set` are returned first, in topological order, followed
+    /// by the packages in `target_changed`.
+    pub fn affected_targets<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, BuildTarget<'g>)> + 'a {
+        let graph = self.graph;
+        let all_targets = self
+            .package_set
+            .affected_set
+            .packages(DependencyDirection::Forward)
+            .flat_map(|package| package.build_targets().map(move |target| (package, target)));
+        let some_targets = self.target_changed.iter().flat_map(move |(id, targets)| {
+            let package = graph.metadata(id).expect("valid package ID");
+            targets.iter().map(move |target_id| {
+                let target = package
+                    .build_target(target_id)
+                    .expect("valid build target ID");
+                (package, target)
+            })
+        });
+        all_targets.chain(some_targets)
+    }
+}
+
+/// Narrows a path matched to its nearest package down to the build targets it affects.
+///
+/// Returns `None` if the path may affect the library or build script, or can't be matched to a
+/// build target: in that case the entire package is affected.
+///
+/// A path is matched to the build target whose source file it is. If there's no such target, it
+/// is matched to the targets in the closest directory above it, e.g. `tests/common/mod.rs` is
+/// matched to every test in `tests`.
+pub(crate) fn narrow_to_targets<'g>(
+    package: PackageMetadata<'g>,
+    workspace_root: &Utf8Path,
+    path: &Utf8Path,
+) -> Option<BTreeSet<BuildTargetId<'g>>> {
+    let mut exact = None;
+    let mut closest = vec![];
+    let mut closest_len = 0;
+    for target in package.build_targets() {
+        let target_path = match target.path().strip_prefix(workspace_root) {
+            Ok(target_path) => target_path,
+            Err(_) => continue,
+        };
+        if target_path == path {
+            exact = Some(target.id());
+            break;
+        }
+        let dir = match target_path.parent() {
+            Some(dir) => dir,
+            None => continue,
+        };
+        if path.starts_with(dir) {
+            let len = dir.components().count();
+            if len > closest_len {
+                closest = vec![target.id()];
+                closest_len = len;
+            } else if len == closest_len {
+                closest.push(target.id());
+            }
+        }
+    }
+
+    let mut targets: BTreeSet<_> = match exact {
+        Some(target_id) => std::iter::once(target_id).collect(),
+        None => closest.into_iter().collect(),
+    };
+    if targets.is_empty()
+        || targets.iter().any(|target_id| {
+            matches!(
+                target_id,
+                BuildTargetId::Library | BuildTargetId::BuildScript
+            )
+        })
+    {
+        return None;
+    }
+
+    // Cargo builds binaries before running integration tests and benchmarks, which may run them.
+    if targets
+        .iter()
+        .any(|target_id| matches!(target_id, BuildTargetId::Binary(_)))
+    {
+        targets.extend(
+            package
+                .build_targets()
+                .filter_map(|target| match target.id() {
+                    target_id @ BuildTargetId::Test(_)
+                    | target_id @ BuildTargetId::Benchmark(_) => Some(target_id),
+                    _ => None,
+                }),
+        );
+    }
+
+    Some(targets)
+}
@@ -0,0 +1,33 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Helpers for unit tests that create temporary workspaces and repositories.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use std::fs;
+use tempfile::TempDir;
+
+/// Creates a temporary directory, returning it along with its path.
+///
+/// The directory is deleted once the returned `TempDir` is dropped.
+pub(crate) fn temp_dir() -> (TempDir, Utf8PathBuf) {
+    let dir = tempfile::tempdir().expect("temp dir created");
+    let root =
+        Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).expect("temp dir is valid UTF-8");
+    (dir, root)
+}
+
+/// Writes `contents` to `path` relative to `root`, creating parent directories as necessary.
+pub(crate) fn write(root: &Utf8Path, path: &str, contents: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().expect("path has parent")).expect("dir created");
+    fs::write(path, contents).expect("file written");
+}
+
+/// Returns a minimal `Cargo.toml` for a package with the given name.
+pub(crate) fn manifest(name: &str) -> String {
+    format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+        name
+    )
+}
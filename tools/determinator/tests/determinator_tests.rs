@@ -3,13 +3,23 @@
 
 //! Higher-level unit tests for the target determinator.
 
+use camino::Utf8Path;
 use cfg_if::cfg_if;
 use determinator::{
+    errors::ShardCostsError,
     rules::{DeterminatorRules, PathMatch, RuleIndex},
-    Determinator, Utf8Paths0,
+    AffectedCause, BuildCache, Determinator, DeterminatorSet, PathMatchedBy, Shard, ShardCosts,
+    Sharder, Utf8Paths0,
 };
 use fixtures::json::JsonFixture;
-use guppy::{graph::feature::StandardFeatures, CargoMetadata};
+use guppy::{
+    graph::{feature::StandardFeatures, BuildTargetId, DependencyDirection},
+    CargoMetadata, MetadataCommand, Platform, TargetFeatures,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
 
 #[test]
 fn guppy_no_rules() {
@@ -386,6 +396,761 @@ fn guppy_deps() {
     );
 }
 
+#[test]
+fn guppy_affected_causes() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "package-rules.toml");
+    let id = |name: &str| {
+        new.graph()
+            .workspace()
+            .member_by_name(name)
+            .expect("valid workspace name")
+            .id()
+    };
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["fixtures/src/main.rs"]);
+    let determinator_set = determinator.compute();
+    check_causes(&determinator_set);
+
+    let causes = &determinator_set.affected_causes;
+    assert_eq!(
+        causes.get(id("fixtures")),
+        Some(&AffectedCause::PathChanged {
+            path: "fixtures/src/main.rs".into(),
+            matched_by: PathMatchedBy::NearestPackage,
+        }),
+        "fixtures matched through nearest package"
+    );
+    assert_eq!(
+        causes.get(id("guppy-cmdlib")),
+        Some(&AffectedCause::PackageRule {
+            rule_index: RuleIndex::Package(0),
+            on_affected: "fixtures".to_owned(),
+        }),
+        "guppy-cmdlib marked changed through package rule"
+    );
+    assert_eq!(
+        causes.get(id("guppy")),
+        Some(&AffectedCause::Dependency {
+            chain: vec!["guppy".to_owned(), "fixtures".to_owned()],
+        }),
+        "guppy dev-depends on fixtures"
+    );
+    assert_eq!(
+        causes.get(id("cargo-guppy")),
+        Some(&AffectedCause::Dependency {
+            chain: vec!["cargo-guppy".to_owned(), "guppy-cmdlib".to_owned()],
+        }),
+        "cargo-guppy depends on guppy-cmdlib"
+    );
+    assert_eq!(
+        causes.get(id("guppy-benchmarks")),
+        None,
+        "guppy-benchmarks not affected"
+    );
+
+    let json = serde_json::to_value(causes).expect("causes serialized");
+    assert_eq!(
+        json["fixtures"],
+        serde_json::json!({
+            "cause": "path-changed",
+            "path": "fixtures/src/main.rs",
+            "matched-by": { "kind": "nearest-package" },
+        }),
+        "path changed serialized correctly"
+    );
+    assert_eq!(
+        json["guppy-cmdlib"],
+        serde_json::json!({
+            "cause": "package-rule",
+            "rule-index": { "package": 0 },
+            "on-affected": "fixtures",
+        }),
+        "package rule serialized correctly"
+    );
+
+    // A package rule marking everything changed.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["foo/fake-trigger"]);
+    let determinator_set = determinator.compute();
+    check_causes(&determinator_set);
+    let causes = &determinator_set.affected_causes;
+    assert_eq!(
+        causes.get(id("proptest-ext")),
+        Some(&AffectedCause::PathChanged {
+            path: "foo/fake-trigger".into(),
+            matched_by: PathMatchedBy::Rule {
+                rule_index: RuleIndex::CustomPath(0),
+            },
+        }),
+        "proptest-ext matched through path rule"
+    );
+    assert_eq!(
+        causes.get(id("target-spec")),
+        Some(&AffectedCause::PackageRule {
+            rule_index: RuleIndex::Package(1),
+            on_affected: "guppy-benchmarks".to_owned(),
+        }),
+        "target-spec marked changed through package rule"
+    );
+
+    // A path not matched by anything.
+    determinator.add_changed_paths(vec!["Cargo.lock"]);
+    let determinator_set = determinator.compute();
+    check_causes(&determinator_set);
+    assert_eq!(
+        determinator_set.affected_causes.get(id("guppy")),
+        Some(&AffectedCause::PathChanged {
+            path: "Cargo.lock".into(),
+            matched_by: PathMatchedBy::NoMatches,
+        }),
+        "Cargo.lock caused everything to be rebuilt"
+    );
+}
+
+#[test]
+fn guppy_summary_causes() {
+    // new updates the version of toml.
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+
+    let determinator = Determinator::new(old.graph(), new.graph());
+    let determinator_set = determinator.compute();
+    check_causes(&determinator_set);
+
+    for (package, cause) in determinator_set.affected_causes.iter() {
+        match cause {
+            AffectedCause::SummaryChanged { changed_packages } => {
+                assert!(
+                    changed_packages
+                        .iter()
+                        .any(|change| change.package.name == "toml"),
+                    "for package {}, toml is changed",
+                    package.name()
+                );
+            }
+            other => panic!(
+                "for package {}, unexpected cause {:?}",
+                package.name(),
+                other
+            ),
+        }
+    }
+}
+
+#[test]
+fn guppy_build_cache() {
+    // new updates the version of toml.
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+
+    assert_eq!(
+        BuildCache::graph_hash(old.graph()),
+        BuildCache::graph_hash(
+            &CargoMetadata::parse_json(old.json())
+                .expect("fixture is valid JSON")
+                .build_graph()
+                .expect("fixture is a valid graph")
+        ),
+        "graph hash is stable"
+    );
+    assert_ne!(
+        BuildCache::graph_hash(old.graph()),
+        BuildCache::graph_hash(new.graph()),
+        "graph hash changes with the graph"
+    );
+
+    // The hash doesn't depend on where the workspace is, or on the order of packages in the
+    // metadata.
+    let mut moved: serde_json::Value = serde_json::from_str(
+        &old.json()
+            .replace("/home/rain/dev/cargo-guppy", "/build/checkout"),
+    )
+    .expect("fixture is valid JSON");
+    moved["packages"]
+        .as_array_mut()
+        .expect("packages is an array")
+        .reverse();
+    let moved_graph = CargoMetadata::parse_json(moved.to_string())
+        .expect("moved fixture is valid JSON")
+        .build_graph()
+        .expect("moved fixture is a valid graph");
+    assert_eq!(
+        moved_graph.workspace().root(),
+        "/build/checkout",
+        "workspace was moved"
+    );
+    assert_eq!(
+        BuildCache::graph_hash(old.graph()),
+        BuildCache::graph_hash(&moved_graph),
+        "graph hash is independent of the workspace location and package order"
+    );
+
+    let determinator = Determinator::new(old.graph(), new.graph());
+    let expected = determinator.compute();
+
+    // Caches round-trip through TOML.
+    let old_cache = determinator.old_build_cache();
+    let new_cache = determinator.new_build_cache();
+    for cache in &[&old_cache, &new_cache] {
+        let serialized = cache.to_string().expect("cache serialized");
+        let parsed = BuildCache::parse(&serialized).expect("cache parsed");
+        assert_eq!(cache, &&parsed, "cache round-trips");
+    }
+
+    // Results with caches are the same as results without them.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .add_build_cache(&old_cache)
+        .add_build_cache(&new_cache);
+    let actual = determinator.compute();
+    assert_eq!(actual.summary_changed_set, expected.summary_changed_set);
+    assert_eq!(actual.affected_set, expected.affected_set);
+    assert_eq!(
+        causes_by_name(&actual),
+        causes_by_name(&expected),
+        "causes match"
+    );
+
+    // Pretend that the new graph's builds are the old graph's: there are no summary changes if
+    // the cache is used.
+    let mut fake_cache = new_cache.clone();
+    fake_cache.graph_hash = old_cache.graph_hash.clone();
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_build_cache(&fake_cache);
+    assert!(
+        determinator.compute().summary_changed_set.is_empty(),
+        "cache used for old graph"
+    );
+
+    // A cache computed with different options is ignored.
+    let mut cargo_options = Determinator::default_cargo_options();
+    cargo_options.set_include_dev(false);
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_cargo_options(&cargo_options);
+    let no_dev_expected = determinator.compute();
+    determinator.add_build_cache(&fake_cache);
+    let no_dev_actual = determinator.compute();
+    assert_eq!(
+        no_dev_actual.summary_changed_set, no_dev_expected.summary_changed_set,
+        "cache with different options ignored"
+    );
+    assert!(
+        !no_dev_actual.summary_changed_set.is_empty(),
+        "some summary changes without dev-dependencies"
+    );
+}
+
+fn causes_by_name(determinator_set: &DeterminatorSet<'_>) -> Vec<(String, AffectedCause)> {
+    determinator_set
+        .affected_causes
+        .iter()
+        .map(|(package, cause)| (package.name().to_owned(), cause.clone()))
+        .collect()
+}
+
+/// Checks that every affected package has a cause, and that dependency chains lead to a directly
+/// changed package.
+fn check_causes(determinator_set: &DeterminatorSet<'_>) {
+    let causes = &determinator_set.affected_causes;
+    assert_eq!(
+        causes.len(),
+        determinator_set.affected_set.len(),
+        "every affected package has a cause"
+    );
+    for (package, cause) in causes.iter() {
+        assert!(
+            determinator_set
+                .affected_set
+                .contains(package.id())
+                .expect("valid package ID"),
+            "package {} with a cause is affected",
+            package.name()
+        );
+        if let AffectedCause::Dependency { chain } = cause {
+            assert_eq!(
+                chain.first().map(|name| name.as_str()),
+                Some(package.name()),
+                "chain starts with package"
+            );
+            let last = chain.last().expect("chain is non-empty");
+            let (_, last_cause) = causes
+                .iter()
+                .find(|(package, _)| package.name() == last)
+                .expect("end of chain is affected");
+            assert!(
+                !matches!(last_cause, AffectedCause::Dependency { .. }),
+                "for package {}, end of chain {} was marked changed directly",
+                package.name(),
+                last
+            );
+        }
+    }
+}
+
+#[test]
+fn guppy_targets() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "path-rules.toml");
+    let id = |name: &str| {
+        new.graph()
+            .workspace()
+            .member_by_name(name)
+            .expect("valid workspace name")
+            .id()
+    };
+
+    let targets_for = |paths: Vec<&'static str>| {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator.set_rules(&opts).expect("rules set correctly");
+        determinator.add_changed_paths(paths);
+        determinator.compute_targets()
+    };
+
+    // A test helper only affects that test, not packages that depend on guppy.
+    let target_set = targets_for(vec!["guppy/tests/graph-tests/graph_tests.rs"]);
+    assert!(
+        target_set.package_set.affected_set.is_empty(),
+        "no packages affected"
+    );
+    let expected: BTreeMap<_, BTreeSet<_>> = vec![(
+        id("guppy"),
+        vec![BuildTargetId::Test("graph-tests")]
+            .into_iter()
+            .collect(),
+    )]
+    .into_iter()
+    .collect();
+    assert_eq!(target_set.target_changed, expected, "graph-tests affected");
+    assert!(
+        target_set.is_affected(id("guppy"), &BuildTargetId::Test("graph-tests")),
+        "graph-tests affected"
+    );
+    assert!(
+        !target_set.is_affected(id("guppy"), &BuildTargetId::Library),
+        "guppy library not affected"
+    );
+    assert_eq!(
+        target_set.affected_targets().count(),
+        1,
+        "one target affected"
+    );
+
+    // Examples and binaries.
+    let target_set = targets_for(vec![
+        "guppy/examples/deps.rs",
+        "internal-tools/fixture-manager/src/main.rs",
+    ]);
+    assert!(
+        target_set.package_set.affected_set.is_empty(),
+        "no packages affected"
+    );
+    let expected: BTreeMap<_, BTreeSet<_>> = vec![
+        (
+            id("guppy"),
+            vec![BuildTargetId::Example("deps")].into_iter().collect(),
+        ),
+        (
+            id("fixture-manager"),
+            vec![
+                BuildTargetId::Binary("fixture-manager"),
+                // Integration tests may run binaries.
+                BuildTargetId::Test("summary_tests"),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        target_set.target_changed, expected,
+        "example and binary affected"
+    );
+
+    // Changes to the library or manifest fan out to dependents.
+    let target_set = targets_for(vec![
+        "guppy/examples/deps.rs",
+        "fixtures/src/lib.rs",
+        "target-spec/Cargo.toml",
+    ]);
+    let package_only_set = {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator.set_rules(&opts).expect("rules set correctly");
+        determinator.add_changed_paths(vec!["fixtures/src/lib.rs", "target-spec/Cargo.toml"]);
+        determinator.compute()
+    };
+    assert_eq!(
+        target_set.package_set.affected_set, package_only_set.affected_set,
+        "package-level changes match"
+    );
+    assert!(
+        target_set
+            .package_set
+            .affected_set
+            .contains(id("guppy"))
+            .expect("valid package ID"),
+        "guppy is affected through fixtures"
+    );
+    assert!(
+        target_set.target_changed.is_empty(),
+        "guppy's example is covered by the package being affected"
+    );
+    let target_count: usize = target_set
+        .package_set
+        .affected_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.build_targets().count())
+        .sum();
+    assert_eq!(
+        target_set.affected_targets().count(),
+        target_count,
+        "all targets of affected packages are returned"
+    );
+}
+
+#[test]
+fn platform_specific_deps() {
+    // In the new workspace, a depends on b, but only on Windows.
+    let old_dir = tempfile::tempdir().expect("temp dir created");
+    let old_root = Utf8Path::from_path(old_dir.path()).expect("temp dir is valid UTF-8");
+    write_workspace(old_root, "");
+    let new_dir = tempfile::tempdir().expect("temp dir created");
+    let new_root = Utf8Path::from_path(new_dir.path()).expect("temp dir is valid UTF-8");
+    write_workspace(
+        new_root,
+        "[target.'cfg(windows)'.dependencies]\nb = { path = \"../b\" }\n",
+    );
+
+    let old = MetadataCommand::new()
+        .current_dir(old_root)
+        .build_graph()
+        .expect("old graph built");
+    let new = MetadataCommand::new()
+        .current_dir(new_root)
+        .build_graph()
+        .expect("new graph built");
+    let linux =
+        Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).expect("known triple");
+    let windows =
+        Platform::new("x86_64-pc-windows-msvc", TargetFeatures::Unknown).expect("known triple");
+
+    let mut determinator = Determinator::new(&old, &new);
+    determinator.add_changed_paths(vec!["c/src/lib.rs"]);
+    let platform_sets = determinator.compute_platforms(vec![&linux, &windows]);
+    assert_eq!(platform_sets.len(), 2, "one set per platform");
+
+    let names = |determinator_set: &DeterminatorSet<'_>| -> Vec<_> {
+        determinator_set
+            .affected_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name().to_owned())
+            .collect()
+    };
+    assert_eq!(
+        names(&platform_sets[0]),
+        vec!["c"],
+        "linux: only c affected"
+    );
+    let mut windows_names = names(&platform_sets[1]);
+    windows_names.sort();
+    assert_eq!(windows_names, vec!["a", "c"], "windows: a and c affected");
+    let a_id = new
+        .workspace()
+        .member_by_path("a")
+        .expect("a is a workspace member")
+        .id();
+    assert!(
+        matches!(
+            platform_sets[1].affected_causes.get(a_id),
+            Some(AffectedCause::SummaryChanged { .. })
+        ),
+        "a is affected because its build changed"
+    );
+
+    // Results should be the same as computing each platform separately.
+    for (platform, platform_set) in [&linux, &windows].iter().zip(&platform_sets) {
+        let mut cargo_options = Determinator::default_cargo_options();
+        cargo_options.set_target_platform(Some(*platform));
+        let mut determinator = determinator.clone();
+        determinator.set_cargo_options(&cargo_options);
+        let determinator_set = determinator.compute();
+        assert_eq!(
+            determinator_set.affected_set,
+            platform_set.affected_set,
+            "affected set matches for {}",
+            platform.triple()
+        );
+    }
+}
+
+fn write_workspace(root: &Utf8Path, a_deps: &str) {
+    let write = |path: &str, contents: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().expect("path has parent")).expect("dir created");
+        fs::write(path, contents).expect("file written");
+    };
+    write(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"a\", \"b\", \"c\"]\n",
+    );
+    for name in &["a", "b", "c"] {
+        let mut manifest = format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+            name
+        );
+        if *name == "a" {
+            manifest.push_str(a_deps);
+        }
+        write(&format!("{}/Cargo.toml", name), &manifest);
+        write(&format!("{}/src/lib.rs", name), "");
+    }
+}
+
+#[test]
+fn package_rule_matchers() {
+    let dir = tempfile::tempdir().expect("temp dir created");
+    let root = Utf8Path::from_path(dir.path()).expect("temp dir is valid UTF-8");
+    let write = |path: &str, contents: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().expect("path has parent")).expect("dir created");
+        fs::write(path, contents).expect("file written");
+    };
+    write(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"app\", \"checker\", \"core\", \"docs\", \"tools/*\"]\n",
+    );
+    let members: &[(&str, &str, &str)] = &[
+        (
+            "app",
+            "app",
+            "[dependencies]\ncore = { path = \"../core\" }\ndocs = { path = \"../docs\" }\n",
+        ),
+        ("checker", "checker", ""),
+        ("core", "core", ""),
+        ("docs", "docs", "[package.metadata.ci]\ndocs-only = true\n"),
+        ("gen-a", "tools/gen-a", ""),
+        ("gen-b", "tools/gen-b", ""),
+    ];
+    for (name, path, extra) in members {
+        write(
+            &format!("{}/Cargo.toml", path),
+            &format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n{}",
+                name, extra
+            ),
+        );
+        write(&format!("{}/src/lib.rs", path), "");
+    }
+    let graph = MetadataCommand::new()
+        .current_dir(root)
+        .build_graph()
+        .expect("graph built");
+
+    let rules = DeterminatorRules::parse(
+        r#"
+        [[package-rule]]
+        on-affected-metadata = ["ci.docs-only"]
+        remove-affected = true
+
+        [[package-rule]]
+        on-affected-globs = ["gen-*"]
+        mark-changed = ["checker"]
+
+        [[package-rule]]
+        on-affected-paths = ["tools/gen-a"]
+        mark-changed = ["core"]
+
+        # docs is removed, so this rule has no effect.
+        [[package-rule]]
+        on-affected = ["checker"]
+        mark-changed = ["docs"]
+        "#,
+    )
+    .expect("rules parsed");
+    let mut determinator = Determinator::new(&graph, &graph);
+    determinator.set_rules(&rules).expect("rules set correctly");
+
+    let compute = |paths: &[&str]| {
+        let mut determinator = determinator.clone();
+        determinator.add_changed_paths(paths.iter().copied());
+        determinator.compute()
+    };
+    let names = |package_set: &guppy::graph::PackageSet<'_>| -> Vec<_> {
+        let mut names: Vec<_> = package_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name().to_owned())
+            .collect();
+        names.sort();
+        names
+    };
+    let id = |name: &str| {
+        graph
+            .workspace()
+            .member_by_name(name)
+            .expect("workspace member")
+            .id()
+    };
+
+    // docs is removed from the affected set, and app isn't affected through it.
+    let determinator_set = compute(&["docs/src/lib.rs"]);
+    assert_eq!(names(&determinator_set.path_changed_set), vec!["docs"]);
+    assert!(
+        determinator_set.affected_set.is_empty(),
+        "removed packages aren't affected"
+    );
+    assert!(determinator_set.affected_causes.is_empty(), "no causes");
+
+    let determinator_set = compute(&["tools/gen-a/src/lib.rs"]);
+    assert_eq!(
+        names(&determinator_set.affected_set),
+        vec!["app", "checker", "core", "gen-a"],
+        "glob and path rules applied"
+    );
+    assert_eq!(
+        determinator_set.affected_causes.get(id("checker")),
+        Some(&AffectedCause::PackageRule {
+            rule_index: RuleIndex::Package(1),
+            on_affected: "gen-a".to_owned(),
+        }),
+        "checker affected through name glob"
+    );
+    assert_eq!(
+        determinator_set.affected_causes.get(id("core")),
+        Some(&AffectedCause::PackageRule {
+            rule_index: RuleIndex::Package(2),
+            on_affected: "gen-a".to_owned(),
+        }),
+        "core affected through path glob"
+    );
+
+    let determinator_set = compute(&["tools/gen-b/src/lib.rs"]);
+    assert_eq!(
+        names(&determinator_set.affected_set),
+        vec!["checker", "gen-b"],
+        "only the glob rule applied"
+    );
+
+    // The default rule for rust-toolchain marks everything changed, other than docs.
+    let determinator_set = compute(&["rust-toolchain"]);
+    assert_eq!(
+        names(&determinator_set.affected_set),
+        vec!["app", "checker", "core", "gen-a", "gen-b"],
+        "everything but docs affected"
+    );
+    assert_eq!(determinator_set.affected_causes.len(), 5, "5 causes");
+}
+
+#[test]
+fn guppy_shards() {
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let workspace_set = new.graph().resolve_workspace();
+
+    let costs = ShardCosts::parse_toml(
+        r#"
+        coupled = [["guppy", "guppy-benchmarks"]]
+
+        [packages]
+        guppy = 50
+        cargo-compare = 30
+        target-spec = 20
+        "#,
+    )
+    .expect("costs parsed");
+    let json_costs = ShardCosts::parse_json(
+        r#"{
+            "coupled": [["guppy", "guppy-benchmarks"]],
+            "packages": { "guppy": 50, "cargo-compare": 30, "target-spec": 20 }
+        }"#,
+    )
+    .expect("JSON costs parsed");
+    assert_eq!(costs, json_costs, "TOML and JSON costs match");
+
+    let mut sharder = Sharder::new(&costs);
+    let shards = sharder.shard(&workspace_set, 3);
+    assert_eq!(
+        shards,
+        sharder.shard(&workspace_set, 3),
+        "sharding is deterministic"
+    );
+    check_shards(
+        &shards,
+        &[
+            (&["guppy", "guppy-benchmarks"], 51.0),
+            (&["cargo-compare"], 30.0),
+            (
+                &[
+                    "cargo-guppy",
+                    "fixture-manager",
+                    "fixtures",
+                    "guppy-cmdlib",
+                    "guppy-summaries",
+                    "proptest-ext",
+                    "target-spec",
+                ],
+                26.0,
+            ),
+        ],
+    );
+
+    sharder.set_keep_coupled(false);
+    check_shards(
+        &sharder.shard(&workspace_set, 3),
+        &[
+            (&["guppy"], 50.0),
+            (&["cargo-compare"], 30.0),
+            (
+                &[
+                    "cargo-guppy",
+                    "fixture-manager",
+                    "fixtures",
+                    "guppy-benchmarks",
+                    "guppy-cmdlib",
+                    "guppy-summaries",
+                    "proptest-ext",
+                    "target-spec",
+                ],
+                27.0,
+            ),
+        ],
+    );
+
+    // More shards than packages: the extra shards are empty.
+    let shards = sharder.shard(&new.graph().resolve_package_name("guppy"), 2);
+    check_shards(&shards, &[(&["guppy"], 50.0), (&[], 0.0)]);
+
+    let err = ShardCosts::parse_toml("[packages]\nguppy = -1").expect_err("negative cost");
+    assert!(
+        matches!(err, ShardCostsError::InvalidCost { package: Some(ref package), .. } if package == "guppy"),
+        "negative costs are rejected"
+    );
+}
+
+fn check_shards(shards: &[Shard<'_>], expected: &[(&[&str], f64)]) {
+    let actual: Vec<_> = shards
+        .iter()
+        .map(|shard| {
+            let mut names: Vec<_> = shard
+                .package_set
+                .packages(DependencyDirection::Forward)
+                .map(|package| package.name())
+                .collect();
+            names.sort_unstable();
+            (names, shard.cost)
+        })
+        .collect();
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(names, cost)| (names.to_vec(), *cost))
+        .collect();
+    assert_eq!(actual, expected, "shards match");
+}
+
 #[test]
 fn guppy_match_paths() {
     let old = JsonFixture::metadata_guppy_869476c();
@@ -0,0 +1,307 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for partitioning a workspace into several groups, each with its own Hakari package.
+
+use guppy::{
+    graph::{PackageGraph, PackageMetadata},
+    PackageId,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error, fmt,
+};
+
+/// A named group of workspace packages with its own Hakari package.
+///
+/// Groups are added through [`HakariBuilder::add_group`](crate::HakariBuilder::add_group).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HakariGroup<'g> {
+    pub(crate) hakari_package: PackageMetadata<'g>,
+    pub(crate) members: BTreeSet<&'g PackageId>,
+}
+
+impl<'g> HakariGroup<'g> {
+    /// Returns the Hakari package for this group.
+    pub fn hakari_package(&self) -> &PackageMetadata<'g> {
+        &self.hakari_package
+    }
+
+    /// Returns the package IDs of the members of this group, not including the Hakari package.
+    pub fn members<'b>(&'b self) -> impl ExactSizeIterator<Item = &'g PackageId> + 'b {
+        self.members.iter().copied()
+    }
+}
+
+/// An error that occurred while adding or checking the groups in a `HakariBuilder`.
+///
+/// Returned by [`HakariBuilder::add_group`](crate::HakariBuilder::add_group) and
+/// [`HakariBuilder::compute_groups`](crate::HakariBuilder::compute_groups).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum GroupError {
+    /// A package ID passed into `add_group` isn't known to the graph.
+    UnknownPackageId(PackageId),
+
+    /// A package passed into `add_group` is known to the graph, but isn't in the workspace.
+    GroupMemberNotInWorkspace(PackageId),
+
+    /// A workspace package is in more than one group.
+    MultipleGroups {
+        /// The package ID that is in more than one group.
+        package_id: PackageId,
+
+        /// The names of the groups it is in, sorted alphabetically.
+        groups: Vec<String>,
+    },
+
+    /// A member of a group depends on the Hakari package of another group.
+    CrossGroupDependency {
+        /// The group the dependent package is in.
+        group: String,
+
+        /// The package ID of the member that depends on the other group's Hakari package.
+        member: PackageId,
+
+        /// The other group.
+        other_group: String,
+
+        /// The package ID of the other group's Hakari package.
+        hakari_package: PackageId,
+    },
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupError::UnknownPackageId(package_id) => {
+                write!(f, "unknown package ID: {}", package_id)
+            }
+            GroupError::GroupMemberNotInWorkspace(package_id) => write!(
+                f,
+                "package '{}' can't be in a group because it isn't in the workspace",
+                package_id,
+            ),
+            GroupError::MultipleGroups { package_id, groups } => write!(
+                f,
+                "package '{}' is in more than one group: {}",
+                package_id,
+                groups.join(", "),
+            ),
+            GroupError::CrossGroupDependency {
+                group,
+                member,
+                other_group,
+                hakari_package,
+            } => write!(
+                f,
+                "package '{}' in group '{}' depends on Hakari package '{}' of group '{}'",
+                member, group, hakari_package, other_group,
+            ),
+        }
+    }
+}
+
+impl error::Error for GroupError {}
+
+/// Checks that groups are disjoint, and that no group depends on another group's Hakari package.
+pub(crate) fn check_groups<'g>(
+    graph: &'g PackageGraph,
+    groups: &BTreeMap<String, HakariGroup<'g>>,
+) -> Result<(), GroupError> {
+    let mut package_groups: HashMap<&'g PackageId, Vec<&str>> = HashMap::new();
+    for (name, group) in groups {
+        for package_id in group.members().chain(Some(group.hakari_package.id())) {
+            package_groups.entry(package_id).or_default().push(name);
+        }
+    }
+    // Iterate over groups in order to produce deterministic errors.
+    for group in groups.values() {
+        for package_id in group.members().chain(Some(group.hakari_package.id())) {
+            let names = &package_groups[package_id];
+            if names.len() > 1 {
+                return Err(GroupError::MultipleGroups {
+                    package_id: package_id.clone(),
+                    groups: names.iter().map(|name| name.to_string()).collect(),
+                });
+            }
+        }
+    }
+
+    for (name, group) in groups {
+        // Dev-dependencies are only built for the members themselves, not for their dependencies.
+        let members = &group.members;
+        let reachable = graph
+            .query_forward(group.members())
+            .expect("members are valid package IDs")
+            .resolve_with_fn(|_, link| !link.dev_only() || members.contains(link.from().id()));
+
+        for (other_name, other_group) in groups {
+            if name == other_name {
+                continue;
+            }
+            let other_hakari = other_group.hakari_package.id();
+            if !reachable
+                .contains(other_hakari)
+                .expect("hakari package is a valid ID")
+            {
+                continue;
+            }
+
+            // Find a member responsible for the dependency, for a more actionable error.
+            let member = group
+                .members()
+                .find(|member| {
+                    graph
+                        .depends_on(member, other_hakari)
+                        .expect("valid package IDs")
+                })
+                .unwrap_or_else(|| group.hakari_package.id());
+            return Err(GroupError::CrossGroupDependency {
+                group: name.clone(),
+                member: member.clone(),
+                other_group: other_name.clone(),
+                hakari_package: other_hakari.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn compute_groups() {
+        let graph = JsonFixture::metadata_guppy_869476c().graph();
+        let workspace = graph.workspace();
+        let id = |name: &str| {
+            workspace
+                .member_by_name(name)
+                .expect("known workspace member")
+                .id()
+        };
+
+        let mut builder = HakariBuilder::new(graph, None).expect("builder constructed");
+        builder
+            .add_group(
+                "tools",
+                id("cargo-compare"),
+                vec![id("cargo-guppy"), id("guppy-cmdlib")],
+            )
+            .expect("group added")
+            .add_group("bench", id("fixture-manager"), vec![id("guppy-benchmarks")])
+            .expect("group added");
+
+        let hakaris = builder.clone().compute_groups().expect("groups are valid");
+        assert_eq!(
+            hakaris.keys().map(|name| name.as_str()).collect::<Vec<_>>(),
+            vec!["bench", "tools"],
+        );
+        for (name, hakari) in &hakaris {
+            assert_eq!(hakari.group_name(), Some(name.as_str()));
+            let group = builder.group(name).expect("group exists");
+            assert_eq!(
+                hakari
+                    .builder()
+                    .hakari_package()
+                    .map(|package| package.id()),
+                Some(group.hakari_package().id()),
+            );
+
+            // Every package in the computed map must be built by a member of the group.
+            let reachable = graph
+                .query_forward(group.members())
+                .expect("valid package IDs")
+                .resolve();
+            for value in hakari.computed_map.values() {
+                for (_, inner_map) in value.inner_maps().iter() {
+                    for (package, _) in inner_map.values().flatten() {
+                        assert!(
+                            group.members.contains(package.id()),
+                            "for group {}, {} is a member",
+                            name,
+                            package.id(),
+                        );
+                    }
+                }
+            }
+            for &(_, package_id) in hakari.computed_map.keys() {
+                assert!(
+                    reachable.contains(package_id).expect("valid package ID"),
+                    "for group {}, {} is reachable from members",
+                    name,
+                    package_id,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn group_errors() {
+        let graph = JsonFixture::metadata_guppy_869476c().graph();
+        let workspace = graph.workspace();
+        let id = |name: &str| {
+            workspace
+                .member_by_name(name)
+                .expect("known workspace member")
+                .id()
+        };
+
+        let mut builder = HakariBuilder::new(graph, None).expect("builder constructed");
+        let third_party = graph
+            .packages()
+            .find(|package| !package.in_workspace())
+            .expect("fixture has third-party packages")
+            .id();
+        match builder.add_group("a", id("cargo-compare"), vec![third_party]) {
+            Err(GroupError::GroupMemberNotInWorkspace(package_id)) => {
+                assert_eq!(&package_id, third_party);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        let unknown = PackageId::new("unknown 0.1.0 (path+file:///unknown)");
+        match builder.add_group("a", &unknown, vec![]) {
+            Err(GroupError::UnknownPackageId(package_id)) => assert_eq!(package_id, unknown),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        builder
+            .add_group("a", id("cargo-compare"), vec![id("guppy-summaries")])
+            .expect("group added")
+            .add_group("b", id("fixture-manager"), vec![id("guppy-summaries")])
+            .expect("group added");
+        match builder.compute_groups() {
+            Err(GroupError::MultipleGroups { package_id, groups }) => {
+                assert_eq!(&package_id, id("guppy-summaries"));
+                assert_eq!(groups, vec!["a", "b"]);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        let mut builder = HakariBuilder::new(graph, None).expect("builder constructed");
+        builder
+            .add_group("a", id("guppy-cmdlib"), vec![id("guppy-summaries")])
+            .expect("group added")
+            .add_group("b", id("fixture-manager"), vec![id("cargo-guppy")])
+            .expect("group added");
+        match builder.compute_groups() {
+            Err(GroupError::CrossGroupDependency {
+                group,
+                member,
+                other_group,
+                hakari_package,
+            }) => {
+                assert_eq!(group, "b");
+                assert_eq!(&member, id("cargo-guppy"));
+                assert_eq!(other_group, "a");
+                assert_eq!(&hakari_package, id("guppy-cmdlib"));
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
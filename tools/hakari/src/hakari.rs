@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    groups::{check_groups, GroupError, HakariGroup},
     toml_out::{write_toml, TomlOptions},
     CargoTomlError, HakariCargoToml, TomlOutError,
 };
@@ -33,6 +34,7 @@ pub struct HakariBuilder<'g, 'a> {
     omitted_packages: HashSet<&'g PackageId>,
     unify_target_host: UnifyTargetHost,
     unify_all: bool,
+    pub(crate) groups: BTreeMap<String, HakariGroup<'g>>,
 }
 
 impl<'g, 'a> HakariBuilder<'g, 'a> {
@@ -69,6 +71,7 @@ impl<'g, 'a> HakariBuilder<'g, 'a> {
             omitted_packages: HashSet::new(),
             unify_target_host: UnifyTargetHost::default(),
             unify_all: false,
+            groups: BTreeMap::new(),
         })
     }
 
@@ -244,9 +247,99 @@ impl<'g, 'a> HakariBuilder<'g, 'a> {
         self.unify_all
     }
 
+    /// Adds a named group of workspace packages with its own Hakari package.
+    ///
+    /// In large workspaces, some sets of packages (for example, packages compiled to WebAssembly
+    /// and packages that make up a server) may never be built together. Unifying features across
+    /// them causes every package to be built with features it doesn't need. Groups partition the
+    /// workspace so that each group gets its own Hakari package, computed only from the builds of
+    /// its members.
+    ///
+    /// The Hakari package is always treated as part of its group, and doesn't need to be listed in
+    /// `members`. If a group with the same name already exists, it is replaced.
+    ///
+    /// Groups are computed through [`compute_groups`](Self::compute_groups), which also checks
+    /// that:
+    /// * every workspace package is in at most one group, and
+    /// * no member of a group depends, directly or transitively, on the Hakari package of another
+    ///   group.
+    ///
+    /// Workspace packages that aren't in any group are not considered by `compute_groups`.
+    ///
+    /// Returns an error if the Hakari package or any members aren't known to the graph, or aren't
+    /// in the workspace.
+    pub fn add_group<'b>(
+        &mut self,
+        name: impl Into<String>,
+        hakari_id: &PackageId,
+        members: impl IntoIterator<Item = &'b PackageId>,
+    ) -> Result<&mut Self, GroupError> {
+        let graph = *self.graph;
+        let workspace_metadata = |package_id: &PackageId| {
+            let package = graph
+                .metadata(package_id)
+                .map_err(|_| GroupError::UnknownPackageId(package_id.clone()))?;
+            if !package.in_workspace() {
+                return Err(GroupError::GroupMemberNotInWorkspace(package_id.clone()));
+            }
+            Ok(package)
+        };
+
+        let hakari_package = workspace_metadata(hakari_id)?;
+        let members = members
+            .into_iter()
+            .map(|package_id| Ok(workspace_metadata(package_id)?.id()))
+            .collect::<Result<_, _>>()?;
+        self.groups.insert(
+            name.into(),
+            HakariGroup {
+                hakari_package,
+                members,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Returns the groups added through [`add_group`](Self::add_group), sorted by name.
+    pub fn groups<'b>(&'b self) -> impl Iterator<Item = (&'b str, &'b HakariGroup<'g>)> + 'b {
+        self.groups
+            .iter()
+            .map(|(name, group)| (name.as_str(), group))
+    }
+
+    /// Returns the group with the given name, or `None` if it wasn't added.
+    pub fn group(&self, name: &str) -> Option<&HakariGroup<'g>> {
+        self.groups.get(name)
+    }
+
     /// Computes the `Hakari` for this builder.
+    ///
+    /// This performs a single computation across the entire workspace, ignoring any groups added
+    /// through [`add_group`](Self::add_group). To compute one `Hakari` per group, use
+    /// [`compute_groups`](Self::compute_groups).
     pub fn compute(self) -> Hakari<'g, 'a> {
-        Hakari::build(self)
+        Hakari::build(self, None)
+    }
+
+    /// Computes one `Hakari` for each group added through [`add_group`](Self::add_group).
+    ///
+    /// Each `Hakari` is computed with its group's Hakari package, considering only Cargo builds of
+    /// the group's members. All other options are shared across groups.
+    ///
+    /// Returns an error if a workspace package is in more than one group, or if a member of a
+    /// group depends on the Hakari package of another group.
+    pub fn compute_groups(self) -> Result<BTreeMap<String, Hakari<'g, 'a>>, GroupError> {
+        check_groups(*self.graph, &self.groups)?;
+
+        Ok(self
+            .groups
+            .iter()
+            .map(|(name, group)| {
+                let mut builder = self.clone();
+                builder.hakari_package = Some(group.hakari_package);
+                (name.clone(), Hakari::build(builder, Some(name.clone())))
+            })
+            .collect())
     }
 
     // ---
@@ -295,6 +388,7 @@ impl<'g, 'a> PartialEq for HakariBuilder<'g, 'a> {
             && self.omitted_packages == other.omitted_packages
             && self.unify_target_host == other.unify_target_host
             && self.unify_all == other.unify_all
+            && self.groups == other.groups
     }
 }
 
@@ -335,6 +429,22 @@ mod summaries {
                 .iter()
                 .map(|summary_id| Ok(graph.metadata_by_summary_id(&summary_id)?.id()))
                 .collect::<Result<HashSet<_>, _>>()?;
+            let workspace = graph.workspace();
+            let groups = summary
+                .groups
+                .iter()
+                .map(|(name, group)| {
+                    let group = HakariGroup {
+                        hakari_package: workspace.member_by_name(&group.hakari_package)?,
+                        members: group
+                            .members
+                            .iter()
+                            .map(|name| Ok(workspace.member_by_name(name)?.id()))
+                            .collect::<Result<_, guppy::Error>>()?,
+                    };
+                    Ok((name.clone(), group))
+                })
+                .collect::<Result<_, guppy::Error>>()?;
 
             Ok(Self {
                 graph: DebugIgnore(graph),
@@ -345,6 +455,7 @@ mod summaries {
                 unify_all: summary.unify_all,
                 platforms,
                 omitted_packages,
+                groups,
             })
         }
     }
@@ -406,6 +517,7 @@ pub struct OutputKey {
 #[non_exhaustive]
 pub struct Hakari<'g, 'a> {
     builder: HakariBuilder<'g, 'a>,
+    group_name: Option<String>,
 
     /// The map built by Hakari of dependencies that need to be unified.
     ///
@@ -425,6 +537,14 @@ impl<'g, 'a> Hakari<'g, 'a> {
         &self.builder
     }
 
+    /// Returns the name of the group this `Hakari` was computed for, or `None` if it was computed
+    /// for the entire workspace.
+    ///
+    /// For more about groups, see [`HakariBuilder::add_group`](HakariBuilder::add_group).
+    pub fn group_name(&self) -> Option<&str> {
+        self.group_name.as_deref()
+    }
+
     /// Reads the existing TOML file for the Hakari package from disk, returning a
     /// `HakariCargoToml`.
     ///
@@ -462,9 +582,12 @@ impl<'g, 'a> Hakari<'g, 'a> {
     // Helper methods
     // ---
 
-    fn build(builder: HakariBuilder<'g, 'a>) -> Self {
+    pub(crate) fn build(builder: HakariBuilder<'g, 'a>, group_name: Option<String>) -> Self {
         let graph = *builder.graph;
-        let computed_map_build = ComputedMapBuild::new(&builder);
+        let members = group_name
+            .as_ref()
+            .map(|name| &builder.groups[name].members);
+        let computed_map_build = ComputedMapBuild::new(&builder, members);
 
         // Collect all the dependencies that need to be unified, by platform and build type.
         let mut map_build: OutputMapBuild<'g> = OutputMapBuild::new(graph);
@@ -568,6 +691,7 @@ impl<'g, 'a> Hakari<'g, 'a> {
 
         Self {
             builder,
+            group_name,
             output_map: map_build.output_map,
             computed_map,
         }
@@ -644,7 +768,10 @@ struct ComputedMapBuild<'g, 'b> {
 }
 
 impl<'g, 'b> ComputedMapBuild<'g, 'b> {
-    fn new(builder: &'b HakariBuilder<'g, '_>) -> Self {
+    fn new(
+        builder: &'b HakariBuilder<'g, '_>,
+        members: Option<&'b BTreeSet<&'g PackageId>>,
+    ) -> Self {
         let platforms_features: Vec<_> = if builder.platforms.is_empty() {
             StandardFeatures::VALUES
                 .iter()
@@ -686,6 +813,15 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
                         // Skip this package since it was omitted.
                         return BTreeMap::new();
                     }
+                    if let Some(members) = members {
+                        // Skip this package since it isn't part of the group being computed. The
+                        // Hakari package is always considered part of its group.
+                        let is_hakari = builder.hakari_package.map(|package| package.id())
+                            == Some(workspace_package.id());
+                        if !is_hakari && !members.contains(workspace_package.id()) {
+                            return BTreeMap::new();
+                        }
+                    }
 
                     let initials = workspace_package
                         .to_package_set()
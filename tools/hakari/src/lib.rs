@@ -140,6 +140,7 @@
 //! * [the version of the Cargo resolver to use](HakariBuilder::set_resolver_version)
 //! * [packages to be omitted from the computation](HakariBuilder::add_omitted_packages)
 //! * [a "verify mode" to ensure that dependency feature sets are correctly unified](HakariBuilder::set_verify_mode)
+//! * [groups of packages with their own `workspace-hack` packages](HakariBuilder::add_group)
 //!
 //! With the optional `summaries` feature, `HakariBuilder` options can be
 //! [read from](HakariBuilder::from_summary) or [written to](HakariBuilder::to_summary)
@@ -197,6 +198,16 @@
 //!
 //! `HakariCargoToml` also supports serializing contents to memory and producing diffs.
 //!
+//! # Verification
+//!
+//! Once the `workspace-hack` package is written out, [`Hakari::verify`] can be used to check
+//! that it actually unifies features. If any third-party dependencies are still built with more
+//! than one feature set, the returned [`VerifyErrors`] lists them along with the workspace
+//! packages responsible. This is useful as a CI check.
+//!
+//! Before adopting a `workspace-hack` package, [`Hakari::build_savings`] can be used to estimate
+//! how many third-party compile units it would save across all workspace builds.
+//!
 //! # Future work
 //!
 //! `hakari` is a work-in-progress and is still missing many core features:
@@ -206,24 +217,28 @@
 //! * Only including a subset of packages in the final result (e.g. unifying core packages like
 //!   `syn` but not any others)
 //! * Automating the creation of `workspace-hack` packages
-//! * Support for alternate registries (depends on
-//!   [Cargo issue #9052](https://github.com/rust-lang/cargo/issues/9052))
 //! * A command-line interface
 //!
 //! These features will be added as time permits.
 
 mod cargo_toml;
+mod groups;
 mod hakari;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
+mod savings;
 #[cfg(feature = "summaries")]
 pub mod summaries;
 mod toml_out;
+mod verify;
 
 pub use crate::{
     cargo_toml::*,
+    groups::{GroupError, HakariGroup},
     hakari::{Hakari, HakariBuilder, UnifyTargetHost},
+    savings::{BuildSavings, PackageSavings},
     toml_out::*,
+    verify::*,
 };
 
 pub mod internals {
@@ -0,0 +1,271 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Estimates of how much a `workspace-hack` package saves.
+
+use crate::hakari::{Hakari, OutputKey};
+use guppy::graph::{cargo::BuildPlatform, PackageMetadata};
+use std::{collections::BTreeSet, convert::TryFrom, fmt, time::Duration};
+
+impl<'g, 'a> Hakari<'g, 'a> {
+    /// Estimates the number of compile units saved by writing out this `Hakari`'s output to the
+    /// `workspace-hack` package.
+    ///
+    /// A compile unit is a distinct combination of a third-party package, a set of features, and
+    /// the platform and host/target it is built on. The number of compile units without the
+    /// `workspace-hack` package is obtained from the simulated Cargo builds of every workspace
+    /// package, as recorded in the [computed map](Hakari::computed_map). With the
+    /// `workspace-hack` package, every dependency in the [output map](Hakari::output_map) is
+    /// built with a single, unified feature set.
+    ///
+    /// This is meant to be called on a `Hakari` computed in generate mode (the default). In
+    /// verify mode, the computed map already accounts for the `workspace-hack` package.
+    pub fn build_savings(&self) -> BuildSavings<'g> {
+        let graph = self.builder().graph();
+
+        let mut units_without = 0;
+        let mut units_with = BTreeSet::new();
+        let mut packages = vec![];
+
+        for (&(platform_idx, package_id), value) in &self.computed_map {
+            for &(build_platform, inner_map) in value.inner_maps().iter() {
+                if inner_map.is_empty() {
+                    continue;
+                }
+                units_without += inner_map.len();
+                units_with.insert((platform_idx, build_platform, package_id));
+                if inner_map.len() > 1 {
+                    packages.push(PackageSavings {
+                        key: OutputKey {
+                            platform_idx,
+                            build_platform,
+                        },
+                        package: graph.metadata(package_id).expect("valid package ID"),
+                        feature_set_count: inner_map.len(),
+                    });
+                }
+            }
+        }
+
+        // The output map may cause some packages to be built on a platform they weren't built on
+        // before, e.g. through UnifyTargetHost::ReplicateTargetAsHost.
+        for (key, inner_map) in &self.output_map {
+            for &package_id in inner_map.keys() {
+                units_with.insert((key.platform_idx, key.build_platform, package_id));
+            }
+        }
+
+        // Sort packages by the number of units saved, most first.
+        packages.sort_by(|a, b| {
+            b.feature_set_count
+                .cmp(&a.feature_set_count)
+                .then_with(|| a.key.cmp(&b.key))
+                .then_with(|| a.package.id().cmp(b.package.id()))
+        });
+
+        BuildSavings {
+            platform_triples: self
+                .builder()
+                .platforms()
+                .iter()
+                .map(|platform| platform.triple().to_string())
+                .collect(),
+            units_without,
+            units_with: units_with.len(),
+            packages,
+        }
+    }
+}
+
+/// An estimate of the compile units saved by a `workspace-hack` package.
+///
+/// Returned by [`Hakari::build_savings`](Hakari::build_savings).
+///
+/// The `Display` implementation produces a human-readable report.
+#[derive(Clone, Debug)]
+pub struct BuildSavings<'g> {
+    platform_triples: Vec<String>,
+    units_without: usize,
+    units_with: usize,
+    packages: Vec<PackageSavings<'g>>,
+}
+
+impl<'g> BuildSavings<'g> {
+    /// Returns the number of distinct third-party compile units across all workspace builds,
+    /// without the `workspace-hack` package.
+    pub fn units_without(&self) -> usize {
+        self.units_without
+    }
+
+    /// Returns the number of distinct third-party compile units across all workspace builds, with
+    /// the `workspace-hack` package.
+    pub fn units_with(&self) -> usize {
+        self.units_with
+    }
+
+    /// Returns the number of compile units saved by the `workspace-hack` package.
+    ///
+    /// This is negative if the `workspace-hack` package causes more compile units to be built than
+    /// it saves, which is possible with some values of
+    /// [`UnifyTargetHost`](crate::UnifyTargetHost).
+    pub fn units_saved(&self) -> isize {
+        self.units_without as isize - self.units_with as isize
+    }
+
+    /// Returns the packages that would no longer be built with different feature sets, sorted by
+    /// the number of compile units saved (most first).
+    pub fn packages(&self) -> &[PackageSavings<'g>] {
+        &self.packages
+    }
+
+    /// Estimates the build time saved, weighted by the given build times.
+    ///
+    /// `build_time` is called with each package in [`packages`](Self::packages), and should return
+    /// the time it takes to build that package once, or `None` if the build time isn't known.
+    /// Packages with unknown build times are not counted.
+    ///
+    /// Returns `None` if the time saved is too large to be represented as a `Duration`.
+    pub fn time_saved(
+        &self,
+        mut build_time: impl FnMut(&PackageMetadata<'g>) -> Option<Duration>,
+    ) -> Option<Duration> {
+        let mut total = Duration::from_secs(0);
+        for savings in &self.packages {
+            let time = match build_time(&savings.package) {
+                Some(time) => time,
+                None => continue,
+            };
+            let units_saved = u32::try_from(savings.units_saved()).ok()?;
+            total = total.checked_add(time.checked_mul(units_saved)?)?;
+        }
+        Some(total)
+    }
+}
+
+impl<'g> fmt::Display for BuildSavings<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "third-party compile units: {} without workspace-hack, {} with workspace-hack ({} saved)",
+            self.units_without,
+            self.units_with,
+            self.units_saved(),
+        )?;
+        if self.packages.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "\npackages no longer built with different feature sets:")?;
+        for savings in &self.packages {
+            let platform_str = match savings.key.platform_idx {
+                Some(idx) => self.platform_triples[idx].as_str(),
+                None => "all platforms",
+            };
+            let build_platform_str = match savings.key.build_platform {
+                BuildPlatform::Target => "target",
+                BuildPlatform::Host => "host",
+            };
+            writeln!(
+                f,
+                "* {} {} on {} ({}): {} feature sets -> 1",
+                savings.package.name(),
+                savings.package.version(),
+                platform_str,
+                build_platform_str,
+                savings.feature_set_count,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A package that would no longer be built with different feature sets, as part of
+/// [`BuildSavings`](BuildSavings).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct PackageSavings<'g> {
+    /// The platform and host/target the package is built on.
+    pub key: OutputKey,
+
+    /// The package.
+    pub package: PackageMetadata<'g>,
+
+    /// The number of feature sets this package is built with, without the `workspace-hack`
+    /// package. With the `workspace-hack` package, it is built with one feature set.
+    pub feature_set_count: usize,
+}
+
+impl<'g> PackageSavings<'g> {
+    /// Returns the number of compile units saved for this package.
+    pub fn units_saved(&self) -> usize {
+        self.feature_set_count - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+    use std::time::Duration;
+
+    #[test]
+    fn build_savings() {
+        for (&name, fixture) in JsonFixture::all_fixtures() {
+            let graph = fixture.graph();
+            let hakari = HakariBuilder::new(graph, None)
+                .expect("builder constructed")
+                .compute();
+            let savings = hakari.build_savings();
+
+            let packages_saved: usize = savings
+                .packages()
+                .iter()
+                .map(|savings| savings.units_saved())
+                .sum();
+            assert!(
+                savings.units_with() <= savings.units_without(),
+                "for fixture {}, default options never add compile units",
+                name
+            );
+            assert!(
+                packages_saved as isize >= savings.units_saved(),
+                "for fixture {}, packages account for all units saved",
+                name
+            );
+            for package_savings in savings.packages() {
+                let inner_map = hakari
+                    .output_map
+                    .get(&package_savings.key)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "for fixture {}, output map has key {:?}",
+                            name, package_savings.key
+                        )
+                    });
+                assert!(
+                    inner_map.contains_key(package_savings.package.id()),
+                    "for fixture {}, output map contains {}",
+                    name,
+                    package_savings.package.id(),
+                );
+            }
+
+            let time_saved = savings.time_saved(|_| Some(Duration::from_secs(1)));
+            assert_eq!(
+                time_saved,
+                Some(Duration::from_secs(packages_saved as u64)),
+                "for fixture {}, time saved matches with unit weights",
+                name
+            );
+            if packages_saved > 0 {
+                assert_eq!(
+                    savings.time_saved(|_| Some(Duration::from_secs(u64::MAX))),
+                    None,
+                    "for fixture {}, overflowing time saved is None",
+                    name
+                );
+            }
+        }
+    }
+}
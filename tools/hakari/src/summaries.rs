@@ -15,7 +15,10 @@ use guppy::{
     TargetSpecError,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeSet, fmt};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
 use toml::Serializer;
 
 /// A `HakariBuilder` in serializable form.
@@ -23,6 +26,7 @@ use toml::Serializer;
 /// Requires the `summaries` feature to be enabled.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
 pub struct HakariBuilderSummary {
     /// The name of the Hakari package in the workspace.
     pub hakari_package: Option<String>,
@@ -47,6 +51,10 @@ pub struct HakariBuilderSummary {
 
     /// The list of omitted packages.
     pub omitted_packages: BTreeSet<SummaryId>,
+
+    /// Groups of workspace packages with their own Hakari packages, keyed by group name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, HakariGroupSummary>,
 }
 
 impl HakariBuilderSummary {
@@ -54,8 +62,7 @@ impl HakariBuilderSummary {
     ///
     /// Requires the `summaries` feature to be enabled.
     ///
-    /// Returns an error if there are any custom platforms. Serializing custom platforms is
-    /// currently unsupported.
+    /// Custom platforms are serialized along with their target information.
     pub fn new(builder: &HakariBuilder<'_, '_>) -> Result<Self, TargetSpecError> {
         Ok(Self {
             hakari_package: builder
@@ -80,6 +87,26 @@ impl HakariBuilderSummary {
                 .collect(),
             unify_target_host: builder.unify_target_host(),
             unify_all: builder.unify_all(),
+            groups: builder
+                .groups()
+                .map(|(name, group)| {
+                    let summary = HakariGroupSummary {
+                        hakari_package: group.hakari_package().name().to_string(),
+                        members: group
+                            .members()
+                            .map(|package_id| {
+                                builder
+                                    .graph()
+                                    .metadata(package_id)
+                                    .expect("valid package ID")
+                                    .name()
+                                    .to_string()
+                            })
+                            .collect(),
+                    };
+                    (name.to_string(), summary)
+                })
+                .collect(),
         })
     }
 
@@ -133,13 +160,26 @@ impl HakariBuilderSummary {
     }
 }
 
+/// A group of workspace packages in a [`HakariBuilderSummary`](HakariBuilderSummary).
+///
+/// Requires the `summaries` feature to be enabled.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct HakariGroupSummary {
+    /// The name of the Hakari package for this group.
+    pub hakari_package: String,
+
+    /// The names of the workspace packages in this group.
+    pub members: BTreeSet<String>,
+}
+
 impl<'g, 'a> HakariBuilder<'g, 'a> {
     /// Converts this `HakariBuilder` to a serializable summary.
     ///
     /// Requires the `summaries` feature to be enabled.
     ///
-    /// Returns an error if there are any custom platforms. Serializing custom platforms is
-    /// currently unsupported.
+    /// Custom platforms are serialized along with their target information.
     pub fn to_summary(&self) -> Result<HakariBuilderSummary, TargetSpecError> {
         HakariBuilderSummary::new(self)
     }
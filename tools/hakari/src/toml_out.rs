@@ -6,15 +6,18 @@
 use crate::hakari::{HakariBuilder, OutputMap};
 #[cfg(feature = "summaries")]
 use crate::summaries::HakariBuilderSummary;
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use cfg_if::cfg_if;
 use guppy::{
-    graph::{cargo::BuildPlatform, ExternalSource, GitReq, PackageMetadata, PackageSource},
+    graph::{
+        cargo::BuildPlatform, feature::FeatureId, ExternalSource, GitReq, PackageGraph,
+        PackageMetadata, PackageSource,
+    },
     PackageId, TargetSpecError, Version,
 };
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     error, fmt,
     fmt::Write,
     hash::{Hash, Hasher},
@@ -26,6 +29,8 @@ use twox_hash::XxHash64;
 pub struct TomlOptions {
     exact_versions: bool,
     absolute_paths: bool,
+    registries: HashMap<String, String>,
+    minimize_features: bool,
     #[cfg(feature = "summaries")]
     builder_summary: bool,
 }
@@ -35,11 +40,15 @@ impl TomlOptions {
     ///
     /// The default settings are:
     /// * do not output exact versions
+    /// * output relative paths
+    /// * output every feature in the unified set
     /// * do not output a summary of builder options
     pub fn new() -> Self {
         Self {
             exact_versions: false,
             absolute_paths: false,
+            registries: HashMap::new(),
+            minimize_features: false,
             #[cfg(feature = "summaries")]
             builder_summary: false,
         }
@@ -91,6 +100,62 @@ impl TomlOptions {
         self
     }
 
+    /// Adds alternate registries, as pairs of registry names and index URLs.
+    ///
+    /// `Cargo.toml` files refer to [alternate registries](https://doc.rust-lang.org/cargo/reference/registries.html)
+    /// by the names they are configured with in `.cargo/config.toml`:
+    ///
+    /// ```toml
+    /// [registries]
+    /// my-registry = { index = "https://my-intranet:8080/git/index" }
+    /// ```
+    ///
+    /// However, Cargo metadata only records the index URL for each package. In order to output
+    /// dependencies fetched from an alternate registry, for example:
+    ///
+    /// ```toml
+    /// my-dependency = { version = "1", registry = "my-registry" }
+    /// ```
+    ///
+    /// the registry must be added through this method. If it isn't,
+    /// [`TomlOutError::UnrecognizedRegistry`](TomlOutError::UnrecognizedRegistry) will be
+    /// returned.
+    pub fn add_registries(
+        &mut self,
+        registries: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> &mut Self {
+        self.registries.extend(
+            registries
+                .into_iter()
+                .map(|(name, url)| (url.into(), name.into())),
+        );
+        self
+    }
+
+    /// If set to true, outputs the smallest list of features that enables the same set of features.
+    ///
+    /// By default, `hakari` outputs every feature enabled for a dependency, even features that are
+    /// implied by other ones. For example, if a crate has:
+    ///
+    /// ```toml
+    /// [features]
+    /// default = ["std"]
+    /// std = []
+    /// ```
+    ///
+    /// then by default `hakari` will output `features = ["default", "std"]`.
+    ///
+    /// If set to true, `hakari` uses the feature graph to drop features implied by other features
+    /// in the list. `"default"` is also dropped, since it is implied by not setting
+    /// `default-features = false`. In the above example, the output would be `features = []`.
+    ///
+    /// This makes the generated TOML less noisy, and less likely to change when upstream crates
+    /// add or reorganize features. The set of features actually built is the same either way.
+    pub fn set_minimize_features(&mut self, minimize_features: bool) -> &mut Self {
+        self.minimize_features = minimize_features;
+        self
+    }
+
     /// If set to true, outputs a summary of the builder options used to generate the `Hakari`, as
     /// TOML comments.
     ///
@@ -158,6 +223,16 @@ pub enum TomlOutError {
         /// The source string that wasn't recognized.
         source: String,
     },
+
+    /// A dependency was fetched from an alternate registry that wasn't added through
+    /// [`TomlOptions::add_registries`](TomlOptions::add_registries).
+    UnrecognizedRegistry {
+        /// The package ID that Hakari tried to write out a dependency line for.
+        package_id: PackageId,
+
+        /// The index URL of the registry that wasn't recognized.
+        registry_url: String,
+    },
 }
 
 impl From<TargetSpecError> for TomlOutError {
@@ -192,6 +267,14 @@ impl fmt::Display for TomlOutError {
                 "for external dependency '{}', unrecognized external source {}",
                 package_id, source,
             ),
+            TomlOutError::UnrecognizedRegistry {
+                package_id,
+                registry_url,
+            } => write!(
+                f,
+                "for dependency '{}', unrecognized registry at URL {}",
+                package_id, registry_url,
+            ),
         }
     }
 }
@@ -203,9 +286,9 @@ impl error::Error for TomlOutError {
             #[cfg(feature = "summaries")]
             TomlOutError::Toml { err, .. } => Some(err),
             TomlOutError::FmtWrite(err) => Some(err),
-            TomlOutError::PathWithoutHakari { .. } | TomlOutError::UnrecognizedExternal { .. } => {
-                None
-            }
+            TomlOutError::PathWithoutHakari { .. }
+            | TomlOutError::UnrecognizedExternal { .. }
+            | TomlOutError::UnrecognizedRegistry { .. } => None,
         }
     }
 }
@@ -267,93 +350,19 @@ pub(crate) fn write_toml(
                 (dep.name().into(), "".into())
             };
 
-            let source = dep.source();
-            let source_kv = if source.is_crates_io() {
-                format!(
-                    "version = \"{}\", ",
-                    VersionDisplay::new(dep.version(), options.exact_versions)
-                )
-            } else {
-                match source {
-                    PackageSource::Workspace(path) | PackageSource::Path(path) => {
-                        // PackageSource::Workspace shouldn't be possible unless the Hakari map
-                        // was fiddled with. Regardless, we can handle it fine.
-                        let path_out = if options.absolute_paths {
-                            // TODO: canonicalize paths here, removing .. etc? tricky if the path is
-                            // missing (as in tests)
-                            builder.graph().workspace().root().join(path)
-                        } else {
-                            let hakari_path =
-                                hakari_path.ok_or_else(|| TomlOutError::PathWithoutHakari {
-                                    package_id: dep.id().clone(),
-                                    rel_path: path.to_path_buf(),
-                                })?;
-                            let rel_path = pathdiff::diff_paths(path, hakari_path)
-                                .expect("both hakari_path and path are relative");
-                            Utf8PathBuf::from_path_buf(rel_path)
-                                .expect("both path and hakari_path are UTF-8 so this is as well")
-                        };
-
-                        let path_str = path_out.as_str();
-                        cfg_if! {
-                            if #[cfg(windows)] {
-                                // TODO: is replacing \\ with / totally safe on Windows? Might run
-                                // into issues with UNC paths.
-                                let path_str = path_str.replace("\\", "/");
-                                format!("path = \"{}\", ", path_str)
-                            } else {
-                                format!("path = \"{}\", ", path_str)
-                            }
-                        }
-                    }
-                    PackageSource::External(s) => {
-                        let external = source.parse_external().ok_or_else(|| {
-                            TomlOutError::UnrecognizedExternal {
-                                package_id: dep.id().clone(),
-                                source: s.to_string(),
-                            }
-                        })?;
-                        match external {
-                            ExternalSource::Git {
-                                repository, req, ..
-                            } => {
-                                let mut out = String::new();
-                                write!(out, "git = \"{}\", ", repository)?;
-                                match req {
-                                    GitReq::Branch(branch) => {
-                                        write!(out, "branch = \"{}\", ", branch)?
-                                    }
-                                    GitReq::Tag(tag) => write!(out, "tag = \"{}\", ", tag)?,
-                                    GitReq::Rev(rev) => write!(out, "rev = \"{}\", ", rev)?,
-                                    GitReq::Default => {}
-                                    _ => {
-                                        return Err(TomlOutError::UnrecognizedExternal {
-                                            package_id: dep.id().clone(),
-                                            source: s.to_string(),
-                                        });
-                                    }
-                                };
-                                out
-                            }
-                            _ => {
-                                // Can't handle non-crates.io registries at the moment: requires
-                                // a fix to https://github.com/rust-lang/cargo/issues/9052.
-                                return Err(TomlOutError::UnrecognizedExternal {
-                                    package_id: dep.id().clone(),
-                                    source: s.to_string(),
-                                });
-                            }
-                        }
-                    }
-                }
-            };
+            let source_kv = make_source_kv(builder, hakari_path, dep, options)?;
 
             let default_features_kv = if all_features.contains("default") {
                 ""
             } else {
                 "default-features = false, "
             };
-            let all_features: Vec<_> = all_features
+            let output_features = if options.minimize_features {
+                Cow::Owned(minimize_features(builder.graph(), dep.id(), all_features))
+            } else {
+                Cow::Borrowed(all_features)
+            };
+            let all_features: Vec<_> = output_features
                 .iter()
                 .map(|feature| format!("\"{}\"", feature))
                 .collect();
@@ -372,6 +381,196 @@ pub(crate) fn write_toml(
     Ok(())
 }
 
+/// Returns the key-value pairs that describe where `dep` comes from, e.g. `version = "1", `.
+fn make_source_kv(
+    builder: &HakariBuilder<'_, '_>,
+    hakari_path: Option<&Utf8Path>,
+    dep: &PackageMetadata<'_>,
+    options: &TomlOptions,
+) -> Result<String, TomlOutError> {
+    // A package that replaces a registry dependency through [patch] must be referred to the same
+    // way its dependents refer to it: through the registry being patched. Cargo then applies the
+    // patch to the Hakari package's dependency as well.
+    if let Some(registry) = patched_registry(dep) {
+        return make_registry_kv(dep, registry, options);
+    }
+
+    let source = dep.source();
+    match source {
+        PackageSource::Workspace(path) | PackageSource::Path(path) => {
+            // PackageSource::Workspace shouldn't be possible unless the Hakari map was fiddled
+            // with. Regardless, we can handle it fine.
+            let path_out = if options.absolute_paths {
+                // TODO: canonicalize paths here, removing .. etc? tricky if the path is missing
+                // (as in tests)
+                builder.graph().workspace().root().join(path)
+            } else {
+                let hakari_path = hakari_path.ok_or_else(|| TomlOutError::PathWithoutHakari {
+                    package_id: dep.id().clone(),
+                    rel_path: path.to_path_buf(),
+                })?;
+                let rel_path = pathdiff::diff_paths(path, hakari_path)
+                    .expect("both hakari_path and path are relative");
+                Utf8PathBuf::from_path_buf(rel_path)
+                    .expect("both path and hakari_path are UTF-8 so this is as well")
+            };
+
+            let path_str = path_out.as_str();
+            cfg_if! {
+                if #[cfg(windows)] {
+                    // TODO: is replacing \\ with / totally safe on Windows? Might run into issues
+                    // with UNC paths.
+                    let path_str = path_str.replace("\\", "/");
+                    Ok(format!("path = \"{}\", ", path_str))
+                } else {
+                    Ok(format!("path = \"{}\", ", path_str))
+                }
+            }
+        }
+        PackageSource::External(s) => {
+            let unrecognized = || TomlOutError::UnrecognizedExternal {
+                package_id: dep.id().clone(),
+                source: s.to_string(),
+            };
+            match source.parse_external().ok_or_else(unrecognized)? {
+                ExternalSource::Registry(registry) => make_registry_kv(dep, registry, options),
+                ExternalSource::Git {
+                    repository, req, ..
+                } => {
+                    let mut out = String::new();
+                    write!(out, "git = \"{}\", ", repository)?;
+                    match req {
+                        GitReq::Branch(branch) => write!(out, "branch = \"{}\", ", branch)?,
+                        GitReq::Tag(tag) => write!(out, "tag = \"{}\", ", tag)?,
+                        GitReq::Rev(rev) => write!(out, "rev = \"{}\", ", rev)?,
+                        GitReq::Default => {}
+                        _ => return Err(unrecognized()),
+                    };
+                    Ok(out)
+                }
+                _ => Err(unrecognized()),
+            }
+        }
+    }
+}
+
+/// Returns the key-value pairs for a dependency fetched from the given registry URL.
+fn make_registry_kv(
+    dep: &PackageMetadata<'_>,
+    registry: &str,
+    options: &TomlOptions,
+) -> Result<String, TomlOutError> {
+    let version_kv = format!(
+        "version = \"{}\", ",
+        VersionDisplay::new(dep.version(), options.exact_versions)
+    );
+    if registry == CRATES_IO_URL {
+        return Ok(version_kv);
+    }
+
+    // Cargo.toml refers to alternate registries by name, but Cargo metadata only has their URLs.
+    match options.registries.get(registry) {
+        Some(name) => Ok(format!(
+            "{}registry = {}, ",
+            version_kv,
+            toml_basic_string(name)
+        )),
+        None => Err(TomlOutError::UnrecognizedRegistry {
+            package_id: dep.id().clone(),
+            registry_url: registry.to_string(),
+        }),
+    }
+}
+
+/// Returns `s` as a TOML basic string, with quotes around it.
+fn toml_basic_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => {
+                write!(out, "\\u{:04X}", c as u32).expect("writing to a String never fails")
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// If `dep` provably replaces a registry package through `[patch]`, returns the URL of the
+/// registry being patched.
+fn patched_registry<'g>(dep: &PackageMetadata<'g>) -> Option<&'g str> {
+    if let Some(ExternalSource::Registry(_)) = dep.source().parse_external() {
+        return None;
+    }
+
+    // Packages published to a registry can only declare dependencies on packages from registries:
+    // registries reject Git dependencies, and path dependencies are stripped on publish. If such
+    // a package resolves to a Git or path package, the only way that can happen is through a
+    // [patch] of the registry it was declared against. Dependencies from workspace and path
+    // packages are not considered, since those may name Git and path sources directly.
+    let registries: BTreeSet<_> = dep
+        .reverse_direct_links()
+        .filter_map(|link| match link.from().source().parse_external() {
+            Some(ExternalSource::Registry(registry)) => Some(registry),
+            _ => None,
+        })
+        .collect();
+    // In the rare case that the same package patches several registries, prefer crates.io and
+    // otherwise pick one deterministically.
+    if registries.contains(CRATES_IO_URL) {
+        Some(CRATES_IO_URL)
+    } else {
+        registries.into_iter().next()
+    }
+}
+
+/// The URL of the crates.io registry, as present in `ExternalSource::Registry`.
+const CRATES_IO_URL: &str = "https://github.com/rust-lang/crates.io-index";
+
+/// Returns the smallest subset of `features` that, along with `default` if present, implies every
+/// feature in `features`.
+fn minimize_features<'g>(
+    graph: &'g PackageGraph,
+    package_id: &'g PackageId,
+    features: &BTreeSet<&'g str>,
+) -> BTreeSet<&'g str> {
+    let feature_graph = graph.feature_graph();
+    let mut minimal = features.clone();
+    for &feature in features {
+        if feature == "default" {
+            // Default features are handled through default-features, below.
+            continue;
+        }
+        let rest = minimal
+            .iter()
+            .filter(|&&other| other != feature)
+            .map(|&other| FeatureId::new(package_id, other));
+        // Only follow links within this package: links to features in other packages can't
+        // enable any further features within this one.
+        let implied = feature_graph
+            .query_forward(rest)
+            .expect("valid feature IDs")
+            .resolve_with_fn(|_, _| false);
+        if implied
+            .contains(FeatureId::new(package_id, feature))
+            .expect("valid feature ID")
+        {
+            minimal.remove(feature);
+        }
+    }
+
+    // "default" is implied by not setting default-features = false.
+    minimal.remove("default");
+    minimal
+}
+
 /// Generate a unique, stable package name from the metadata.
 fn make_hashed_name(dep: &PackageMetadata<'_>) -> String {
     // Use a fixed seed to ensure stable hashes.
@@ -427,7 +626,13 @@ impl<'a> fmt::Display for VersionDisplay<'a> {
 mod tests {
     use super::*;
     use fixtures::json::JsonFixture;
-    use guppy::{graph::DependencyDirection, VersionReq};
+    use guppy::{
+        graph::{
+            cargo::{CargoOptions, InitialsPlatform},
+            DependencyDirection,
+        },
+        VersionReq,
+    };
     use std::collections::{btree_map::Entry, BTreeMap};
 
     #[test]
@@ -504,4 +709,126 @@ mod tests {
             }
         }
     }
+
+    /// Ensure that minimizing features doesn't change the set of features that are built.
+    #[test]
+    fn minimize_features_same_cargo_set() {
+        for (&name, fixture) in JsonFixture::all_fixtures() {
+            let graph = fixture.graph();
+            let feature_graph = graph.feature_graph();
+            let mut builder = HakariBuilder::new(graph, None).expect("builder constructed");
+            builder.set_unify_all(true);
+            let hakari = builder.compute();
+
+            for (key, vals) in &hakari.output_map {
+                let initials_platform = match key.build_platform {
+                    BuildPlatform::Target => InitialsPlatform::Standard,
+                    BuildPlatform::Host => InitialsPlatform::Host,
+                };
+                let mut cargo_opts = CargoOptions::new();
+                cargo_opts
+                    .set_include_dev(false)
+                    .set_initials_platform(initials_platform);
+
+                let full_ids = vals.iter().flat_map(|(&package_id, (_, features))| {
+                    features
+                        .iter()
+                        .map(move |&feature| FeatureId::new(package_id, feature))
+                });
+                let minimal_ids = vals.iter().flat_map(|(&package_id, (_, features))| {
+                    let mut minimal = minimize_features(graph, package_id, features);
+                    assert!(
+                        minimal.is_subset(features),
+                        "for fixture {}, minimized features are a subset",
+                        name
+                    );
+                    if features.contains("default") {
+                        minimal.insert("default");
+                    }
+                    minimal
+                        .into_iter()
+                        .map(move |feature| FeatureId::new(package_id, feature))
+                });
+
+                let full_set = feature_graph
+                    .resolve_ids(full_ids)
+                    .expect("valid feature IDs")
+                    .into_cargo_set(&cargo_opts)
+                    .expect("cargo set computed");
+                let minimal_set = feature_graph
+                    .resolve_ids(minimal_ids)
+                    .expect("valid feature IDs")
+                    .into_cargo_set(&cargo_opts)
+                    .expect("cargo set computed");
+                assert_eq!(
+                    full_set.target_features(),
+                    minimal_set.target_features(),
+                    "for fixture {}, key {:?}, target features match",
+                    name,
+                    key,
+                );
+                assert_eq!(
+                    full_set.host_features(),
+                    minimal_set.host_features(),
+                    "for fixture {}, key {:?}, host features match",
+                    name,
+                    key,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn registries_and_patches() {
+        let graph = PackageGraph::from_json(REGISTRIES_JSON).expect("valid metadata");
+        let mut builder = HakariBuilder::new(&graph, None).expect("builder constructed");
+        builder.set_unify_all(true);
+        let hakari = builder.compute();
+
+        let err = hakari
+            .to_toml_string(&TomlOptions::new())
+            .expect_err("alternate registry isn't known");
+        match err {
+            TomlOutError::UnrecognizedRegistry {
+                package_id,
+                registry_url,
+            } => {
+                assert_eq!(package_id.repr(), ALT_DEP_ID);
+                assert_eq!(registry_url, "https://example.com/my-registry-index");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+
+        let mut options = TomlOptions::new();
+        options.add_registries(vec![(
+            "my-registry",
+            "https://example.com/my-registry-index",
+        )]);
+        let toml = hakari
+            .to_toml_string(&options)
+            .expect("alternate registry is known");
+        assert_eq!(
+            toml,
+            "[target.'cfg(all())'.dependencies]\n\
+             alt-dep = { version = \"1\", registry = \"my-registry\", default-features = false, features = [] }\n\
+             patched-dep = { version = \"2\", default-features = false, features = [] }\n\
+             reg-dep = { version = \"0.3\", default-features = false, features = [] }\n\
+             \n",
+        );
+    }
+
+    #[test]
+    fn basic_string_escapes() {
+        assert_eq!(toml_basic_string("my-registry"), "\"my-registry\"");
+        assert_eq!(
+            toml_basic_string("a\"b\\c\nd\u{7}"),
+            "\"a\\\"b\\\\c\\nd\\u0007\"",
+        );
+    }
+
+    const ALT_DEP_ID: &str = "alt-dep 1.2.0 (registry+https://example.com/my-registry-index)";
+
+    /// A workspace with a dependency from an alternate registry, and a crates.io dependency that
+    /// is patched to come from a Git repository.
+    static REGISTRIES_JSON: &str = include_str!("../../../fixtures/small/metadata_registries.json");
 }
@@ -0,0 +1,245 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Verification of `workspace-hack` packages.
+
+use crate::hakari::{ComputedInnerMap, Hakari, OutputKey};
+use guppy::graph::{cargo::BuildPlatform, feature::StandardFeatures, PackageMetadata};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    error, fmt,
+};
+
+impl<'g, 'a> Hakari<'g, 'a> {
+    /// Verifies that the current contents of the Hakari package unify all features.
+    ///
+    /// This runs the builder in [verify mode](crate::HakariBuilder::set_verify_mode), using the Hakari
+    /// package as it currently exists in the `PackageGraph`, and checks that every third-party
+    /// dependency is built with a single feature set per platform.
+    ///
+    /// Returns `Ok(())` if features are unified, or a [`VerifyErrors`](VerifyErrors) listing
+    /// every dependency that is built more than one way, along with the workspace packages
+    /// responsible for each feature set.
+    ///
+    /// If this instance was already computed in verify mode, its results are reused. Otherwise, a
+    /// new computation is performed.
+    pub fn verify(&self) -> Result<(), VerifyErrors<'g>> {
+        let hakari = if self.builder().verify_mode() {
+            Cow::Borrowed(self)
+        } else {
+            let mut builder = self.builder().clone();
+            builder.set_verify_mode(true);
+            Cow::Owned(builder.compute())
+        };
+
+        let graph = hakari.builder().graph();
+        let errors: Vec<_> = hakari
+            .computed_map
+            .iter()
+            .flat_map(|(&(platform_idx, package_id), value)| {
+                value
+                    .inner_maps()
+                    .iter()
+                    .filter(|(_, inner_map)| inner_map.len() > 1)
+                    .map(|&(build_platform, inner_map)| VerifyError {
+                        key: OutputKey {
+                            platform_idx,
+                            build_platform,
+                        },
+                        dependency: graph.metadata(package_id).expect("valid package ID"),
+                        feature_sets: inner_map.clone(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let builder = hakari.builder();
+            Err(VerifyErrors {
+                hakari_package: builder.hakari_package().copied(),
+                platform_triples: builder
+                    .platforms()
+                    .iter()
+                    .map(|platform| platform.triple().to_string())
+                    .collect(),
+                errors,
+            })
+        }
+    }
+}
+
+/// A list of dependencies that a Hakari package does not unify features for.
+///
+/// Returned by [`Hakari::verify`](Hakari::verify).
+///
+/// The `Display` implementation produces a report suitable for showing to users, for example as
+/// the output of a CI check.
+#[derive(Clone, Debug)]
+pub struct VerifyErrors<'g> {
+    hakari_package: Option<PackageMetadata<'g>>,
+    platform_triples: Vec<String>,
+    errors: Vec<VerifyError<'g>>,
+}
+
+impl<'g> VerifyErrors<'g> {
+    /// Returns the Hakari package that was verified, or `None` if no Hakari package was specified.
+    pub fn hakari_package(&self) -> Option<&PackageMetadata<'g>> {
+        self.hakari_package.as_ref()
+    }
+
+    /// Returns the list of dependencies built with more than one feature set.
+    ///
+    /// The list is sorted by platform index and package ID, and is never empty.
+    pub fn errors(&self) -> &[VerifyError<'g>] {
+        &self.errors
+    }
+}
+
+impl<'g> fmt::Display for VerifyErrors<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.hakari_package {
+            Some(package) => write!(f, "Hakari package '{}'", package.name())?,
+            None => write!(f, "no Hakari package was specified, so the workspace")?,
+        }
+        writeln!(
+            f,
+            " does not unify features for {} dependencies:",
+            self.errors.len()
+        )?;
+
+        for error in &self.errors {
+            let platform_str = match error.key.platform_idx {
+                Some(idx) => self.platform_triples[idx].as_str(),
+                None => "all platforms",
+            };
+            let build_platform_str = match error.key.build_platform {
+                BuildPlatform::Target => "target",
+                BuildPlatform::Host => "host",
+            };
+            writeln!(
+                f,
+                "\n* '{}' on {} ({}) is built with {} feature sets:",
+                error.dependency.id(),
+                platform_str,
+                build_platform_str,
+                error.feature_sets.len(),
+            )?;
+
+            for (features, packages) in &error.feature_sets {
+                let features: Vec<_> = features
+                    .iter()
+                    .map(|feature| format!("\"{}\"", feature))
+                    .collect();
+                // Group by workspace package so that each one is only mentioned once.
+                let mut by_package: BTreeMap<&str, BTreeSet<StandardFeatures>> = BTreeMap::new();
+                for (package, feature_filter) in packages {
+                    by_package
+                        .entry(package.name())
+                        .or_default()
+                        .insert(*feature_filter);
+                }
+                let packages: Vec<_> = by_package
+                    .into_iter()
+                    .map(|(name, feature_filters)| {
+                        if feature_filters.len() == StandardFeatures::VALUES.len() {
+                            return name.to_string();
+                        }
+                        let filters: Vec<_> = feature_filters
+                            .into_iter()
+                            .map(|feature_filter| match feature_filter {
+                                StandardFeatures::None => "no",
+                                StandardFeatures::Default => "default",
+                                StandardFeatures::All => "all",
+                            })
+                            .collect();
+                        format!("{} ({} features)", name, filters.join(", "))
+                    })
+                    .collect();
+                writeln!(
+                    f,
+                    "  - [{}]: from {}",
+                    features.join(", "),
+                    packages.join(", ")
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'g> error::Error for VerifyErrors<'g> {}
+
+/// A single dependency built with more than one feature set, as part of
+/// [`VerifyErrors`](VerifyErrors).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct VerifyError<'g> {
+    /// The platform and host/target the dependency is built on.
+    pub key: OutputKey,
+
+    /// The dependency that is built with more than one feature set.
+    pub dependency: PackageMetadata<'g>,
+
+    /// The feature sets the dependency is built with, along with the workspace packages and
+    /// selected features that cause each feature set to be built.
+    ///
+    /// This map always has at least two elements.
+    pub feature_sets: ComputedInnerMap<'g>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+
+    /// Ensure that every dependency that fails verification is present in the output map.
+    #[test]
+    fn verify_errors_in_output_map() {
+        for (&name, fixture) in JsonFixture::all_fixtures() {
+            let graph = fixture.graph();
+            let hakari = HakariBuilder::new(graph, None)
+                .expect("builder constructed")
+                .compute();
+
+            let errors = match hakari.verify() {
+                Ok(()) => {
+                    assert!(
+                        hakari.output_map.is_empty(),
+                        "for fixture {}, verify succeeded => output map is empty",
+                        name
+                    );
+                    continue;
+                }
+                Err(errors) => errors,
+            };
+
+            let report = format!("{}", errors);
+            for error in errors.errors() {
+                assert!(
+                    error.feature_sets.len() > 1,
+                    "for fixture {}, error has multiple feature sets",
+                    name
+                );
+                let inner_map = hakari.output_map.get(&error.key).unwrap_or_else(|| {
+                    panic!("for fixture {}, output map has key {:?}", name, error.key)
+                });
+                assert!(
+                    inner_map.contains_key(error.dependency.id()),
+                    "for fixture {}, output map contains {}",
+                    name,
+                    error.dependency.id(),
+                );
+                assert!(
+                    report.contains(&error.dependency.id().to_string()),
+                    "for fixture {}, report mentions {}",
+                    name,
+                    error.dependency.id(),
+                );
+            }
+        }
+    }
+}